@@ -0,0 +1,829 @@
+//! Generate the GL and GLX entry points from the Khronos XML API registry
+//! (`gl.xml` and `glx.xml`, vendored in `registry/`).
+//!
+//! For GL, this produces (see `src/gl/api_1_0.rs` for how it's used):
+//! * `Enum` (with aliases for names sharing a value) and the bitfield constants
+//! * a `commands!` invocation for all the commands that can be compiled into
+//!   display lists, and have parameters `commands!` knows how to convert
+//!   (with per-vertex attribute commands converted to a canonical form)
+//! * the `EntryPoints` trait for all other commands, which have to be
+//!   implemented by hand (defaulting to reporting `GL_INVALID_OPERATION`),
+//!   and the exported functions forwarding to those implementations
+//! * `proc_address`, mapping command names to entry points
+//!
+//! For GLX, only the last two (see `src/glx/api.rs`).
+
+use roxmltree::{Document, Node};
+use std::collections::{HashMap, HashSet};
+use std::env;
+use std::fmt::Write;
+use std::fs;
+use std::path::Path;
+
+/// The GL features (i.e. versions) commands and enums are taken from.
+const GL_FEATURES: &[&str] = &["GL_VERSION_1_0", "GL_VERSION_1_1"];
+
+/// Commands from later GL versions, that must be exported regardless.
+const GL_EXTRA_COMMANDS: &[&str] = &[
+    // GLU links against this, even if it doesn't always use it.
+    "glTexImage3D",
+];
+
+/// Lengths for `COMPSIZE(...)` array parameters (as Rust expressions, which
+/// can refer to the already-converted parameters before them), for commands
+/// which can't become `Command`s otherwise.
+const GL_COMPSIZE_LENGTHS: &[(&str, &str)] = &[
+    ("glMaterialfv", "light::material_params_len(pname)?"),
+    ("glMaterialiv", "light::material_params_len(pname)?"),
+    ("glLightfv", "light::light_params_len(pname)?"),
+    ("glLightiv", "light::light_params_len(pname)?"),
+    ("glLightModelfv", "light::light_model_params_len(pname)?"),
+    ("glLightModeliv", "light::light_model_params_len(pname)?"),
+    ("glFogfv", "state::fog_params_len(pname)?"),
+    ("glFogiv", "state::fog_params_len(pname)?"),
+    ("glTexParameterfv", "texture::params_len(pname)?"),
+    ("glTexParameteriv", "texture::params_len(pname)?"),
+    ("glTexEnvfv", "texture::env_params_len(pname)?"),
+    ("glTexEnviv", "texture::env_params_len(pname)?"),
+    ("glTexGendv", "tex_gen::params_len(pname)?"),
+    ("glTexGenfv", "tex_gen::params_len(pname)?"),
+    ("glTexGeniv", "tex_gen::params_len(pname)?"),
+];
+
+/// Parameters which need a more specific `commands!` type than the one
+/// implied by their registry type (e.g. `clampf` instead of `float`, as
+/// newer registry versions have replaced `GLclampf` with `GLfloat`).
+/// For `const void *` parameters (and those of unknown length, i.e. with
+/// `COMPSIZE()` lengths), the whole (array) type is replaced.
+const GL_PARAM_TYPES: &[(&str, &str, &str)] = &[
+    ("glAlphaFunc", "ref", "clampf"),
+    ("glClearColor", "red", "clampf"),
+    ("glClearColor", "green", "clampf"),
+    ("glClearColor", "blue", "clampf"),
+    ("glClearColor", "alpha", "clampf"),
+    ("glClearDepth", "depth", "clampd"),
+    ("glDepthRange", "n", "clampd"),
+    ("glDepthRange", "f", "clampd"),
+    ("glPrioritizeTextures", "priorities", "clampf"),
+    ("glPolygonStipple", "mask", "(stipple)"),
+    ("glCallLists", "lists", "(lists n r#type)"),
+    (
+        "glTexImage1D",
+        "pixels",
+        "(pixels target width 1 format r#type)",
+    ),
+    (
+        "glTexImage2D",
+        "pixels",
+        "(pixels target width height format r#type)",
+    ),
+    (
+        "glTexSubImage1D",
+        "pixels",
+        "(pixels target width 1 format r#type)",
+    ),
+    (
+        "glTexSubImage2D",
+        "pixels",
+        "(pixels target width height format r#type)",
+    ),
+];
+
+/// Commands which could otherwise become `Command`s, but which depend on
+/// client state (i.e. vertex arrays) at the time they're called, even when
+/// compiled into display lists, so they have to be implemented by hand.
+const GL_CLIENT_STATE_COMMANDS: &[&str] = &["glDrawArrays"];
+
+/// Families of per-vertex attribute commands (e.g. `glColor3ub`), which all
+/// get converted to one canonical command (e.g. `glColor4f`), described by
+/// the name prefix, the canonical command, the defaults for any components
+/// missing from the other commands, and whether integers are normalized
+/// (i.e. mapped to `[0, 1]` or `[-1, 1]`) or just converted.
+const GL_CANONICAL_COMMANDS: &[(&str, &str, &[&str], bool)] = &[
+    (
+        "glVertex",
+        "glVertex4f",
+        &["0.0", "0.0", "0.0", "1.0"],
+        false,
+    ),
+    ("glColor", "glColor4f", &["0.0", "0.0", "0.0", "1.0"], true),
+    ("glNormal", "glNormal3f", &["0.0", "0.0", "0.0"], true),
+    (
+        "glTexCoord",
+        "glTexCoord4f",
+        &["0.0", "0.0", "0.0", "1.0"],
+        false,
+    ),
+    ("glIndex", "glIndexf", &["0.0"], false),
+    (
+        "glRasterPos",
+        "glRasterPos4f",
+        &["0.0", "0.0", "0.0", "1.0"],
+        false,
+    ),
+];
+
+/// The GLX features (i.e. versions) and extensions commands are taken from.
+const GLX_FEATURES: &[&str] = &[
+    "GLX_VERSION_1_0",
+    "GLX_VERSION_1_1",
+    "GLX_VERSION_1_2",
+    "GLX_VERSION_1_3",
+];
+const GLX_EXTENSIONS: &[&str] = &["GLX_ARB_get_proc_address"];
+
+/// A C type, as found in the registry (e.g. `const GLfloat *`).
+struct CType {
+    base: String,
+    is_const: bool,
+    pointers: usize,
+}
+
+impl CType {
+    fn parse(s: &str) -> Self {
+        let pointers = s.matches('*').count();
+        let mut is_const = false;
+        let base = s
+            .replace('*', " ")
+            .split_whitespace()
+            .filter(|&word| {
+                if word == "const" {
+                    is_const = true;
+                }
+                word != "const"
+            })
+            .collect::<Vec<_>>()
+            .join(" ");
+        CType {
+            base,
+            is_const,
+            pointers,
+        }
+    }
+
+    fn is_void(&self) -> bool {
+        self.base == "void" && self.pointers == 0
+    }
+
+    fn to_rust(&self) -> String {
+        if self.is_void() {
+            return "()".to_string();
+        }
+        let mut ty = match &self.base[..] {
+            "void" => "std::ffi::c_void",
+
+            "GLenum" | "GLbitfield" | "GLuint" => "u32",
+            "GLboolean" | "GLubyte" => "u8",
+            "GLbyte" => "i8",
+            "GLshort" => "i16",
+            "GLushort" => "u16",
+            "GLint" | "GLsizei" => "i32",
+            "GLfloat" | "GLclampf" => "f32",
+            "GLdouble" | "GLclampd" => "f64",
+
+            "char" => "libc::c_char",
+            "int" => "libc::c_int",
+            "unsigned int" => "libc::c_uint",
+            "unsigned long" => "libc::c_ulong",
+
+            "Bool" => "x11_dl::xlib::Bool",
+            "Display" => "x11_dl::xlib::Display",
+            "Font" => "x11_dl::xlib::Font",
+            "Pixmap" => "x11_dl::xlib::Pixmap",
+            "Window" => "x11_dl::xlib::Window",
+            "XVisualInfo" => "x11_dl::xlib::XVisualInfo",
+
+            "GLXContext" => "x11_dl::glx::GLXContext",
+            "GLXDrawable" => "x11_dl::glx::GLXDrawable",
+            "GLXFBConfig" => "x11_dl::glx::GLXFBConfig",
+            "GLXPbuffer" => "x11_dl::glx::GLXPbuffer",
+            "GLXPixmap" => "x11_dl::glx::GLXPixmap",
+            "GLXWindow" => "x11_dl::glx::GLXWindow",
+            "__GLXextFuncPtr" => "Option<unsafe extern \"C\" fn()>",
+
+            base => panic!("unsupported registry type `{}`", base),
+        }
+        .to_string();
+        for i in 0..self.pointers {
+            // NOTE(eddyb) `const` only ever applies to the innermost pointer.
+            ty = if i == 0 && self.is_const {
+                format!("*const {}", ty)
+            } else {
+                format!("*mut {}", ty)
+            };
+        }
+        ty
+    }
+
+    /// A Rust expression for the "default" value of this type (i.e. `0`,
+    /// or a null pointer), for returning from unimplemented entry points.
+    fn default_value(&self) -> &'static str {
+        match (&self.base[..], self.pointers) {
+            // NOTE(eddyb) these are opaque pointers, despite not using `*`.
+            ("GLXContext", 0) | ("GLXFBConfig", 0) => "std::ptr::null_mut()",
+            (_, 0) => "Default::default()",
+            (_, 1) if self.is_const => "std::ptr::null()",
+            _ => "std::ptr::null_mut()",
+        }
+    }
+
+    /// The type name `commands!` uses, for non-pointer GL types.
+    fn commands_macro_type(&self) -> Option<&'static str> {
+        if self.pointers != 0 {
+            return None;
+        }
+        Some(match &self.base[..] {
+            "GLenum" => "enum",
+            "GLbitfield" => "bitfield",
+            "GLboolean" => "boolean",
+            "GLbyte" => "byte",
+            "GLubyte" => "ubyte",
+            "GLshort" => "short",
+            "GLushort" => "ushort",
+            "GLint" => "int",
+            "GLuint" => "uint",
+            "GLsizei" => "sizei",
+            "GLfloat" => "float",
+            "GLclampf" => "clampf",
+            "GLdouble" => "double",
+            "GLclampd" => "clampd",
+            _ => return None,
+        })
+    }
+}
+
+struct Param {
+    /// The name as it appears in the registry.
+    registry_name: String,
+    /// The name used in Rust (i.e. `snake_case` and not a keyword).
+    name: String,
+    ty: CType,
+    len: Option<String>,
+}
+
+struct Command {
+    name: String,
+    ret: CType,
+    params: Vec<Param>,
+    glx_type: Option<String>,
+    vecequiv: Option<String>,
+}
+
+impl Command {
+    fn parse(node: Node) -> Self {
+        // Concatenate the text of a `<proto>`/`<param>`, up to its `<name>`.
+        let split_name = |node: Node| {
+            let mut ty = String::new();
+            let mut name = None;
+            for child in node.children() {
+                if child.has_tag_name("name") {
+                    name = child.text();
+                } else if name.is_none() {
+                    ty += child.text().unwrap_or("");
+                }
+            }
+            (CType::parse(&ty), name.unwrap().to_string())
+        };
+
+        let mut cmd = None;
+        let mut params = vec![];
+        let mut glx_type = None;
+        let mut vecequiv = None;
+        for child in node.children().filter(|n| n.is_element()) {
+            match child.tag_name().name() {
+                "proto" => cmd = Some(split_name(child)),
+                "param" => {
+                    let (ty, registry_name) = split_name(child);
+                    params.push(Param {
+                        name: rust_ident(&registry_name),
+                        registry_name,
+                        ty,
+                        len: child.attribute("len").map(|s| s.to_string()),
+                    });
+                }
+                "glx" => glx_type = child.attribute("type").map(|s| s.to_string()),
+                "vecequiv" => vecequiv = child.attribute("name").map(|s| s.to_string()),
+                _ => {}
+            }
+        }
+        let (ret, name) = cmd.unwrap();
+        Command {
+            name,
+            ret,
+            params,
+            glx_type,
+            vecequiv,
+        }
+    }
+
+    fn ffi_signature(&self, param_prefix: &str) -> String {
+        let mut s = format!("{}(", self.name);
+        for (i, param) in self.params.iter().enumerate() {
+            if i > 0 {
+                s += ", ";
+            }
+            if param_prefix.is_empty() {
+                s += &param.name;
+            } else {
+                // NOTE(eddyb) the prefix also avoids the need for `r#`.
+                s += param_prefix;
+                s += param.name.trim_start_matches("r#");
+            }
+            s += ": ";
+            s += &param.ty.to_rust();
+        }
+        s += ")";
+        if !self.ret.is_void() {
+            s += " -> ";
+            s += &self.ret.to_rust();
+        }
+        s
+    }
+
+    fn args(&self) -> String {
+        self.params
+            .iter()
+            .map(|param| &param.name[..])
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    fn lint_attrs(&self) -> &'static str {
+        if self.params.len() > 7 {
+            "#[allow(clippy::too_many_arguments)]\n"
+        } else {
+            ""
+        }
+    }
+}
+
+/// Convert a registry name (e.g. `attribList`) to a Rust one (`attrib_list`).
+fn rust_ident(name: &str) -> String {
+    let mut ident = String::new();
+    let mut prev_lower = false;
+    for c in name.chars() {
+        if c.is_ascii_uppercase() && prev_lower {
+            ident.push('_');
+        }
+        prev_lower = c.is_ascii_lowercase() || c.is_ascii_digit();
+        ident.push(c.to_ascii_lowercase());
+    }
+    match &ident[..] {
+        "as" | "box" | "break" | "const" | "continue" | "crate" | "do" | "dyn" | "else"
+        | "enum" | "extern" | "false" | "fn" | "for" | "if" | "impl" | "in" | "let" | "loop"
+        | "match" | "mod" | "move" | "mut" | "pub" | "ref" | "return" | "static" | "struct"
+        | "super" | "trait" | "true" | "type" | "unsafe" | "use" | "where" | "while" => {
+            format!("r#{}", ident)
+        }
+        _ => ident,
+    }
+}
+
+struct Registry<'a> {
+    doc: &'a Document<'a>,
+    commands: HashMap<&'a str, Node<'a, 'a>>,
+}
+
+impl<'a> Registry<'a> {
+    fn new(doc: &'a Document<'a>) -> Self {
+        let commands = doc
+            .root_element()
+            .children()
+            .filter(|n| n.has_tag_name("commands"))
+            .flat_map(|n| n.children().filter(|n| n.has_tag_name("command")))
+            .map(|n| {
+                let proto = n.children().find(|n| n.has_tag_name("proto")).unwrap();
+                let name = proto.children().find(|n| n.has_tag_name("name")).unwrap();
+                (name.text().unwrap(), n)
+            })
+            .collect();
+        Registry { doc, commands }
+    }
+
+    /// Collect the names of all the commands and enums required by `features`
+    /// (`<feature>` or `<extension>` elements), in the order they appear in.
+    fn required(&self, features: &[&str], api: &str) -> (Vec<&'a str>, HashSet<&'a str>) {
+        let mut commands = vec![];
+        let mut enums = HashSet::new();
+        let mut found = 0;
+        for feature in self.doc.descendants() {
+            let is_feature = feature.has_tag_name("feature")
+                && feature.attribute("api") == Some(api)
+                || feature.has_tag_name("extension");
+            if !is_feature || !features.contains(&feature.attribute("name").unwrap_or("")) {
+                continue;
+            }
+            found += 1;
+            for require in feature.children().filter(|n| n.has_tag_name("require")) {
+                for item in require.children().filter(|n| n.is_element()) {
+                    let name = item.attribute("name").unwrap();
+                    match item.tag_name().name() {
+                        "command" if !commands.contains(&name) => commands.push(name),
+                        "enum" => {
+                            enums.insert(name);
+                        }
+                        _ => {}
+                    }
+                }
+            }
+        }
+        assert_eq!(found, features.len(), "missing some of {:?}", features);
+        (commands, enums)
+    }
+
+    fn command(&self, name: &str) -> Command {
+        Command::parse(self.commands[name])
+    }
+}
+
+fn generate_enums(out: &mut String, reg: &Registry, required: &HashSet<&str>) {
+    let mut variants = vec![];
+    let mut aliases = vec![];
+    let mut bits = vec![];
+    let mut seen_names = HashSet::new();
+    let mut variant_by_value = HashMap::<u32, String>::new();
+
+    // NOTE(eddyb) special numbers (e.g. `GL_FALSE`/`GL_NO_ERROR`) come first in
+    // the registry, but they're better off as aliases (e.g. of `GL_POINTS`).
+    let blocks: Vec<_> = reg
+        .doc
+        .root_element()
+        .children()
+        .filter(|n| n.has_tag_name("enums"))
+        .collect();
+    let is_special = |block: &Node| block.attribute("group") == Some("SpecialNumbers");
+    let blocks = blocks
+        .iter()
+        .filter(|block| !is_special(block))
+        .chain(blocks.iter().filter(|block| is_special(block)));
+
+    for block in blocks {
+        let is_bitmask = block.attribute("type") == Some("bitmask");
+        for e in block.children().filter(|n| n.has_tag_name("enum")) {
+            let name = e.attribute("name").unwrap();
+            if !required.contains(name) || !matches!(e.attribute("api"), None | Some("gl")) {
+                continue;
+            }
+            if !seen_names.insert(name) {
+                continue;
+            }
+
+            let value = e.attribute("value").unwrap();
+            let value = match value.strip_prefix("0x") {
+                Some(hex) => u32::from_str_radix(hex, 16),
+                None => value.parse(),
+            }
+            .unwrap();
+
+            let mut name = name.strip_prefix("GL_").unwrap().to_string();
+            if name.starts_with(|c: char| c.is_ascii_digit()) {
+                name.insert(0, '_');
+            }
+
+            if is_bitmask {
+                bits.push((name, value));
+            } else if let Some(variant) = variant_by_value.get(&value) {
+                aliases.push((name, variant.clone()));
+            } else {
+                variant_by_value.insert(value, name.clone());
+                variants.push((name, value));
+            }
+        }
+    }
+    assert_eq!(seen_names.len(), required.len(), "missing some enums");
+
+    variants.sort_by_key(|&(_, value)| value);
+
+    out.push_str("#[allow(non_camel_case_types, clippy::upper_case_acronyms)]\n");
+    out.push_str("#[derive(Copy, Clone, Debug, PartialEq, Eq, TryFromPrimitive)]\n");
+    out.push_str("#[repr(u32)]\n");
+    out.push_str("pub enum Enum {\n");
+    for (name, value) in &variants {
+        writeln!(out, "    {} = 0x{:04X},", name, value).unwrap();
+    }
+    out.push_str("}\n\n");
+
+    out.push_str("#[allow(dead_code)]\n");
+    out.push_str("impl Enum {\n");
+    for (name, variant) in &aliases {
+        writeln!(out, "    pub const {}: Self = Self::{};", name, variant).unwrap();
+    }
+    out.push_str("}\n\n");
+
+    for (name, value) in &bits {
+        out.push_str("#[allow(dead_code)]\n");
+        writeln!(out, "pub const {}: u32 = 0x{:08X};", name, value).unwrap();
+    }
+    out.push('\n');
+}
+
+/// Generate the `EntryPoints` trait and exported functions calling into it,
+/// for `cmds`, and `proc_address` for `cmds` and `extra_exports`.
+///
+/// The default bodies of the `EntryPoints` methods (used for the commands not
+/// implemented by hand) are produced by `default_body`.
+fn generate_entry_points(
+    out: &mut String,
+    cmds: &[&Command],
+    extra_exports: &[&str],
+    default_body: impl Fn(&Command) -> String,
+) {
+    out.push_str("#[allow(non_snake_case, clippy::too_many_arguments)]\n");
+    out.push_str("pub trait EntryPoints {\n");
+    for cmd in cmds {
+        writeln!(
+            out,
+            "    unsafe fn {} {{\n{}    }}",
+            cmd.ffi_signature("_"),
+            default_body(cmd)
+        )
+        .unwrap();
+    }
+    out.push_str("}\n\n");
+
+    for cmd in cmds {
+        write!(
+            out,
+            "{}#[no_mangle]\npub unsafe extern \"C\" fn {} {{\n    \
+             <Api as EntryPoints>::{}({})\n}}\n\n",
+            cmd.lint_attrs(),
+            cmd.ffi_signature(""),
+            cmd.name,
+            cmd.args()
+        )
+        .unwrap();
+    }
+
+    out.push_str("/// Look up an entry point by name (for `glXGetProcAddress`).\n");
+    out.push_str("pub fn proc_address(name: &str) -> Option<unsafe extern \"C\" fn()> {\n");
+    out.push_str("    let f = match name {\n");
+    let names = cmds
+        .iter()
+        .map(|cmd| &cmd.name[..])
+        .chain(extra_exports.iter().copied());
+    for name in names {
+        writeln!(out, "        {:?} => {} as *const (),", name, name).unwrap();
+    }
+    out.push_str("        _ => return None,\n");
+    out.push_str("    };\n");
+    out.push_str(
+        "    Some(unsafe { std::mem::transmute::<*const (), unsafe extern \"C\" fn()>(f) })\n",
+    );
+    out.push_str("}\n");
+}
+
+/// Describe `cmd` in `commands!` syntax, as a conversion to its canonical
+/// command (see `GL_CANONICAL_COMMANDS`), if it has one.
+fn canonical_command(cmd: &Command) -> Option<String> {
+    let &(_, canonical, defaults, normalized) =
+        GL_CANONICAL_COMMANDS.iter().find(|&&(prefix, _, _, _)| {
+            let suffix = cmd.name.strip_prefix(prefix).unwrap_or("-");
+            let suffix = suffix.trim_start_matches(|c: char| ('1'..='4').contains(&c));
+            ["b", "s", "i", "f", "d", "ub", "us", "ui"].contains(&suffix)
+        })?;
+    if cmd.name == canonical {
+        return None;
+    }
+
+    let params: Vec<_> = cmd
+        .params
+        .iter()
+        .map(|param| {
+            let ty = param.ty.commands_macro_type().unwrap();
+            let ty = match ty {
+                "float" => ty.to_string(),
+                "double" => format!("(float {})", ty),
+                _ if normalized => format!("(normalized {})", ty),
+                _ => format!("(float {})", ty),
+            };
+            format!("{}: {}", param.name, ty)
+        })
+        .collect();
+    let args: Vec<_> = cmd
+        .params
+        .iter()
+        .map(|param| &param.name[..])
+        .chain(defaults[cmd.params.len()..].iter().copied())
+        .collect();
+    Some(format!(
+        "{}({}) => {}({})",
+        cmd.name,
+        params.join(", "),
+        canonical,
+        args.join(", ")
+    ))
+}
+
+fn generate_gl(xml: &str) -> String {
+    let doc = Document::parse(xml).unwrap();
+    let reg = Registry::new(&doc);
+    let (mut names, required_enums) = reg.required(GL_FEATURES, "gl");
+    names.extend(GL_EXTRA_COMMANDS);
+
+    let mut out = String::new();
+    generate_enums(&mut out, &reg, &required_enums);
+
+    let cmds: Vec<_> = names.iter().map(|name| reg.command(name)).collect();
+    let by_name: HashMap<_, _> = cmds.iter().map(|cmd| (&cmd.name[..], cmd)).collect();
+
+    // Vector forms (e.g. `glVertex3fv`), which just call their scalar forms.
+    let scalar_of_vector: HashMap<_, _> = cmds
+        .iter()
+        .filter_map(|cmd| Some((&by_name.get(&cmd.vecequiv.as_deref()?)?.name[..], cmd)))
+        .collect();
+
+    let mut used_compsize_lengths = HashSet::new();
+    let mut used_param_types = HashSet::new();
+
+    let mut vector_forms = String::new();
+    let mut commands_macro = String::new();
+    let mut canonical_commands = String::new();
+    let mut other_cmds = vec![];
+    let mut extra_exports = vec![];
+    for cmd in cmds.iter() {
+        if let Some(scalar) = scalar_of_vector.get(&cmd.name[..]) {
+            let lens: Option<Vec<usize>> = cmd
+                .params
+                .iter()
+                .map(|param| param.len.as_ref()?.parse().ok())
+                .collect();
+            if let Some(lens) =
+                lens.filter(|lens| lens.iter().sum::<usize>() == scalar.params.len())
+            {
+                write!(
+                    vector_forms,
+                    "#[no_mangle]\npub unsafe extern \"C\" fn {} {{\n",
+                    cmd.ffi_signature("")
+                )
+                .unwrap();
+                let mut args = vec![];
+                for (param, len) in cmd.params.iter().zip(lens) {
+                    writeln!(
+                        vector_forms,
+                        "    let {0} = std::slice::from_raw_parts({0}, {1});",
+                        param.name, len
+                    )
+                    .unwrap();
+                    args.extend((0..len).map(|i| format!("{}[{}]", param.name, i)));
+                }
+                write!(
+                    vector_forms,
+                    "    {}({})\n}}\n\n",
+                    scalar.name,
+                    args.join(", ")
+                )
+                .unwrap();
+                extra_exports.push(&cmd.name[..]);
+                continue;
+            }
+        }
+
+        if let Some(canonical) = canonical_command(cmd) {
+            writeln!(canonical_commands, "    {},", canonical).unwrap();
+            extra_exports.push(&cmd.name[..]);
+            continue;
+        }
+
+        if GL_CLIENT_STATE_COMMANDS.contains(&&cmd.name[..]) {
+            other_cmds.push(cmd);
+            continue;
+        }
+
+        // Only commands sent as GLX "render" commands are compiled into display
+        // lists (vector forms are used for all the per-vertex ones), which
+        // excludes all those that the spec requires to always be executed
+        // immediately (e.g. `glGenLists`, `glGet*`, `glFlush`, `glPixelStore*`,
+        // and client state, like `glVertexPointer`, which has no GLX protocol).
+        let glx_type = match &cmd.vecequiv {
+            Some(vector) => by_name.get(&vector[..]).and_then(|v| v.glx_type.as_deref()),
+            None => cmd.glx_type.as_deref(),
+        };
+        let params: Option<Vec<_>> = if glx_type == Some("render") && cmd.ret.is_void() {
+            cmd.params
+                .iter()
+                .map(|param| {
+                    let ty_override = GL_PARAM_TYPES
+                        .iter()
+                        .find(|&&(c, p, _)| c == cmd.name && p == param.registry_name)
+                        .map(|&entry| {
+                            used_param_types.insert(entry);
+                            entry.2
+                        });
+                    let elem_ty = CType {
+                        base: param.ty.base.clone(),
+                        is_const: false,
+                        pointers: 0,
+                    };
+                    let ty = match param.ty.pointers {
+                        0 => ty_override
+                            .or_else(|| param.ty.commands_macro_type())?
+                            .to_string(),
+                        1 if param.ty.is_const
+                            && (param.ty.base == "void"
+                                || param.len.as_deref() == Some("COMPSIZE()")) =>
+                        {
+                            ty_override?.to_string()
+                        }
+                        1 if param.ty.is_const => {
+                            let elem = ty_override.or_else(|| elem_ty.commands_macro_type())?;
+                            let len = param.len.as_ref()?;
+                            if len.parse::<usize>().is_ok() {
+                                format!("[{}; {}]", elem, len)
+                            } else if len.starts_with("COMPSIZE(") {
+                                let &entry =
+                                    GL_COMPSIZE_LENGTHS.iter().find(|&&(c, _)| c == cmd.name)?;
+                                used_compsize_lengths.insert(entry);
+                                format!("[{}; dyn {{ {} }}]", elem, entry.1)
+                            } else {
+                                format!("[{}; dyn {{ {} as usize }}]", elem, rust_ident(len))
+                            }
+                        }
+                        _ => return None,
+                    };
+                    Some(format!("{}: {}", param.name, ty))
+                })
+                .collect()
+        } else {
+            None
+        };
+        match params {
+            Some(params) if params.is_empty() => writeln!(commands_macro, "    {},", cmd.name),
+            Some(params) => writeln!(commands_macro, "    {}({}),", cmd.name, params.join(", ")),
+            None => {
+                other_cmds.push(cmd);
+                continue;
+            }
+        }
+        .unwrap();
+        extra_exports.push(&cmd.name[..]);
+    }
+
+    for entry in GL_COMPSIZE_LENGTHS {
+        assert!(used_compsize_lengths.contains(entry), "unused {:?}", entry);
+    }
+    for entry in GL_PARAM_TYPES {
+        assert!(used_param_types.contains(entry), "unused {:?}", entry);
+    }
+
+    writeln!(
+        out,
+        "commands! {{\n{};\n{}}}\n",
+        commands_macro, canonical_commands
+    )
+    .unwrap();
+    out.push_str(&vector_forms);
+    // FIXME(eddyb) implement the remaining entry points, instead of having
+    // them report `GL_INVALID_OPERATION` (and return `0`, if anything).
+    generate_entry_points(&mut out, &other_cmds, &extra_exports, |cmd| {
+        let mut body = format!(
+            "        super::Context::with({:?}, |gl| gl.record_error(Error::InvalidOperation));\n",
+            cmd.name
+        );
+        if !cmd.ret.is_void() {
+            body += "        Default::default()\n";
+        }
+        body
+    });
+    out
+}
+
+fn generate_glx(xml: &str) -> String {
+    let doc = Document::parse(xml).unwrap();
+    let reg = Registry::new(&doc);
+    let (mut names, _) = reg.required(GLX_FEATURES, "glx");
+    names.extend(reg.required(GLX_EXTENSIONS, "glx").0);
+
+    let cmds: Vec<_> = names.iter().map(|name| reg.command(name)).collect();
+    let mut out = String::new();
+    // FIXME(eddyb) implement the remaining entry points, instead of having
+    // them do nothing (and return `0`, or a null pointer, if anything).
+    generate_entry_points(&mut out, &cmds.iter().collect::<Vec<_>>(), &[], |cmd| {
+        let mut body = format!(
+            "        eprintln!(\"glOOF: {} is unimplemented\");\n",
+            cmd.name
+        );
+        if !cmd.ret.is_void() {
+            writeln!(body, "        {}", cmd.ret.default_value()).unwrap();
+        }
+        body
+    });
+    out
+}
+
+fn main() {
+    println!("cargo:rerun-if-changed=build.rs");
+
+    let out_dir = env::var_os("OUT_DIR").unwrap();
+    let out_dir = Path::new(&out_dir);
+
+    fs::write(
+        out_dir.join("gl_api.rs"),
+        generate_gl(include_str!("registry/gl.xml")),
+    )
+    .unwrap();
+    fs::write(
+        out_dir.join("glx_api.rs"),
+        generate_glx(include_str!("registry/glx.xml")),
+    )
+    .unwrap();
+}
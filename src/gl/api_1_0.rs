@@ -1,527 +1,185 @@
+use super::{light, state, tex_gen, texture, Error};
 use derive_try_from_primitive::TryFromPrimitive;
+use glam::Vec4;
 use smallvec::SmallVec;
 use std::convert::{TryFrom, TryInto};
+use std::ffi::c_void;
 use std::ptr;
 use std::sync::Arc;
 
-#[allow(non_camel_case_types)]
-#[derive(Copy, Clone, Debug, TryFromPrimitive)]
-#[repr(u32)]
-pub enum Enum {
-    POINTS = 0x0000,
-    LINES = 0x0001,
-    LINE_LOOP = 0x0002,
-    LINE_STRIP = 0x0003,
-    TRIANGLES = 0x0004,
-    TRIANGLE_STRIP = 0x0005,
-    TRIANGLE_FAN = 0x0006,
-    QUADS = 0x0007,
-    QUAD_STRIP = 0x0008,
-    POLYGON = 0x0009,
-
-    ACCUM = 0x0100,
-    LOAD = 0x0101,
-    RETURN = 0x0102,
-    MULT = 0x0103,
-    ADD = 0x0104,
-
-    NEVER = 0x0200,
-    LESS = 0x0201,
-    EQUAL = 0x0202,
-    LEQUAL = 0x0203,
-    GREATER = 0x0204,
-    NOTEQUAL = 0x0205,
-    GEQUAL = 0x0206,
-    ALWAYS = 0x0207,
-
-    SRC_COLOR = 0x0300,
-    ONE_MINUS_SRC_COLOR = 0x0301,
-    SRC_ALPHA = 0x0302,
-    ONE_MINUS_SRC_ALPHA = 0x0303,
-    DST_ALPHA = 0x0304,
-    ONE_MINUS_DST_ALPHA = 0x0305,
-    DST_COLOR = 0x0306,
-    ONE_MINUS_DST_COLOR = 0x0307,
-    SRC_ALPHA_SATURATE = 0x0308,
-
-    FRONT_LEFT = 0x0400,
-    FRONT_RIGHT = 0x0401,
-    BACK_LEFT = 0x0402,
-    BACK_RIGHT = 0x0403,
-    FRONT = 0x0404,
-    BACK = 0x0405,
-    LEFT = 0x0406,
-    RIGHT = 0x0407,
-    FRONT_AND_BACK = 0x0408,
-    AUX0 = 0x0409,
-    AUX1 = 0x040A,
-    AUX2 = 0x040B,
-    AUX3 = 0x040C,
-
-    INVALID_ENUM = 0x0500,
-    INVALID_VALUE = 0x0501,
-    INVALID_OPERATION = 0x0502,
-    STACK_OVERFLOW = 0x0503,
-    STACK_UNDERFLOW = 0x0504,
-    OUT_OF_MEMORY = 0x0505,
-
-    _2D = 0x0600,
-    _3D = 0x0601,
-    _3D_COLOR = 0x0602,
-    _3D_COLOR_TEXTURE = 0x0603,
-    _4D_COLOR_TEXTURE = 0x0604,
-
-    PASS_THROUGH_TOKEN = 0x0700,
-    POINT_TOKEN = 0x0701,
-    LINE_TOKEN = 0x0702,
-    POLYGON_TOKEN = 0x0703,
-    BITMAP_TOKEN = 0x0704,
-    DRAW_PIXEL_TOKEN = 0x0705,
-    COPY_PIXEL_TOKEN = 0x0706,
-    LINE_RESET_TOKEN = 0x0707,
-
-    EXP = 0x0800,
-    EXP2 = 0x0801,
-
-    CW = 0x0900,
-    CCW = 0x0901,
-
-    COEFF = 0x0A00,
-    ORDER = 0x0A01,
-    DOMAIN = 0x0A02,
-
-    CURRENT_COLOR = 0x0B00,
-    CURRENT_INDEX = 0x0B01,
-    CURRENT_NORMAL = 0x0B02,
-    CURRENT_TEXTURE_COORDS = 0x0B03,
-    CURRENT_RASTER_COLOR = 0x0B04,
-    CURRENT_RASTER_INDEX = 0x0B05,
-    CURRENT_RASTER_TEXTURE_COORDS = 0x0B06,
-    CURRENT_RASTER_POSITION = 0x0B07,
-    CURRENT_RASTER_POSITION_VALID = 0x0B08,
-    CURRENT_RASTER_DISTANCE = 0x0B09,
-
-    POINT_SMOOTH = 0x0B10,
-    POINT_SIZE = 0x0B11,
-    POINT_SIZE_RANGE = 0x0B12,
-    POINT_SIZE_GRANULARITY = 0x0B13,
-
-    LINE_SMOOTH = 0x0B20,
-    LINE_WIDTH = 0x0B21,
-    LINE_WIDTH_RANGE = 0x0B22,
-    LINE_WIDTH_GRANULARITY = 0x0B23,
-    LINE_STIPPLE = 0x0B24,
-    LINE_STIPPLE_PATTERN = 0x0B25,
-    LINE_STIPPLE_REPEAT = 0x0B26,
-
-    LIST_MODE = 0x0B30,
-    MAX_LIST_NESTING = 0x0B31,
-    LIST_BASE = 0x0B32,
-    LIST_INDEX = 0x0B33,
-
-    POLYGON_MODE = 0x0B40,
-    POLYGON_SMOOTH = 0x0B41,
-    POLYGON_STIPPLE = 0x0B42,
-    EDGE_FLAG = 0x0B43,
-    CULL_FACE = 0x0B44,
-    CULL_FACE_MODE = 0x0B45,
-    FRONT_FACE = 0x0B46,
-
-    LIGHTING = 0x0B50,
-    LIGHT_MODEL_LOCAL_VIEWER = 0x0B51,
-    LIGHT_MODEL_TWO_SIDE = 0x0B52,
-    LIGHT_MODEL_AMBIENT = 0x0B53,
-    SHADE_MODEL = 0x0B54,
-    COLOR_MATERIAL_FACE = 0x0B55,
-    COLOR_MATERIAL_PARAMETER = 0x0B56,
-    COLOR_MATERIAL = 0x0B57,
-
-    FOG = 0x0B60,
-    FOG_INDEX = 0x0B61,
-    FOG_DENSITY = 0x0B62,
-    FOG_START = 0x0B63,
-    FOG_END = 0x0B64,
-    FOG_MODE = 0x0B65,
-    FOG_COLOR = 0x0B66,
-
-    DEPTH_RANGE = 0x0B70,
-    DEPTH_TEST = 0x0B71,
-    DEPTH_WRITEMASK = 0x0B72,
-    DEPTH_CLEAR_VALUE = 0x0B73,
-    DEPTH_FUNC = 0x0B74,
-
-    ACCUM_CLEAR_VALUE = 0x0B80,
-
-    STENCIL_TEST = 0x0B90,
-    STENCIL_CLEAR_VALUE = 0x0B91,
-    STENCIL_FUNC = 0x0B92,
-    STENCIL_VALUE_MASK = 0x0B93,
-    STENCIL_FAIL = 0x0B94,
-    STENCIL_PASS_DEPTH_FAIL = 0x0B95,
-    STENCIL_PASS_DEPTH_PASS = 0x0B96,
-    STENCIL_REF = 0x0B97,
-    STENCIL_WRITEMASK = 0x0B98,
-
-    MATRIX_MODE = 0x0BA0,
-    NORMALIZE = 0x0BA1,
-    VIEWPORT = 0x0BA2,
-    MODELVIEW_STACK_DEPTH = 0x0BA3,
-    PROJECTION_STACK_DEPTH = 0x0BA4,
-    TEXTURE_STACK_DEPTH = 0x0BA5,
-    MODELVIEW_MATRIX = 0x0BA6,
-    PROJECTION_MATRIX = 0x0BA7,
-    TEXTURE_MATRIX = 0x0BA8,
-
-    ATTRIB_STACK_DEPTH = 0x0BB0,
-
-    ALPHA_TEST = 0x0BC0,
-    ALPHA_TEST_FUNC = 0x0BC1,
-    ALPHA_TEST_REF = 0x0BC2,
-
-    DITHER = 0x0BD0,
-
-    BLEND_DST = 0x0BE0,
-    BLEND_SRC = 0x0BE1,
-    BLEND = 0x0BE2,
-
-    LOGIC_OP_MODE = 0x0BF0,
-    LOGIC_OP = 0x0BF1,
-
-    AUX_BUFFERS = 0x0C00,
-    DRAW_BUFFER = 0x0C01,
-    READ_BUFFER = 0x0C02,
-
-    SCISSOR_BOX = 0x0C10,
-    SCISSOR_TEST = 0x0C11,
-
-    INDEX_CLEAR_VALUE = 0x0C20,
-    INDEX_WRITEMASK = 0x0C21,
-    COLOR_CLEAR_VALUE = 0x0C22,
-    COLOR_WRITEMASK = 0x0C23,
-
-    INDEX_MODE = 0x0C30,
-    RGBA_MODE = 0x0C31,
-    DOUBLEBUFFER = 0x0C32,
-    STEREO = 0x0C33,
-
-    RENDER_MODE = 0x0C40,
-
-    PERSPECTIVE_CORRECTION_HINT = 0x0C50,
-    POINT_SMOOTH_HINT = 0x0C51,
-    LINE_SMOOTH_HINT = 0x0C52,
-    POLYGON_SMOOTH_HINT = 0x0C53,
-    FOG_HINT = 0x0C54,
-
-    TEXTURE_GEN_S = 0x0C60,
-    TEXTURE_GEN_T = 0x0C61,
-    TEXTURE_GEN_R = 0x0C62,
-    TEXTURE_GEN_Q = 0x0C63,
-
-    PIXEL_MAP_I_TO_I = 0x0C70,
-    PIXEL_MAP_S_TO_S = 0x0C71,
-    PIXEL_MAP_I_TO_R = 0x0C72,
-    PIXEL_MAP_I_TO_G = 0x0C73,
-    PIXEL_MAP_I_TO_B = 0x0C74,
-    PIXEL_MAP_I_TO_A = 0x0C75,
-    PIXEL_MAP_R_TO_R = 0x0C76,
-    PIXEL_MAP_G_TO_G = 0x0C77,
-    PIXEL_MAP_B_TO_B = 0x0C78,
-    PIXEL_MAP_A_TO_A = 0x0C79,
-
-    PIXEL_MAP_I_TO_I_SIZE = 0x0CB0,
-    PIXEL_MAP_S_TO_S_SIZE = 0x0CB1,
-    PIXEL_MAP_I_TO_R_SIZE = 0x0CB2,
-    PIXEL_MAP_I_TO_G_SIZE = 0x0CB3,
-    PIXEL_MAP_I_TO_B_SIZE = 0x0CB4,
-    PIXEL_MAP_I_TO_A_SIZE = 0x0CB5,
-    PIXEL_MAP_R_TO_R_SIZE = 0x0CB6,
-    PIXEL_MAP_G_TO_G_SIZE = 0x0CB7,
-    PIXEL_MAP_B_TO_B_SIZE = 0x0CB8,
-    PIXEL_MAP_A_TO_A_SIZE = 0x0CB9,
-
-    UNPACK_SWAP_BYTES = 0x0CF0,
-    UNPACK_LSB_FIRST = 0x0CF1,
-    UNPACK_ROW_LENGTH = 0x0CF2,
-    UNPACK_SKIP_ROWS = 0x0CF3,
-    UNPACK_SKIP_PIXELS = 0x0CF4,
-    UNPACK_ALIGNMENT = 0x0CF5,
-
-    PACK_SWAP_BYTES = 0x0D00,
-    PACK_LSB_FIRST = 0x0D01,
-    PACK_ROW_LENGTH = 0x0D02,
-    PACK_SKIP_ROWS = 0x0D03,
-    PACK_SKIP_PIXELS = 0x0D04,
-    PACK_ALIGNMENT = 0x0D05,
-
-    MAP_COLOR = 0x0D10,
-    MAP_STENCIL = 0x0D11,
-    INDEX_SHIFT = 0x0D12,
-    INDEX_OFFSET = 0x0D13,
-    RED_SCALE = 0x0D14,
-    RED_BIAS = 0x0D15,
-    ZOOM_X = 0x0D16,
-    ZOOM_Y = 0x0D17,
-    GREEN_SCALE = 0x0D18,
-    GREEN_BIAS = 0x0D19,
-    BLUE_SCALE = 0x0D1A,
-    BLUE_BIAS = 0x0D1B,
-    ALPHA_SCALE = 0x0D1C,
-    ALPHA_BIAS = 0x0D1D,
-    DEPTH_SCALE = 0x0D1E,
-    DEPTH_BIAS = 0x0D1F,
-
-    MAX_EVAL_ORDER = 0x0D30,
-    MAX_LIGHTS = 0x0D31,
-    MAX_CLIP_PLANES = 0x0D32,
-    MAX_TEXTURE_SIZE = 0x0D33,
-    MAX_PIXEL_MAP_TABLE = 0x0D34,
-    MAX_ATTRIB_STACK_DEPTH = 0x0D35,
-    MAX_MODELVIEW_STACK_DEPTH = 0x0D36,
-    MAX_NAME_STACK_DEPTH = 0x0D37,
-    MAX_PROJECTION_STACK_DEPTH = 0x0D38,
-    MAX_TEXTURE_STACK_DEPTH = 0x0D39,
-    MAX_VIEWPORT_DIMS = 0x0D3A,
-
-    SUBPIXEL_BITS = 0x0D50,
-    INDEX_BITS = 0x0D51,
-    RED_BITS = 0x0D52,
-    GREEN_BITS = 0x0D53,
-    BLUE_BITS = 0x0D54,
-    ALPHA_BITS = 0x0D55,
-    DEPTH_BITS = 0x0D56,
-    STENCIL_BITS = 0x0D57,
-    ACCUM_RED_BITS = 0x0D58,
-    ACCUM_GREEN_BITS = 0x0D59,
-    ACCUM_BLUE_BITS = 0x0D5A,
-    ACCUM_ALPHA_BITS = 0x0D5B,
-
-    NAME_STACK_DEPTH = 0x0D70,
-
-    AUTO_NORMAL = 0x0D80,
-
-    MAP1_COLOR_4 = 0x0D90,
-    MAP1_INDEX = 0x0D91,
-    MAP1_NORMAL = 0x0D92,
-    MAP1_TEXTURE_COORD_1 = 0x0D93,
-    MAP1_TEXTURE_COORD_2 = 0x0D94,
-    MAP1_TEXTURE_COORD_3 = 0x0D95,
-    MAP1_TEXTURE_COORD_4 = 0x0D96,
-    MAP1_VERTEX_3 = 0x0D97,
-    MAP1_VERTEX_4 = 0x0D98,
-
-    MAP2_COLOR_4 = 0x0DB0,
-    MAP2_INDEX = 0x0DB1,
-    MAP2_NORMAL = 0x0DB2,
-    MAP2_TEXTURE_COORD_1 = 0x0DB3,
-    MAP2_TEXTURE_COORD_2 = 0x0DB4,
-    MAP2_TEXTURE_COORD_3 = 0x0DB5,
-    MAP2_TEXTURE_COORD_4 = 0x0DB6,
-    MAP2_VERTEX_3 = 0x0DB7,
-    MAP2_VERTEX_4 = 0x0DB8,
-
-    MAP1_GRID_DOMAIN = 0x0DD0,
-    MAP1_GRID_SEGMENTS = 0x0DD1,
-    MAP2_GRID_DOMAIN = 0x0DD2,
-    MAP2_GRID_SEGMENTS = 0x0DD3,
-
-    TEXTURE_1D = 0x0DE0,
-    TEXTURE_2D = 0x0DE1,
-
-    TEXTURE_WIDTH = 0x1000,
-    TEXTURE_HEIGHT = 0x1001,
-    TEXTURE_COMPONENTS = 0x1003,
-    TEXTURE_BORDER_COLOR = 0x1004,
-    TEXTURE_BORDER = 0x1005,
-
-    DONT_CARE = 0x1100,
-    FASTEST = 0x1101,
-    NICEST = 0x1102,
-
-    AMBIENT = 0x1200,
-    DIFFUSE = 0x1201,
-    SPECULAR = 0x1202,
-    POSITION = 0x1203,
-    SPOT_DIRECTION = 0x1204,
-    SPOT_EXPONENT = 0x1205,
-    SPOT_CUTOFF = 0x1206,
-    CONSTANT_ATTENUATION = 0x1207,
-    LINEAR_ATTENUATION = 0x1208,
-    QUADRATIC_ATTENUATION = 0x1209,
-
-    COMPILE = 0x1300,
-    COMPILE_AND_EXECUTE = 0x1301,
-
-    BYTE = 0x1400,
-    UNSIGNED_BYTE = 0x1401,
-    SHORT = 0x1402,
-    UNSIGNED_SHORT = 0x1403,
-    INT = 0x1404,
-    UNSIGNED_INT = 0x1405,
-    FLOAT = 0x1406,
-    _2_BYTES = 0x1407,
-    _3_BYTES = 0x1408,
-    _4_BYTES = 0x1409,
-
-    CLEAR = 0x1500,
-    AND = 0x1501,
-    AND_REVERSE = 0x1502,
-    COPY = 0x1503,
-    AND_INVERTED = 0x1504,
-    NOOP = 0x1505,
-    XOR = 0x1506,
-    OR = 0x1507,
-    NOR = 0x1508,
-    EQUIV = 0x1509,
-    INVERT = 0x150A,
-    OR_REVERSE = 0x150B,
-    COPY_INVERTED = 0x150C,
-    OR_INVERTED = 0x150D,
-    NAND = 0x150E,
-    SET = 0x150F,
-
-    EMISSION = 0x1600,
-    SHININESS = 0x1601,
-    AMBIENT_AND_DIFFUSE = 0x1602,
-    COLOR_INDEXES = 0x1603,
-
-    MODELVIEW = 0x1700,
-    PROJECTION = 0x1701,
-    TEXTURE = 0x1702,
-
-    COLOR = 0x1800,
-    DEPTH = 0x1801,
-    STENCIL = 0x1802,
-
-    COLOR_INDEX = 0x1900,
-    STENCIL_INDEX = 0x1901,
-    DEPTH_COMPONENT = 0x1902,
-    RED = 0x1903,
-    GREEN = 0x1904,
-    BLUE = 0x1905,
-    ALPHA = 0x1906,
-    RGB = 0x1907,
-    RGBA = 0x1908,
-    LUMINANCE = 0x1909,
-    LUMINANCE_ALPHA = 0x190A,
-
-    BITMAP = 0x1A00,
-
-    POINT = 0x1B00,
-    LINE = 0x1B01,
-    FILL = 0x1B02,
-
-    RENDER = 0x1C00,
-    FEEDBACK = 0x1C01,
-    SELECT = 0x1C02,
-
-    FLAT = 0x1D00,
-    SMOOTH = 0x1D01,
-
-    KEEP = 0x1E00,
-    REPLACE = 0x1E01,
-    INCR = 0x1E02,
-    DECR = 0x1E03,
-
-    VENDOR = 0x1F00,
-    RENDERER = 0x1F01,
-    VERSION = 0x1F02,
-    EXTENSIONS = 0x1F03,
-
-    S = 0x2000,
-    T = 0x2001,
-    R = 0x2002,
-    Q = 0x2003,
-
-    MODULATE = 0x2100,
-    DECAL = 0x2101,
-
-    TEXTURE_ENV_MODE = 0x2200,
-    TEXTURE_ENV_COLOR = 0x2201,
-
-    TEXTURE_ENV = 0x2300,
-
-    EYE_LINEAR = 0x2400,
-    OBJECT_LINEAR = 0x2401,
-    SPHERE_MAP = 0x2402,
-
-    TEXTURE_GEN_MODE = 0x2500,
-    OBJECT_PLANE = 0x2501,
-    EYE_PLANE = 0x2502,
-
-    NEAREST = 0x2600,
-    LINEAR = 0x2601,
-
-    NEAREST_MIPMAP_NEAREST = 0x2700,
-    LINEAR_MIPMAP_NEAREST = 0x2701,
-    NEAREST_MIPMAP_LINEAR = 0x2702,
-    LINEAR_MIPMAP_LINEAR = 0x2703,
-
-    TEXTURE_MAG_FILTER = 0x2800,
-    TEXTURE_MIN_FILTER = 0x2801,
-    TEXTURE_WRAP_S = 0x2802,
-    TEXTURE_WRAP_T = 0x2803,
-
-    CLAMP = 0x2900,
-    REPEAT = 0x2901,
-
-    CLIP_PLANE0 = 0x3000,
-    CLIP_PLANE1 = 0x3001,
-    CLIP_PLANE2 = 0x3002,
-    CLIP_PLANE3 = 0x3003,
-    CLIP_PLANE4 = 0x3004,
-    CLIP_PLANE5 = 0x3005,
-
-    LIGHT0 = 0x4000,
-    LIGHT1 = 0x4001,
-    LIGHT2 = 0x4002,
-    LIGHT3 = 0x4003,
-    LIGHT4 = 0x4004,
-    LIGHT5 = 0x4005,
-    LIGHT6 = 0x4006,
-    LIGHT7 = 0x4007,
+// FIXME(eddyb) model bitfields as types, instead of just constants.
+pub const CLEAR_BUFFER_BITS: u32 =
+    DEPTH_BUFFER_BIT | ACCUM_BUFFER_BIT | STENCIL_BUFFER_BIT | COLOR_BUFFER_BIT;
+
+/// Conversion of integer color (or normal) components to floating-point, such
+/// that the full range of an unsigned integer type maps linearly to `[0, 1]`,
+/// and that of a signed one to `[-1, 1]`.
+pub trait Normalize {
+    fn normalize(self) -> f32;
+}
+
+macro_rules! impl_normalize {
+    ($($unsigned:ty: $signed:ty),*) => {$(
+        impl Normalize for $unsigned {
+            fn normalize(self) -> f32 {
+                (self as f64 / <$unsigned>::MAX as f64) as f32
+            }
+        }
+        impl Normalize for $signed {
+            fn normalize(self) -> f32 {
+                ((2.0 * self as f64 + 1.0) / <$unsigned>::MAX as f64) as f32
+            }
+        }
+    )*};
 }
 
+impl_normalize!(u8: i8, u16: i16, u32: i32);
+
 macro_rules! commands {
     (@type enum) => {Enum};
     (@ffi_type enum) => {u32};
-    (@from_ffi($gl:ident) $name:ident: enum) => {$name.try_into().unwrap()};
+    (@from_ffi($gl:ident) $name:ident: enum) => {$name.try_into().map_err(|_| Error::InvalidEnum)?};
 
     // FIXME(eddyb) model these properly
     (@type bitfield) => {u32};
     (@ffi_type bitfield) => {u32};
     (@from_ffi($gl:ident) $name:ident: bitfield) => {$name};
 
+    (@type byte) => {i8};
+    (@ffi_type byte) => {i8};
+    (@from_ffi($gl:ident) $name:ident: byte) => {$name};
+
+    (@type ubyte) => {u8};
+    (@ffi_type ubyte) => {u8};
+    (@from_ffi($gl:ident) $name:ident: ubyte) => {$name};
+
+    (@type short) => {i16};
+    (@ffi_type short) => {i16};
+    (@from_ffi($gl:ident) $name:ident: short) => {$name};
+
+    (@type ushort) => {u16};
+    (@ffi_type ushort) => {u16};
+    (@from_ffi($gl:ident) $name:ident: ushort) => {$name};
+
     (@type int) => {i32};
     (@ffi_type int) => {i32};
     (@from_ffi($gl:ident) $name:ident: int) => {$name};
 
+    (@type uint) => {u32};
+    (@ffi_type uint) => {u32};
+    (@from_ffi($gl:ident) $name:ident: uint) => {$name};
+
     (@type sizei) => {u32};
     (@ffi_type sizei) => {i32};
-    (@from_ffi($gl:ident) $name:ident: sizei) => {$name.try_into().unwrap()};
+    (@from_ffi($gl:ident) $name:ident: sizei) => {$name.try_into().map_err(|_| Error::InvalidValue)?};
 
     (@type float) => {f32};
     (@ffi_type float) => {f32};
     (@from_ffi($gl:ident) $name:ident: float) => {$name};
 
+    (@type clampf) => {f32};
+    (@ffi_type clampf) => {f32};
+    (@from_ffi($gl:ident) $name:ident: clampf) => {$name.clamp(0.0, 1.0)};
+
+    (@type boolean) => {bool};
+    (@ffi_type boolean) => {u8};
+    (@from_ffi($gl:ident) $name:ident: boolean) => {$name != 0};
+
     (@type double) => {f64};
     (@ffi_type double) => {f64};
     (@from_ffi($gl:ident) $name:ident: double) => {$name};
 
+    (@type clampd) => {f64};
+    (@ffi_type clampd) => {f64};
+    (@from_ffi($gl:ident) $name:ident: clampd) => {$name.clamp(0.0, 1.0)};
+
     (@type [$elem:tt; dyn $len:block]) => {SmallVec<[commands!(@type $elem); 4]>};
     (@ffi_type [$elem:tt; dyn $len:block]) => {*const commands!(@ffi_type $elem)};
     (@from_ffi($gl:ident) $name:ident: [$elem:tt; dyn $len:block]) => {
         unsafe { std::slice::from_raw_parts($name, $len) }
             .iter()
-            .map(|&x| commands!(@from_ffi($gl) x: $elem))
-            .collect()
+            .map(|&x| Ok(commands!(@from_ffi($gl) x: $elem)))
+            .collect::<Result<_, Error>>()?
     };
 
-    (@type list) => {Arc<super::DisplayList>};
-    (@ffi_type list) => {u32};
-    (@from_ffi($gl:ident) $name:ident: list) => {$gl.lists[&$name].clone()};
+    (@type [$elem:tt; $len:literal]) => {[commands!(@type $elem); $len]};
+    (@ffi_type [$elem:tt; $len:literal]) => {*const commands!(@ffi_type $elem)};
+    (@from_ffi($gl:ident) $name:ident: [$elem:tt; $len:literal]) => {{
+        let mut array = [Default::default(); $len];
+        let src = unsafe { std::slice::from_raw_parts($name, $len) };
+        for (dst, &x) in array.iter_mut().zip(src) {
+            *dst = commands!(@from_ffi($gl) x: $elem);
+        }
+        array
+    }};
+
+    // Polygon stipple pattern (see `pixel::unpack_stipple`).
+    (@type (stipple)) => {[u32; 32]};
+    (@ffi_type (stipple)) => {*const u8};
+    (@from_ffi($gl:ident) $name:ident: (stipple)) => {
+        unsafe { super::pixel::unpack_stipple(&$gl.client.pixel_store.unpack, $name) }
+    };
+
+    // List names (relative to the list base) for `glCallLists`, with their
+    // number and type given by the other parameters.
+    (@type (lists $n:ident $ty:ident)) => {Vec<u32>};
+    (@ffi_type (lists $n:ident $ty:ident)) => {*const std::ffi::c_void};
+    (@from_ffi($gl:ident) $name:ident: (lists $n:ident $ty:ident)) => {
+        unsafe { super::list::read_names($n, $ty, $name)? }
+    };
+
+    // Images unpacked from client memory (see `pixel::unpack_image`), with
+    // their target, size, format and type given by the other parameters.
+    (@type (pixels $target:ident $width:tt $height:tt $format:ident $ty:ident)) => {
+        Option<Vec<Vec4>>
+    };
+    (@ffi_type (pixels $target:ident $width:tt $height:tt $format:ident $ty:ident)) => {
+        *const std::ffi::c_void
+    };
+    (@from_ffi($gl:ident) $name:ident: (pixels $target:ident $width:tt $height:tt $format:ident $ty:ident)) => {
+        unsafe {
+            let modes = &$gl.client.pixel_store.unpack;
+            super::pixel::unpack_image(modes, $target, $width, $height, $format, $ty, $name)?
+        }
+    };
+
+    // Integers converted to floating-point, either directly (`float`), or by
+    // mapping their range to `[0, 1]` or `[-1, 1]` (`normalized`).
+    (@type (float $ty:tt)) => {f32};
+    (@ffi_type (float $ty:tt)) => {commands!(@ffi_type $ty)};
+    (@from_ffi($gl:ident) $name:ident: (float $ty:tt)) => {commands!(@from_ffi($gl) $name: $ty) as f32};
+
+    (@type (normalized $ty:tt)) => {f32};
+    (@ffi_type (normalized $ty:tt)) => {commands!(@ffi_type $ty)};
+    (@from_ffi($gl:ident) $name:ident: (normalized $ty:tt)) => {
+        Normalize::normalize(commands!(@from_ffi($gl) $name: $ty))
+    };
 
-    ($($name:ident $(($($param:ident: $ty:tt),*))?),*) => {
-        #[allow(non_camel_case_types)]
+    (@entry_point $name:ident($($param:ident: $ty:tt),*) => $cmd:expr) => {
+        #[no_mangle]
+        #[allow(clippy::too_many_arguments)]
+        pub extern "C" fn $name($($param: commands!(@ffi_type $ty)),*) {
+            super::Context::with(stringify!($name), |gl| {
+                let from_ffi = || {
+                    $(let $param = commands!(@from_ffi(gl) $param: $ty);)*
+                    Ok::<_, Error>($cmd)
+                };
+                match from_ffi() {
+                    Ok(cmd) => cmd.submit(gl),
+                    Err(error) => gl.record_error(error),
+                }
+            });
+        }
+    };
+
+    // NOTE(eddyb) the commands after `;` don't get their own `Command`s, but
+    // are instead converted to other ones (e.g. `glColor3ub` to `glColor4f`).
+    (
+        $($name:ident $(($($param:ident: $ty:tt),*))?),* $(,)?;
+        $($alias:ident($($alias_param:ident: $alias_ty:tt),*) => $canonical:ident($($arg:expr),*)),* $(,)?
+    ) => {
+        // FIXME(eddyb) remove `dead_code` once all commands are fully handled.
+        #[allow(non_camel_case_types, dead_code)]
         #[derive(Clone, Debug)]
         pub enum Command {
             $($name $(($(commands!(@type $ty)),*))?),*
@@ -529,467 +187,1062 @@ macro_rules! commands {
 
         impl Command {
             fn submit(self, gl: &mut super::Context) {
-                if let Some((_, list)) = &mut gl.compile_list {
+                if let Some((_, cmds)) = &mut gl.compile_list {
+                    if cmds.try_reserve(1).is_err() {
+                        gl.record_error(Error::OutOfMemory);
+                        return;
+                    }
                     if gl.execute_immediately {
-                        gl.pending_cmds.push(self.clone());
+                        cmds.push(self.clone());
+                        gl.execute(self);
+                    } else {
+                        cmds.push(self);
                     }
-                    list.cmds.push(self);
                 } else {
                     assert!(gl.execute_immediately);
-                    gl.pending_cmds.push(self);
+                    gl.execute(self);
                 }
             }
         }
 
-        $(#[no_mangle]
-        pub extern "C" fn $name($($($param: commands!(@ffi_type $ty)),*)?) {
-            super::Context::with(stringify!($name), |gl| {
-                $($(let $param = commands!(@from_ffi(gl) $param: $ty);)*)?
-                Command::$name $(($($param),*))?.submit(gl);
-            });
-        })*
+        $(commands!(@entry_point $name($($($param: $ty),*)?) => Command::$name $(($($param),*))?);)*
+        $(commands!(@entry_point $alias($($alias_param: $alias_ty),*) => Command::$canonical($($arg),*));)*
     };
 }
 
-commands! {
-    glClear(buf: bitfield),
-
-    glEnable(target: enum),
-
-    glMaterialfv(face: enum, pname: enum, params: [float; dyn {
-        use Enum::*;
-        match pname {
-            AMBIENT | DIFFUSE | AMBIENT_AND_DIFFUSE | SPECULAR | EMISSION => 4,
+// NOTE(eddyb) this contains the `commands!` invocation, `Enum`, and all the
+// entry points (see `build.rs`), and needs to come after `commands!`.
+include!(concat!(env!("OUT_DIR"), "/gl_api.rs"));
+
+impl Command {
+    /// Whether this command can be used between `glBegin` and `glEnd`, with
+    /// all other commands (including the entry points which aren't `Command`s)
+    /// instead causing `GL_INVALID_OPERATION` errors.
+    pub fn is_allowed_between_begin_end(&self) -> bool {
+        use Command::*;
+        matches!(
+            self,
+            glVertex4f(..)
+                | glColor4f(..)
+                | glIndexf(_)
+                | glNormal3f(..)
+                | glTexCoord4f(..)
+                | glEdgeFlag(_)
+                | glMaterialf(..)
+                | glMaterialfv(..)
+                | glMateriali(..)
+                | glMaterialiv(..)
+                | glEvalCoord1f(_)
+                | glEvalCoord1d(_)
+                | glEvalCoord2f(..)
+                | glEvalCoord2d(..)
+                | glEvalPoint1(_)
+                | glEvalPoint2(..)
+                | glCallList(_)
+                | glCallLists(..)
+                | glEnd
+        )
+    }
+}
 
-            SHININESS => 1,
+unsafe fn get<T>(
+    blame: &'static str,
+    params: *mut T,
+    convert: impl Fn(super::get::Value) -> T,
+    query: impl FnOnce(&super::Context) -> Result<super::get::Values, Error>,
+) {
+    super::Context::with(blame, |gl| match query(gl) {
+        Ok(values) => {
+            for (i, value) in values.into_iter().enumerate() {
+                params.add(i).write(convert(value));
+            }
+        }
+        Err(error) => gl.record_error(error),
+    });
+}
 
-            COLOR_INDEXES => 3,
+/// Draw the primitive made out of the elements (of the enabled vertex arrays)
+/// at the `count` indices returned by `index`, by submitting the same commands
+/// as `glBegin(mode)`, `glArrayElement` (for each index), and `glEnd` would.
+///
+/// # Safety
+///
+/// All the enabled arrays must contain every element `index` can return.
+unsafe fn draw_elements(
+    gl: &mut super::Context,
+    mode: u32,
+    count: i32,
+    index: impl Fn(usize) -> usize,
+) -> Result<(), Error> {
+    let mode = Enum::try_from(mode)
+        .ok()
+        .filter(|&mode| super::raster::is_primitive_mode(mode))
+        .ok_or(Error::InvalidEnum)?;
+    if count < 0 {
+        return Err(Error::InvalidValue);
+    }
+    if gl.state.begin.is_some() {
+        return Err(Error::InvalidOperation);
+    }
 
-            // FIXME(eddyb) report error
-            _ => unimplemented!("glMaterialfv(pname={:?})", pname),
-        }
-    }]),
-    glLightfv(light: enum, pname: enum, params: [float; dyn {
-        use Enum::*;
-        match pname {
-            AMBIENT | DIFFUSE | SPECULAR | POSITION => 4,
-
-            SPOT_DIRECTION => 3,
-
-            SPOT_EXPONENT
-            | SPOT_CUTOFF
-            | CONSTANT_ATTENUATION
-            | LINEAR_ATTENUATION
-            | QUADRATIC_ATTENUATION => 1,
-
-            // FIXME(eddyb) report error
-            _ => unimplemented!("glLightfv(pname={:?})", pname),
+    // NOTE(eddyb) going through `Command`s means that the array contents are
+    // also copied into the display list being compiled (if any), as required.
+    Command::glBegin(mode).submit(gl);
+    for i in 0..count as usize {
+        for cmd in gl.client.arrays.element(index(i)) {
+            cmd.submit(gl);
         }
-    }]),
-    glShadeModel(mode: enum),
-
-    glBegin(mode: enum),
-    glVertex3f(x: float, y: float, z: float),
-    glNormal3f(x: float, y: float, z: float),
-    glEnd,
-
-    glViewport(x: int, y: int, w: sizei, h: sizei),
-
-    glMatrixMode(mode: enum),
-    glPushMatrix,
-    glPopMatrix,
-    glLoadIdentity,
-    glRotatef(angle: float, x: float, y: float, z: float),
-    glTranslatef(x: float, y: float, z: float),
-    glTranslated(x: double, y: double, z: double),
-    glFrustum(l: double, r: double, b: double, t: double, n: double, f: double),
-
-    glCallList(n: list)
-}
+    }
+    Command::glEnd.submit(gl);
 
-#[no_mangle]
-pub extern "C" fn glGenLists(s: i32) -> u32 {
-    super::Context::with("glGenLists", |gl| {
-        let n = gl.first_unused_list;
-        gl.first_unused_list += s as u32;
-        n
-    })
+    Ok(())
 }
 
-#[no_mangle]
-pub extern "C" fn glNewList(n: u32, mode: u32) {
-    let mode = Enum::try_from(mode).unwrap();
-    super::Context::with("glNewList", |gl| {
-        // FIXME(eddyb) report error
-        assert!(gl.compile_list.is_none());
-        gl.compile_list = Some((n, super::DisplayList::default()));
-        gl.execute_immediately = {
+/// Hand-written implementations of the entry points which aren't `Command`s.
+pub struct Api;
+
+#[allow(non_snake_case)]
+impl EntryPoints for Api {
+    unsafe fn glGenLists(s: i32) -> u32 {
+        super::Context::with("glGenLists", |gl| {
+            if gl.state.begin.is_some() {
+                gl.record_error(Error::InvalidOperation);
+                return 0;
+            }
+            if s < 0 {
+                gl.record_error(Error::InvalidValue);
+                return 0;
+            }
+            if s == 0 {
+                return 0;
+            }
+            let lists = &mut gl.share_group.lock().unwrap().lists;
+            match super::list::find_unused_range(lists, s as u32) {
+                Some(first) => {
+                    // NOTE(eddyb) the names are reserved by creating empty
+                    // lists, which `glNewList` can later replace.
+                    let empty = Arc::new(super::DisplayList::default());
+                    for n in first..=first + (s as u32 - 1) {
+                        lists.insert(n, empty.clone());
+                    }
+                    first
+                }
+                None => 0,
+            }
+        })
+    }
+
+    unsafe fn glDeleteLists(list: u32, range: i32) {
+        super::Context::with("glDeleteLists", |gl| {
+            if gl.state.begin.is_some() {
+                return gl.record_error(Error::InvalidOperation);
+            }
+            if range < 0 {
+                return gl.record_error(Error::InvalidValue);
+            }
+            let end = list as u64 + range as u64;
+            gl.share_group
+                .lock()
+                .unwrap()
+                .lists
+                .retain(|&n, _| !(list as u64..end).contains(&(n as u64)));
+        });
+    }
+
+    unsafe fn glIsList(list: u32) -> u8 {
+        super::Context::with("glIsList", |gl| {
+            if gl.state.begin.is_some() {
+                gl.record_error(Error::InvalidOperation);
+                return 0;
+            }
+            gl.share_group.lock().unwrap().lists.contains_key(&list) as u8
+        })
+    }
+
+    unsafe fn glNewList(n: u32, mode: u32) {
+        super::Context::with("glNewList", |gl| {
+            let execute_immediately = {
+                use Enum::*;
+                match Enum::try_from(mode) {
+                    Ok(COMPILE) => false,
+                    Ok(COMPILE_AND_EXECUTE) => true,
+                    _ => return gl.record_error(Error::InvalidEnum),
+                }
+            };
+            if n == 0 {
+                return gl.record_error(Error::InvalidValue);
+            }
+            if gl.compile_list.is_some() || gl.state.begin.is_some() {
+                return gl.record_error(Error::InvalidOperation);
+            }
+            gl.compile_list = Some((n, vec![]));
+            gl.execute_immediately = execute_immediately;
+        });
+    }
+
+    unsafe fn glEndList() {
+        super::Context::with("glEndList", |gl| {
+            if gl.state.begin.is_some() {
+                return gl.record_error(Error::InvalidOperation);
+            }
+            match gl.compile_list.take() {
+                Some((n, cmds)) => {
+                    // NOTE(eddyb) this replaces any existing list with the
+                    // same name, only now that the new one is complete, and
+                    // lists calling it will use the new one from now on.
+                    let lists = &mut gl.share_group.lock().unwrap().lists;
+                    let list = super::DisplayList::compile(&cmds, lists);
+                    lists.insert(n, Arc::new(list));
+                    gl.execute_immediately = true;
+                }
+                None => gl.record_error(Error::InvalidOperation),
+            }
+        });
+    }
+
+    unsafe fn glVertexPointer(size: i32, ty: u32, stride: i32, pointer: *const c_void) {
+        super::Context::with("glVertexPointer", |gl| {
             use Enum::*;
-            match mode {
-                COMPILE => false,
-                COMPILE_AND_EXECUTE => true,
-                // FIXME(eddyb) report error
-                _ => unimplemented!("glNewList(mode={:?})", mode),
+            let types = &[SHORT, INT, FLOAT, DOUBLE];
+            let array = &mut gl.client.arrays.vertex;
+            if let Err(error) = array.set_pointer(size, ty, stride, pointer, 2..=4, types) {
+                gl.record_error(error);
             }
-        };
-    });
-}
+        });
+    }
 
-#[no_mangle]
-pub extern "C" fn glEndList() {
-    super::Context::with("glEndList", |gl| {
-        // FIXME(eddyb) report error
-        let (n, list) = gl.compile_list.take().unwrap();
-        gl.lists.insert(n, Arc::new(list));
-        gl.execute_immediately = true;
-    });
-}
+    unsafe fn glNormalPointer(ty: u32, stride: i32, pointer: *const c_void) {
+        super::Context::with("glNormalPointer", |gl| {
+            use Enum::*;
+            let types = &[BYTE, SHORT, INT, FLOAT, DOUBLE];
+            let array = &mut gl.client.arrays.normal;
+            if let Err(error) = array.set_pointer(3, ty, stride, pointer, 3..=3, types) {
+                gl.record_error(error);
+            }
+        });
+    }
 
-#[no_mangle]
-pub unsafe extern "C" fn glGetString(name: Enum) -> *const u8 {
-    eprintln!("glGetString(name={:?})", name);
+    unsafe fn glColorPointer(size: i32, ty: u32, stride: i32, pointer: *const c_void) {
+        super::Context::with("glColorPointer", |gl| {
+            use Enum::*;
+            let types = &[
+                BYTE,
+                UNSIGNED_BYTE,
+                SHORT,
+                UNSIGNED_SHORT,
+                INT,
+                UNSIGNED_INT,
+                FLOAT,
+                DOUBLE,
+            ];
+            let array = &mut gl.client.arrays.color;
+            if let Err(error) = array.set_pointer(size, ty, stride, pointer, 3..=4, types) {
+                gl.record_error(error);
+            }
+        });
+    }
 
-    use Enum::*;
-    match name {
-        VENDOR | RENDERER => "glOOF\0",
-        VERSION => concat!(version_str!(major.minor), "\0"),
-        EXTENSIONS => "\0",
-        _ => return ptr::null(),
+    unsafe fn glIndexPointer(ty: u32, stride: i32, pointer: *const c_void) {
+        super::Context::with("glIndexPointer", |gl| {
+            use Enum::*;
+            let types = &[UNSIGNED_BYTE, SHORT, INT, FLOAT, DOUBLE];
+            let array = &mut gl.client.arrays.index;
+            if let Err(error) = array.set_pointer(1, ty, stride, pointer, 1..=1, types) {
+                gl.record_error(error);
+            }
+        });
     }
-    .as_ptr()
-}
 
-macro_rules! unimplemented_entry_points {
-    ($($name:ident)*) => {
-        $(#[no_mangle]
-        pub extern "C" fn $name() {
-            unimplemented!(stringify!($name));
-        })*
-    };
+    unsafe fn glTexCoordPointer(size: i32, ty: u32, stride: i32, pointer: *const c_void) {
+        super::Context::with("glTexCoordPointer", |gl| {
+            use Enum::*;
+            let types = &[SHORT, INT, FLOAT, DOUBLE];
+            let array = &mut gl.client.arrays.texcoord;
+            if let Err(error) = array.set_pointer(size, ty, stride, pointer, 1..=4, types) {
+                gl.record_error(error);
+            }
+        });
+    }
+
+    unsafe fn glEdgeFlagPointer(stride: i32, pointer: *const c_void) {
+        super::Context::with("glEdgeFlagPointer", |gl| {
+            let ty = Enum::UNSIGNED_BYTE;
+            let array = &mut gl.client.arrays.edge_flag;
+            if let Err(error) = array.set_pointer(1, ty as u32, stride, pointer, 1..=1, &[ty]) {
+                gl.record_error(error);
+            }
+        });
+    }
+
+    unsafe fn glInterleavedArrays(format: u32, stride: i32, pointer: *const c_void) {
+        super::Context::with("glInterleavedArrays", |gl| {
+            if let Err(error) = gl.client.arrays.set_interleaved(format, stride, pointer) {
+                gl.record_error(error);
+            }
+        });
+    }
+
+    unsafe fn glEnableClientState(array: u32) {
+        super::Context::with("glEnableClientState", |gl| {
+            let array = array.try_into().map_err(|_| Error::InvalidEnum);
+            match array.and_then(|array| gl.client.arrays.get_mut(array)) {
+                Ok(array) => array.enabled = true,
+                Err(error) => gl.record_error(error),
+            }
+        });
+    }
+
+    unsafe fn glDisableClientState(array: u32) {
+        super::Context::with("glDisableClientState", |gl| {
+            let array = array.try_into().map_err(|_| Error::InvalidEnum);
+            match array.and_then(|array| gl.client.arrays.get_mut(array)) {
+                Ok(array) => array.enabled = false,
+                Err(error) => gl.record_error(error),
+            }
+        });
+    }
+
+    unsafe fn glGetPointerv(pname: u32, params: *mut *mut c_void) {
+        super::Context::with("glGetPointerv", |gl| {
+            use Enum::*;
+            let arrays = &gl.client.arrays;
+            let pointer = match Enum::try_from(pname) {
+                Ok(VERTEX_ARRAY_POINTER) => arrays.vertex.pointer,
+                Ok(NORMAL_ARRAY_POINTER) => arrays.normal.pointer,
+                Ok(COLOR_ARRAY_POINTER) => arrays.color.pointer,
+                Ok(INDEX_ARRAY_POINTER) => arrays.index.pointer,
+                Ok(TEXTURE_COORD_ARRAY_POINTER) => arrays.texcoord.pointer,
+                Ok(EDGE_FLAG_ARRAY_POINTER) => arrays.edge_flag.pointer,
+                // FIXME(eddyb) return the buffers, once feedback and
+                // selection are supported (they're never set until then).
+                Ok(FEEDBACK_BUFFER_POINTER) | Ok(SELECTION_BUFFER_POINTER) => 0,
+                _ => return gl.record_error(Error::InvalidEnum),
+            };
+            params.write(pointer as *mut c_void);
+        });
+    }
+
+    unsafe fn glArrayElement(i: i32) {
+        super::Context::with("glArrayElement", |gl| {
+            if i < 0 {
+                return gl.record_error(Error::InvalidValue);
+            }
+            for cmd in gl.client.arrays.element(i as usize) {
+                cmd.submit(gl);
+            }
+        });
+    }
+
+    unsafe fn glDrawArrays(mode: u32, first: i32, count: i32) {
+        super::Context::with("glDrawArrays", |gl| {
+            if first < 0 {
+                return gl.record_error(Error::InvalidValue);
+            }
+            let first = first as usize;
+            if let Err(error) = draw_elements(gl, mode, count, |i| first + i) {
+                gl.record_error(error);
+            }
+        });
+    }
+
+    unsafe fn glDrawElements(mode: u32, count: i32, ty: u32, indices: *const c_void) {
+        super::Context::with("glDrawElements", |gl| {
+            use Enum::*;
+            let ty = match Enum::try_from(ty) {
+                Ok(ty @ UNSIGNED_BYTE) | Ok(ty @ UNSIGNED_SHORT) | Ok(ty @ UNSIGNED_INT) => ty,
+                _ => return gl.record_error(Error::InvalidEnum),
+            };
+            let result = draw_elements(gl, mode, count, |i| match ty {
+                UNSIGNED_BYTE => (indices as *const u8).add(i).read() as usize,
+                UNSIGNED_SHORT => (indices as *const u16).add(i).read() as usize,
+                _ => (indices as *const u32).add(i).read() as usize,
+            });
+            if let Err(error) = result {
+                gl.record_error(error);
+            }
+        });
+    }
+
+    unsafe fn glPushClientAttrib(mask: u32) {
+        super::Context::with("glPushClientAttrib", |gl| {
+            if gl.state.begin.is_some() {
+                return gl.record_error(Error::InvalidOperation);
+            }
+            if let Err(error) = gl.client.push_attrib(mask) {
+                gl.record_error(error);
+            }
+        });
+    }
+
+    unsafe fn glPopClientAttrib() {
+        super::Context::with("glPopClientAttrib", |gl| {
+            if gl.state.begin.is_some() {
+                return gl.record_error(Error::InvalidOperation);
+            }
+            if let Err(error) = gl.client.pop_attrib() {
+                gl.record_error(error);
+            }
+        });
+    }
+
+    unsafe fn glPixelStoref(pname: u32, param: f32) {
+        super::Context::with("glPixelStoref", |gl| {
+            if gl.state.begin.is_some() {
+                return gl.record_error(Error::InvalidOperation);
+            }
+            let pname = pname.try_into().map_err(|_| Error::InvalidEnum);
+            let result = pname.and_then(|pname| gl.client.pixel_store.set(pname, param as f64));
+            if let Err(error) = result {
+                gl.record_error(error);
+            }
+        });
+    }
+
+    unsafe fn glPixelStorei(pname: u32, param: i32) {
+        super::Context::with("glPixelStorei", |gl| {
+            if gl.state.begin.is_some() {
+                return gl.record_error(Error::InvalidOperation);
+            }
+            let pname = pname.try_into().map_err(|_| Error::InvalidEnum);
+            let result = pname.and_then(|pname| gl.client.pixel_store.set(pname, param as f64));
+            if let Err(error) = result {
+                gl.record_error(error);
+            }
+        });
+    }
+
+    unsafe fn glReadPixels(
+        x: i32,
+        y: i32,
+        width: i32,
+        height: i32,
+        format: u32,
+        ty: u32,
+        pixels: *mut c_void,
+    ) {
+        super::Context::with("glReadPixels", |gl| {
+            if gl.state.begin.is_some() {
+                return gl.record_error(Error::InvalidOperation);
+            }
+            if width < 0 || height < 0 {
+                return gl.record_error(Error::InvalidValue);
+            }
+            let (format, ty) = match (Enum::try_from(format), Enum::try_from(ty)) {
+                (Ok(format), Ok(ty)) => (format, ty),
+                _ => return gl.record_error(Error::InvalidEnum),
+            };
+            // NOTE(eddyb) there's no color index mode.
+            if format == Enum::COLOR_INDEX {
+                return gl.record_error(Error::InvalidOperation);
+            }
+            let framebuffer = match &gl.framebuffer {
+                Some(framebuffer) => framebuffer.clone(),
+                None => return,
+            };
+            let framebuffer = framebuffer.lock().unwrap();
+
+            // NOTE(eddyb) pixels outside the window are undefined, so they're
+            // left as zero, and the depth is converted like in `glGetFloatv`.
+            let max_depth = ((1 << super::raster::DEPTH_BITS) - 1) as f32;
+            let pixel_mode = &gl.state.pixel_mode;
+            let modes = &gl.client.pixel_store.pack;
+            let pixel_index = |i, j| {
+                let (x, y) = (x as i64 + i as i64, y as i64 + j as i64);
+                let inside = (0..framebuffer.width as i64).contains(&x)
+                    && (0..framebuffer.height as i64).contains(&y);
+                Some((y * framebuffer.width as i64 + x) as usize).filter(|_| inside)
+            };
+            let result = if format == Enum::STENCIL_INDEX {
+                super::pixel::pack_indices(
+                    modes,
+                    width as u32,
+                    height as u32,
+                    format,
+                    ty,
+                    pixels,
+                    |i, j| match pixel_index(i, j) {
+                        Some(index) => {
+                            pixel_mode.transfer_stencil(framebuffer.stencil[index] as u32)
+                        }
+                        None => 0.0,
+                    },
+                )
+            } else {
+                let pixel = |i, j| {
+                    let index = match pixel_index(i, j) {
+                        Some(index) => index,
+                        None => return Vec4::zero(),
+                    };
+                    if format == Enum::DEPTH_COMPONENT {
+                        let depth =
+                            pixel_mode.transfer_depth(framebuffer.depth[index] as f32 / max_depth);
+                        return Vec4::new(depth, 0.0, 0.0, 0.0);
+                    }
+                    let [r, g, b, a] = framebuffer.color[index];
+                    pixel_mode
+                        .transfer_color(Vec4::new(r as f32, g as f32, b as f32, a as f32) / 255.0)
+                };
+                super::pixel::pack(
+                    modes,
+                    width as u32,
+                    height as u32,
+                    format,
+                    ty,
+                    pixels,
+                    pixel,
+                )
+            };
+            drop(framebuffer);
+            if let Err(error) = result {
+                gl.record_error(error);
+            }
+        });
+    }
+
+    unsafe fn glGetTexImage(target: u32, level: i32, format: u32, ty: u32, pixels: *mut c_void) {
+        super::Context::with("glGetTexImage", |gl| {
+            if gl.state.begin.is_some() {
+                return gl.record_error(Error::InvalidOperation);
+            }
+            let enums = (
+                Enum::try_from(target)
+                    .map_err(|_| Error::InvalidEnum)
+                    .and_then(texture::Target::from_enum),
+                Enum::try_from(format),
+                Enum::try_from(ty),
+            );
+            let (target, format, ty) = match enums {
+                (Ok(target), Ok(format), Ok(ty)) => (target, format, ty),
+                _ => return gl.record_error(Error::InvalidEnum),
+            };
+            if !(0..texture::MAX_LEVELS as i32).contains(&level) {
+                return gl.record_error(Error::InvalidValue);
+            }
+
+            let shared = gl.share_group.lock().unwrap();
+            let image = gl.state.texture(&shared, target).level(level as usize);
+            let modes = &gl.client.pixel_store.pack;
+            let result = super::pixel::pack(
+                modes,
+                image.width,
+                image.height,
+                format,
+                ty,
+                pixels,
+                |x, y| image.read_back(x, y),
+            );
+            drop(shared);
+            if let Err(error) = result {
+                gl.record_error(error);
+            }
+        });
+    }
+
+    unsafe fn glGenTextures(n: i32, textures: *mut u32) {
+        super::Context::with("glGenTextures", |gl| {
+            if gl.state.begin.is_some() {
+                return gl.record_error(Error::InvalidOperation);
+            }
+            if n < 0 {
+                return gl.record_error(Error::InvalidValue);
+            }
+            // NOTE(eddyb) the names are reserved by creating textures which
+            // aren't bound to any target yet (see `Texture::target`).
+            let shared = &mut *gl.share_group.lock().unwrap();
+            let names = texture::find_unused_names(&shared.textures, n as u32);
+            for (i, &name) in names.iter().enumerate() {
+                shared.textures.insert(name, texture::Texture::default());
+                textures.add(i).write(name);
+            }
+        });
+    }
+
+    unsafe fn glDeleteTextures(n: i32, textures: *const u32) {
+        super::Context::with("glDeleteTextures", |gl| {
+            if gl.state.begin.is_some() {
+                return gl.record_error(Error::InvalidOperation);
+            }
+            if n < 0 {
+                return gl.record_error(Error::InvalidValue);
+            }
+            let shared = &mut *gl.share_group.lock().unwrap();
+            let bindings = &mut gl.state.texturing.bindings;
+            for &name in std::slice::from_raw_parts(textures, n as usize) {
+                // NOTE(eddyb) deleted textures that were bound in this
+                // context revert to the default textures (named `0`).
+                if name != 0 && shared.textures.swap_remove(&name).is_some() {
+                    for binding in bindings.iter_mut().filter(|b| **b == name) {
+                        *binding = 0;
+                    }
+                }
+            }
+        });
+    }
+
+    unsafe fn glIsTexture(texture: u32) -> u8 {
+        super::Context::with("glIsTexture", |gl| {
+            if gl.state.begin.is_some() {
+                gl.record_error(Error::InvalidOperation);
+                return 0;
+            }
+            let shared = gl.share_group.lock().unwrap();
+            let texture = shared.textures.get(&texture);
+            texture.is_some_and(|texture| texture.target.is_some()) as u8
+        })
+    }
+
+    unsafe fn glAreTexturesResident(n: i32, textures: *const u32, _residences: *mut u8) -> u8 {
+        super::Context::with("glAreTexturesResident", |gl| {
+            if gl.state.begin.is_some() {
+                gl.record_error(Error::InvalidOperation);
+                return 0;
+            }
+            if n < 0 {
+                gl.record_error(Error::InvalidValue);
+                return 0;
+            }
+            // NOTE(eddyb) all textures are always resident, so `residences`
+            // never needs to be written to, but the names are still checked.
+            let shared = gl.share_group.lock().unwrap();
+            let all_textures =
+                std::slice::from_raw_parts(textures, n as usize)
+                    .iter()
+                    .all(|name| {
+                        shared
+                            .textures
+                            .get(name)
+                            .is_some_and(|t| t.target.is_some())
+                    });
+            drop(shared);
+            if !all_textures {
+                gl.record_error(Error::InvalidValue);
+                return 0;
+            }
+            1
+        })
+    }
+
+    // NOTE(eddyb) rendering is synchronous, so there's nothing to wait for.
+    unsafe fn glFlush() {
+        super::Context::with("glFlush", |gl| {
+            if gl.state.begin.is_some() {
+                gl.record_error(Error::InvalidOperation);
+            }
+        });
+    }
+
+    unsafe fn glFinish() {
+        super::Context::with("glFinish", |gl| {
+            if gl.state.begin.is_some() {
+                gl.record_error(Error::InvalidOperation);
+            }
+        });
+    }
+
+    unsafe fn glIsEnabled(cap: u32) -> u8 {
+        super::Context::with("glIsEnabled", |gl| {
+            let cap = if gl.state.begin.is_some() {
+                Err(Error::InvalidOperation)
+            } else {
+                cap.try_into().map_err(|_| Error::InvalidEnum)
+            };
+            // NOTE(eddyb) the vertex arrays are client state, but they're
+            // still queried through `glIsEnabled`, like capabilities.
+            if let Ok(array) = cap.and_then(|cap| gl.client.arrays.get(cap)) {
+                return array.enabled as u8;
+            }
+            match cap.and_then(super::state::Capability::from_enum) {
+                Ok(cap) => gl.state.caps.contains(cap) as u8,
+                Err(error) => {
+                    gl.record_error(error);
+                    0
+                }
+            }
+        })
+    }
+
+    unsafe fn glGetBooleanv(pname: u32, params: *mut u8) {
+        get(
+            "glGetBooleanv",
+            params,
+            |v| v.to_bool() as u8,
+            |gl| gl.get(pname.try_into().map_err(|_| Error::InvalidEnum)?),
+        );
+    }
+
+    unsafe fn glGetIntegerv(pname: u32, params: *mut i32) {
+        get(
+            "glGetIntegerv",
+            params,
+            |v| v.to_i32(),
+            |gl| gl.get(pname.try_into().map_err(|_| Error::InvalidEnum)?),
+        );
+    }
+
+    unsafe fn glGetFloatv(pname: u32, params: *mut f32) {
+        get(
+            "glGetFloatv",
+            params,
+            |v| v.to_f64() as f32,
+            |gl| gl.get(pname.try_into().map_err(|_| Error::InvalidEnum)?),
+        );
+    }
+
+    unsafe fn glGetDoublev(pname: u32, params: *mut f64) {
+        get(
+            "glGetDoublev",
+            params,
+            |v| v.to_f64(),
+            |gl| gl.get(pname.try_into().map_err(|_| Error::InvalidEnum)?),
+        );
+    }
+
+    unsafe fn glGetLightfv(light: u32, pname: u32, params: *mut f32) {
+        get(
+            "glGetLightfv",
+            params,
+            |v| v.to_f64() as f32,
+            |gl| {
+                gl.get_light(
+                    light.try_into().map_err(|_| Error::InvalidEnum)?,
+                    pname.try_into().map_err(|_| Error::InvalidEnum)?,
+                )
+            },
+        );
+    }
+
+    unsafe fn glGetLightiv(light: u32, pname: u32, params: *mut i32) {
+        get(
+            "glGetLightiv",
+            params,
+            |v| v.to_i32(),
+            |gl| {
+                gl.get_light(
+                    light.try_into().map_err(|_| Error::InvalidEnum)?,
+                    pname.try_into().map_err(|_| Error::InvalidEnum)?,
+                )
+            },
+        );
+    }
+
+    unsafe fn glGetMaterialfv(face: u32, pname: u32, params: *mut f32) {
+        get(
+            "glGetMaterialfv",
+            params,
+            |v| v.to_f64() as f32,
+            |gl| {
+                gl.get_material(
+                    face.try_into().map_err(|_| Error::InvalidEnum)?,
+                    pname.try_into().map_err(|_| Error::InvalidEnum)?,
+                )
+            },
+        );
+    }
+
+    unsafe fn glGetMaterialiv(face: u32, pname: u32, params: *mut i32) {
+        get(
+            "glGetMaterialiv",
+            params,
+            |v| v.to_i32(),
+            |gl| {
+                gl.get_material(
+                    face.try_into().map_err(|_| Error::InvalidEnum)?,
+                    pname.try_into().map_err(|_| Error::InvalidEnum)?,
+                )
+            },
+        );
+    }
+
+    unsafe fn glGetTexParameterfv(target: u32, pname: u32, params: *mut f32) {
+        get(
+            "glGetTexParameterfv",
+            params,
+            |v| v.to_f64() as f32,
+            |gl| {
+                gl.get_tex_parameter(
+                    target.try_into().map_err(|_| Error::InvalidEnum)?,
+                    pname.try_into().map_err(|_| Error::InvalidEnum)?,
+                )
+            },
+        );
+    }
+
+    unsafe fn glGetTexParameteriv(target: u32, pname: u32, params: *mut i32) {
+        get(
+            "glGetTexParameteriv",
+            params,
+            |v| v.to_i32(),
+            |gl| {
+                gl.get_tex_parameter(
+                    target.try_into().map_err(|_| Error::InvalidEnum)?,
+                    pname.try_into().map_err(|_| Error::InvalidEnum)?,
+                )
+            },
+        );
+    }
+
+    unsafe fn glGetTexEnvfv(target: u32, pname: u32, params: *mut f32) {
+        get(
+            "glGetTexEnvfv",
+            params,
+            |v| v.to_f64() as f32,
+            |gl| {
+                gl.get_tex_env(
+                    target.try_into().map_err(|_| Error::InvalidEnum)?,
+                    pname.try_into().map_err(|_| Error::InvalidEnum)?,
+                )
+            },
+        );
+    }
+
+    unsafe fn glGetTexEnviv(target: u32, pname: u32, params: *mut i32) {
+        get(
+            "glGetTexEnviv",
+            params,
+            |v| v.to_i32(),
+            |gl| {
+                gl.get_tex_env(
+                    target.try_into().map_err(|_| Error::InvalidEnum)?,
+                    pname.try_into().map_err(|_| Error::InvalidEnum)?,
+                )
+            },
+        );
+    }
+
+    unsafe fn glGetTexGendv(coord: u32, pname: u32, params: *mut f64) {
+        get(
+            "glGetTexGendv",
+            params,
+            |v| v.to_f64(),
+            |gl| {
+                gl.get_tex_gen(
+                    coord.try_into().map_err(|_| Error::InvalidEnum)?,
+                    pname.try_into().map_err(|_| Error::InvalidEnum)?,
+                )
+            },
+        );
+    }
+
+    unsafe fn glGetTexGenfv(coord: u32, pname: u32, params: *mut f32) {
+        get(
+            "glGetTexGenfv",
+            params,
+            |v| v.to_f64() as f32,
+            |gl| {
+                gl.get_tex_gen(
+                    coord.try_into().map_err(|_| Error::InvalidEnum)?,
+                    pname.try_into().map_err(|_| Error::InvalidEnum)?,
+                )
+            },
+        );
+    }
+
+    unsafe fn glGetTexGeniv(coord: u32, pname: u32, params: *mut i32) {
+        get(
+            "glGetTexGeniv",
+            params,
+            |v| v.to_i32(),
+            |gl| {
+                gl.get_tex_gen(
+                    coord.try_into().map_err(|_| Error::InvalidEnum)?,
+                    pname.try_into().map_err(|_| Error::InvalidEnum)?,
+                )
+            },
+        );
+    }
+
+    unsafe fn glGetTexLevelParameterfv(target: u32, level: i32, pname: u32, params: *mut f32) {
+        get(
+            "glGetTexLevelParameterfv",
+            params,
+            |v| v.to_f64() as f32,
+            |gl| {
+                gl.get_tex_level_parameter(
+                    target.try_into().map_err(|_| Error::InvalidEnum)?,
+                    level,
+                    pname.try_into().map_err(|_| Error::InvalidEnum)?,
+                )
+            },
+        );
+    }
+
+    unsafe fn glGetTexLevelParameteriv(target: u32, level: i32, pname: u32, params: *mut i32) {
+        get(
+            "glGetTexLevelParameteriv",
+            params,
+            |v| v.to_i32(),
+            |gl| {
+                gl.get_tex_level_parameter(
+                    target.try_into().map_err(|_| Error::InvalidEnum)?,
+                    level,
+                    pname.try_into().map_err(|_| Error::InvalidEnum)?,
+                )
+            },
+        );
+    }
+
+    unsafe fn glGetClipPlane(plane: u32, equation: *mut f64) {
+        get(
+            "glGetClipPlane",
+            equation,
+            |v| v.to_f64(),
+            |gl| gl.get_clip_plane(plane.try_into().map_err(|_| Error::InvalidEnum)?),
+        );
+    }
+
+    unsafe fn glGetPixelMapfv(map: u32, values: *mut f32) {
+        get(
+            "glGetPixelMapfv",
+            values,
+            |v| v.to_f64() as f32,
+            |gl| gl.get_pixel_map(map.try_into().map_err(|_| Error::InvalidEnum)?),
+        );
+    }
+
+    unsafe fn glGetPixelMapuiv(map: u32, values: *mut u32) {
+        get(
+            "glGetPixelMapuiv",
+            values,
+            |v| v.to_f64() as u32,
+            |gl| gl.get_pixel_map(map.try_into().map_err(|_| Error::InvalidEnum)?),
+        );
+    }
+
+    unsafe fn glGetPixelMapusv(map: u32, values: *mut u16) {
+        get(
+            "glGetPixelMapusv",
+            values,
+            |v| v.to_f64() as u16,
+            |gl| gl.get_pixel_map(map.try_into().map_err(|_| Error::InvalidEnum)?),
+        );
+    }
+
+    unsafe fn glGetPolygonStipple(mask: *mut u8) {
+        super::Context::with("glGetPolygonStipple", |gl| {
+            if gl.state.begin.is_some() {
+                return gl.record_error(Error::InvalidOperation);
+            }
+            let modes = &gl.client.pixel_store.pack;
+            super::pixel::pack_stipple(modes, &gl.state.polygon_stipple.pattern, mask);
+        });
+    }
+
+    unsafe fn glGetError() -> u32 {
+        super::Context::with("glGetError", |gl| {
+            if gl.state.begin.is_some() {
+                gl.record_error(Error::InvalidOperation);
+                return Enum::NO_ERROR as u32;
+            }
+            gl.error.take().map_or(Enum::NO_ERROR, Enum::from) as u32
+        })
+    }
+
+    unsafe fn glGetString(name: u32) -> *const u8 {
+        let name = Enum::try_from(name);
+
+        eprintln!("glGetString(name={:?})", name);
+
+        super::Context::with("glGetString", |gl| {
+            if gl.state.begin.is_some() {
+                gl.record_error(Error::InvalidOperation);
+                return ptr::null();
+            }
+
+            use Enum::*;
+            match name {
+                Ok(VENDOR) | Ok(RENDERER) => "glOOF\0",
+                Ok(VERSION) => concat!(version_str!(major.minor), "\0"),
+                Ok(EXTENSIONS) => "\0",
+                _ => {
+                    gl.record_error(Error::InvalidEnum);
+                    return ptr::null();
+                }
+            }
+            .as_ptr()
+        })
+    }
 }
 
-unimplemented_entry_points! {
-    // GLU
-    glColor3f
-    glDisable
-    glEvalCoord1f
-    glEvalCoord2f
-    glEvalMesh1
-    glEvalMesh2
-    glEvalPoint1
-    glEvalPoint2
-    glGetFloatv
-    glGetIntegerv
-    glGetTexLevelParameteriv
-    glMap1f
-    glMap2f
-    glMapGrid1f
-    glMapGrid2d
-    glMapGrid2f
-    glMultMatrixd
-    glMultMatrixf
-    glNormal3fv
-    glOrtho
-    glPixelStorei
-    glPolygonMode
-    glPopAttrib
-    glPushAttrib
-    glScalef
-    glTexCoord2f
-    glTexImage1D
-    glTexImage2D
-    glVertex2f
-    glVertex2fv
-    glVertex3fv
-
-    // GLU / OpenGL 1.2
-    glTexImage3D
-
-    // glxgears
-    glDrawBuffer
-    glDeleteLists
-
-    // wine
-    glAccum
-    glAlphaFunc
-    glBitmap
-    glBlendFunc
-    glCallLists
-    glClearAccum
-    glClearColor
-    glClearDepth
-    glClearIndex
-    glClearStencil
-    glClipPlane
-    glColor3b
-    glColor3bv
-    glColor3d
-    glColor3dv
-    glColor3fv
-    glColor3i
-    glColor3iv
-    glColor3s
-    glColor3sv
-    glColor3ub
-    glColor3ubv
-    glColor3ui
-    glColor3uiv
-    glColor3us
-    glColor3usv
-    glColor4b
-    glColor4bv
-    glColor4d
-    glColor4dv
-    glColor4f
-    glColor4fv
-    glColor4i
-    glColor4iv
-    glColor4s
-    glColor4sv
-    glColor4ub
-    glColor4ubv
-    glColor4ui
-    glColor4uiv
-    glColor4us
-    glColor4usv
-    glColorMask
-    glColorMaterial
-    glCopyPixels
-    glCullFace
-    glDepthFunc
-    glDepthMask
-    glDepthRange
-    glDrawPixels
-    glEdgeFlag
-    glEdgeFlagv
-    glEvalCoord1d
-    glEvalCoord1dv
-    glEvalCoord1fv
-    glEvalCoord2d
-    glEvalCoord2dv
-    glEvalCoord2fv
-    glFeedbackBuffer
-    glFinish
-    glFlush
-    glFogf
-    glFogfv
-    glFogi
-    glFogiv
-    glFrontFace
-    glGetBooleanv
-    glGetClipPlane
-    glGetDoublev
-    glGetError
-    glGetLightfv
-    glGetLightiv
-    glGetMapdv
-    glGetMapfv
-    glGetMapiv
-    glGetMaterialfv
-    glGetMaterialiv
-    glGetPixelMapfv
-    glGetPixelMapuiv
-    glGetPixelMapusv
-    glGetPolygonStipple
-    glGetTexEnvfv
-    glGetTexEnviv
-    glGetTexGendv
-    glGetTexGenfv
-    glGetTexGeniv
-    glGetTexImage
-    glGetTexLevelParameterfv
-    glGetTexParameterfv
-    glGetTexParameteriv
-    glHint
-    glIndexMask
-    glIndexd
-    glIndexdv
-    glIndexf
-    glIndexfv
-    glIndexi
-    glIndexiv
-    glIndexs
-    glIndexsv
-    glInitNames
-    glIsEnabled
-    glIsList
-    glLightModelf
-    glLightModelfv
-    glLightModeli
-    glLightModeliv
-    glLightf
-    glLighti
-    glLightiv
-    glLineStipple
-    glLineWidth
-    glListBase
-    glLoadMatrixd
-    glLoadMatrixf
-    glLoadName
-    glLogicOp
-    glMap1d
-    glMap2d
-    glMapGrid1d
-    glMaterialf
-    glMateriali
-    glMaterialiv
-    glNormal3b
-    glNormal3bv
-    glNormal3d
-    glNormal3dv
-    glNormal3i
-    glNormal3iv
-    glNormal3s
-    glNormal3sv
-    glPassThrough
-    glPixelMapfv
-    glPixelMapuiv
-    glPixelMapusv
-    glPixelStoref
-    glPixelTransferf
-    glPixelTransferi
-    glPixelZoom
-    glPointSize
-    glPolygonStipple
-    glPopName
-    glPushName
-    glRasterPos2d
-    glRasterPos2dv
-    glRasterPos2f
-    glRasterPos2fv
-    glRasterPos2i
-    glRasterPos2iv
-    glRasterPos2s
-    glRasterPos2sv
-    glRasterPos3d
-    glRasterPos3dv
-    glRasterPos3f
-    glRasterPos3fv
-    glRasterPos3i
-    glRasterPos3iv
-    glRasterPos3s
-    glRasterPos3sv
-    glRasterPos4d
-    glRasterPos4dv
-    glRasterPos4f
-    glRasterPos4fv
-    glRasterPos4i
-    glRasterPos4iv
-    glRasterPos4s
-    glRasterPos4sv
-    glReadBuffer
-    glReadPixels
-    glRectd
-    glRectdv
-    glRectf
-    glRectfv
-    glRecti
-    glRectiv
-    glRects
-    glRectsv
-    glRenderMode
-    glRotated
-    glScaled
-    glScissor
-    glSelectBuffer
-    glStencilFunc
-    glStencilMask
-    glStencilOp
-    glTexCoord1d
-    glTexCoord1dv
-    glTexCoord1f
-    glTexCoord1fv
-    glTexCoord1i
-    glTexCoord1iv
-    glTexCoord1s
-    glTexCoord1sv
-    glTexCoord2d
-    glTexCoord2dv
-    glTexCoord2fv
-    glTexCoord2i
-    glTexCoord2iv
-    glTexCoord2s
-    glTexCoord2sv
-    glTexCoord3d
-    glTexCoord3dv
-    glTexCoord3f
-    glTexCoord3fv
-    glTexCoord3i
-    glTexCoord3iv
-    glTexCoord3s
-    glTexCoord3sv
-    glTexCoord4d
-    glTexCoord4dv
-    glTexCoord4f
-    glTexCoord4fv
-    glTexCoord4i
-    glTexCoord4iv
-    glTexCoord4s
-    glTexCoord4sv
-    glTexEnvf
-    glTexEnvfv
-    glTexEnvi
-    glTexEnviv
-    glTexGend
-    glTexGendv
-    glTexGenf
-    glTexGenfv
-    glTexGeni
-    glTexGeniv
-    glTexParameterf
-    glTexParameterfv
-    glTexParameteri
-    glTexParameteriv
-    glVertex2d
-    glVertex2dv
-    glVertex2i
-    glVertex2iv
-    glVertex2s
-    glVertex2sv
-    glVertex3d
-    glVertex3dv
-    glVertex3i
-    glVertex3iv
-    glVertex3s
-    glVertex3sv
-    glVertex4d
-    glVertex4dv
-    glVertex4f
-    glVertex4fv
-    glVertex4i
-    glVertex4iv
-    glVertex4s
-    glVertex4sv
-
-    // wine / OpenGL 1.1
-    glAreTexturesResident
-    glArrayElement
-    glBindTexture
-    glColorPointer
-    glCopyTexImage1D
-    glCopyTexImage2D
-    glCopyTexSubImage1D
-    glCopyTexSubImage2D
-    glDeleteTextures
-    glDisableClientState
-    glDrawArrays
-    glDrawElements
-    glEdgeFlagPointer
-    glEnableClientState
-    glGenTextures
-    glGetPointerv
-    glIndexub
-    glIndexubv
-    glIndexPointer
-    glInterleavedArrays
-    glIsTexture
-    glNormalPointer
-    glPolygonOffset
-    glPopClientAttrib
-    glPrioritizeTextures
-    glPushClientAttrib
-    glTexCoordPointer
-    glTexSubImage1D
-    glTexSubImage2D
-    glVertexPointer
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gl::Context;
+    use glam::{Vec3, Vec4};
+
+    #[test]
+    fn generated_entry_points() {
+        Box::new(Context::new(Default::default())).enter();
+        unsafe {
+            // Vector forms forward to their scalar forms.
+            glNormal3fv([1.0, 2.0, 3.0].as_ptr());
+            Context::with("test", |gl| {
+                assert_eq!(gl.state.current.normal, Vec3::new(1.0, 2.0, 3.0))
+            });
+            assert_eq!(glGetError(), Enum::NO_ERROR as u32);
+
+            // Entry points not implemented yet only report an error.
+            glSelectBuffer(0, ptr::null_mut());
+            assert_eq!(glGetError(), Enum::INVALID_OPERATION as u32);
+        }
+        Context::leave();
+
+        assert!(proc_address("glNormal3fv").is_some());
+        assert!(proc_address("glSelectBuffer").is_some());
+        assert!(proc_address("glXSwapBuffers").is_none());
+    }
+
+    #[test]
+    fn normalization() {
+        // Unsigned integers map to `[0, 1]`, and signed ones to `[-1, 1]`.
+        assert_eq!(0u8.normalize(), 0.0);
+        assert_eq!(u8::MAX.normalize(), 1.0);
+        assert_eq!(u16::MAX.normalize(), 1.0);
+        assert_eq!(u32::MAX.normalize(), 1.0);
+        assert_eq!(i8::MIN.normalize(), -1.0);
+        assert_eq!(i8::MAX.normalize(), 1.0);
+        assert_eq!(i16::MIN.normalize(), -1.0);
+        assert_eq!(i32::MAX.normalize(), 1.0);
+        assert_eq!(i32::MIN.normalize(), -1.0);
+
+        // Signed zero isn't exactly `0.0`, but the midpoint of `-1` and `0`.
+        assert_eq!((-1i8).normalize(), -(0i8.normalize()));
+        assert!(0i8.normalize() > 0.0);
+    }
+
+    #[test]
+    fn canonical_attribute_forms() {
+        Box::new(Context::new(Default::default())).enter();
+        Context::with("test", |gl| {
+            let framebuffer = super::super::raster::Framebuffer::new(10, 10);
+            gl.set_framebuffer(Some(Arc::new(std::sync::Mutex::new(framebuffer))));
+        });
+        glColor3ub(u8::MAX, 0, 0);
+        glNormal3b(0, 0, i8::MIN);
+        glTexCoord1i(2);
+        glRasterPos2i(0, 0);
+        Context::with("test", |gl| {
+            let current = &gl.state.current;
+            assert_eq!(current.color, Vec4::new(1.0, 0.0, 0.0, 1.0));
+            assert_eq!(current.normal.z, -1.0);
+            assert_eq!(current.texcoord, Vec4::new(2.0, 0.0, 0.0, 1.0));
+
+            // The raster position is processed like a vertex.
+            assert!(current.raster.valid);
+            assert_eq!(current.raster.position, Vec4::new(5.0, 5.0, 0.5, 1.0));
+            assert_eq!(current.raster.color, current.color);
+            assert_eq!(current.raster.texcoord, current.texcoord);
+        });
+        glRasterPos2i(2, 0);
+        Context::with("test", |gl| assert!(!gl.state.current.raster.valid));
+        Context::leave();
+    }
+
+    #[test]
+    fn enum_aliases() {
+        assert_eq!(Enum::NONE, Enum::POINTS);
+        assert_eq!(Enum::FALSE as u32, 0);
+        assert_eq!(Enum::TRUE, Enum::LINES);
+        assert_eq!(Enum::try_from(0x1B02), Ok(Enum::FILL));
+        assert!(Enum::try_from(0xdead).is_err());
+    }
 }
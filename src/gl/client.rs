@@ -0,0 +1,419 @@
+//! Client state (i.e. vertex arrays and pixel storage modes), which is kept
+//! separately from the rest of the GL state (see `State`), is never compiled
+//! into display lists, and is saved by `glPushClientAttrib`, instead of
+//! `glPushAttrib`.
+
+use crate::gl::api_1_0::{self, Command, Enum, Normalize};
+use crate::gl::pixel::PixelStore;
+use crate::gl::Error;
+use glam::{Vec3, Vec4};
+use smallvec::SmallVec;
+use std::convert::TryFrom;
+use std::ops::RangeInclusive;
+
+/// Maximum depth of the client attribute stack (see `glPushClientAttrib`).
+pub const MAX_CLIENT_ATTRIB_STACK_DEPTH: usize = 16;
+
+macro_rules! client_attrib_groups {
+    ($($group:ident: $ty:ty = $bit:ident),* $(,)?) => {
+        /// Attribute groups saved by `glPushClientAttrib`.
+        #[derive(Debug)]
+        struct SavedClientAttribs {
+            $($group: Option<$ty>),*
+        }
+
+        impl ClientState {
+            pub fn push_attrib(&mut self, mask: u32) -> Result<(), Error> {
+                if self.attrib_stack.len() >= MAX_CLIENT_ATTRIB_STACK_DEPTH {
+                    return Err(Error::StackOverflow);
+                }
+                self.attrib_stack.push(SavedClientAttribs {
+                    $($group: if mask & api_1_0::$bit != 0 {
+                        Some(self.$group.clone())
+                    } else {
+                        None
+                    }),*
+                });
+                Ok(())
+            }
+
+            pub fn pop_attrib(&mut self) -> Result<(), Error> {
+                let saved = self.attrib_stack.pop().ok_or(Error::StackUnderflow)?;
+                $(if let Some(group) = saved.$group {
+                    self.$group = group;
+                })*
+                Ok(())
+            }
+        }
+    };
+}
+
+#[derive(Debug, Default)]
+pub struct ClientState {
+    // Attribute groups.
+    pub arrays: VertexArrays,
+    pub pixel_store: PixelStore,
+
+    attrib_stack: Vec<SavedClientAttribs>,
+}
+
+client_attrib_groups! {
+    arrays: VertexArrays = CLIENT_VERTEX_ARRAY_BIT,
+    pixel_store: PixelStore = CLIENT_PIXEL_STORE_BIT,
+}
+
+impl ClientState {
+    pub fn attrib_stack_depth(&self) -> usize {
+        self.attrib_stack.len()
+    }
+}
+
+/// A vertex array, as specified by e.g. `glVertexPointer`.
+#[derive(Copy, Clone, Debug)]
+pub struct Array {
+    pub enabled: bool,
+
+    /// Number of components in each element.
+    pub size: u32,
+
+    pub ty: Enum,
+
+    /// Offset between consecutive elements, in bytes, with `0` indicating
+    /// that they're tightly packed (see `Array::stride_or_default`).
+    pub stride: u32,
+
+    /// Address of the first element, kept as an integer to allow `Send`ing
+    /// `Context`s between threads (which is only safe because the contents
+    /// are only accessed while the array is in use, according to the spec).
+    pub pointer: usize,
+}
+
+impl Array {
+    fn new(size: u32, ty: Enum) -> Self {
+        Array {
+            enabled: false,
+            size,
+            ty,
+            stride: 0,
+            pointer: 0,
+        }
+    }
+
+    /// Set the array's location and format, given its valid sizes and types.
+    pub fn set_pointer(
+        &mut self,
+        size: i32,
+        ty: u32,
+        stride: i32,
+        pointer: *const std::ffi::c_void,
+        sizes: RangeInclusive<i32>,
+        types: &[Enum],
+    ) -> Result<(), Error> {
+        let ty = Enum::try_from(ty)
+            .ok()
+            .filter(|ty| types.contains(ty))
+            .ok_or(Error::InvalidEnum)?;
+        if !sizes.contains(&size) || stride < 0 {
+            return Err(Error::InvalidValue);
+        }
+        self.size = size as u32;
+        self.ty = ty;
+        self.stride = stride as u32;
+        self.pointer = pointer as usize;
+        Ok(())
+    }
+
+    fn stride_or_default(&self) -> usize {
+        if self.stride != 0 {
+            return self.stride as usize;
+        }
+        use Enum::*;
+        let component_size = match self.ty {
+            BYTE | UNSIGNED_BYTE => 1,
+            SHORT | UNSIGNED_SHORT => 2,
+            INT | UNSIGNED_INT | FLOAT => 4,
+            DOUBLE => 8,
+            _ => unreachable!("glOOF: invalid vertex array type {:?}", self.ty),
+        };
+        self.size as usize * component_size
+    }
+
+    /// Read the element at `index`, with any components missing from it
+    /// taken from `defaults`, and integer components converted to floating-point
+    /// either directly, or by mapping their range to `[0, 1]`/`[-1, 1]`
+    /// (if `normalized`, see `Normalize`).
+    ///
+    /// # Safety
+    ///
+    /// The array must contain (at least) `index + 1` elements.
+    unsafe fn read(&self, index: usize, defaults: Vec4, normalized: bool) -> Vec4 {
+        let element = (self.pointer + index * self.stride_or_default()) as *const u8;
+        let mut components = defaults;
+        for (i, dst) in components
+            .as_mut()
+            .iter_mut()
+            .take(self.size as usize)
+            .enumerate()
+        {
+            macro_rules! read {
+                ($ty:ty) => {{
+                    let x = (element as *const $ty).add(i).read_unaligned();
+                    if normalized {
+                        x.normalize()
+                    } else {
+                        x as f32
+                    }
+                }};
+            }
+            use Enum::*;
+            *dst = match self.ty {
+                BYTE => read!(i8),
+                UNSIGNED_BYTE => read!(u8),
+                SHORT => read!(i16),
+                UNSIGNED_SHORT => read!(u16),
+                INT => read!(i32),
+                UNSIGNED_INT => read!(u32),
+                FLOAT => (element as *const f32).add(i).read_unaligned(),
+                DOUBLE => (element as *const f64).add(i).read_unaligned() as f32,
+                _ => unreachable!("glOOF: invalid vertex array type {:?}", self.ty),
+            };
+        }
+        components
+    }
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct VertexArrays {
+    pub vertex: Array,
+    pub normal: Array,
+    pub color: Array,
+    pub index: Array,
+    pub texcoord: Array,
+    pub edge_flag: Array,
+}
+
+impl Default for VertexArrays {
+    fn default() -> Self {
+        use Enum::*;
+        VertexArrays {
+            vertex: Array::new(4, FLOAT),
+            normal: Array::new(3, FLOAT),
+            color: Array::new(4, FLOAT),
+            index: Array::new(1, FLOAT),
+            texcoord: Array::new(4, FLOAT),
+            // NOTE(eddyb) the type of edge flags is always `GLboolean`.
+            edge_flag: Array::new(1, UNSIGNED_BYTE),
+        }
+    }
+}
+
+/// Vertex formats for `glInterleavedArrays`: the number of texture coordinate,
+/// color (and their type), and vertex components, and whether there's a normal.
+const INTERLEAVED_FORMATS: &[(Enum, u32, u32, Enum, bool, u32)] = {
+    use Enum::*;
+    &[
+        (V2F, 0, 0, FLOAT, false, 2),
+        (V3F, 0, 0, FLOAT, false, 3),
+        (C4UB_V2F, 0, 4, UNSIGNED_BYTE, false, 2),
+        (C4UB_V3F, 0, 4, UNSIGNED_BYTE, false, 3),
+        (C3F_V3F, 0, 3, FLOAT, false, 3),
+        (N3F_V3F, 0, 0, FLOAT, true, 3),
+        (C4F_N3F_V3F, 0, 4, FLOAT, true, 3),
+        (T2F_V3F, 2, 0, FLOAT, false, 3),
+        (T4F_V4F, 4, 0, FLOAT, false, 4),
+        (T2F_C4UB_V3F, 2, 4, UNSIGNED_BYTE, false, 3),
+        (T2F_C3F_V3F, 2, 3, FLOAT, false, 3),
+        (T2F_N3F_V3F, 2, 0, FLOAT, true, 3),
+        (T2F_C4F_N3F_V3F, 2, 4, FLOAT, true, 3),
+        (T4F_C4F_N3F_V4F, 4, 4, FLOAT, true, 4),
+    ]
+};
+
+impl VertexArrays {
+    /// Get the array named by `array` (e.g. `GL_VERTEX_ARRAY`).
+    pub fn get(&self, array: Enum) -> Result<&Array, Error> {
+        use Enum::*;
+        Ok(match array {
+            VERTEX_ARRAY => &self.vertex,
+            NORMAL_ARRAY => &self.normal,
+            COLOR_ARRAY => &self.color,
+            INDEX_ARRAY => &self.index,
+            TEXTURE_COORD_ARRAY => &self.texcoord,
+            EDGE_FLAG_ARRAY => &self.edge_flag,
+            _ => return Err(Error::InvalidEnum),
+        })
+    }
+
+    pub fn get_mut(&mut self, array: Enum) -> Result<&mut Array, Error> {
+        use Enum::*;
+        Ok(match array {
+            VERTEX_ARRAY => &mut self.vertex,
+            NORMAL_ARRAY => &mut self.normal,
+            COLOR_ARRAY => &mut self.color,
+            INDEX_ARRAY => &mut self.index,
+            TEXTURE_COORD_ARRAY => &mut self.texcoord,
+            EDGE_FLAG_ARRAY => &mut self.edge_flag,
+            _ => return Err(Error::InvalidEnum),
+        })
+    }
+
+    /// Specify (and enable) the arrays for one of the `glInterleavedArrays`
+    /// formats, disabling all the arrays not used by that format.
+    pub fn set_interleaved(
+        &mut self,
+        format: u32,
+        stride: i32,
+        pointer: *const std::ffi::c_void,
+    ) -> Result<(), Error> {
+        let &(_, texcoords, colors, color_ty, normal, vertices) = INTERLEAVED_FORMATS
+            .iter()
+            .find(|&&(f, ..)| f as u32 == format)
+            .ok_or(Error::InvalidEnum)?;
+        if stride < 0 {
+            return Err(Error::InvalidValue);
+        }
+
+        // NOTE(eddyb) all components are 4 bytes, except for `GL_UNSIGNED_BYTE`
+        // colors, but there's always exactly 4 of those, so 4 bytes in total.
+        let color_bytes = match color_ty {
+            Enum::UNSIGNED_BYTE => 4,
+            _ => colors * 4,
+        };
+        let normals = if normal { 3 } else { 0 };
+        let bytes = [texcoords * 4, color_bytes, normals * 4, vertices * 4];
+
+        // The stride defaults to the total size of all the components.
+        let stride = if stride != 0 {
+            stride as u32
+        } else {
+            bytes.iter().sum()
+        };
+
+        let mut arrays = [
+            (&mut self.texcoord, texcoords, Enum::FLOAT),
+            (&mut self.color, colors, color_ty),
+            (&mut self.normal, normals, Enum::FLOAT),
+            (&mut self.vertex, vertices, Enum::FLOAT),
+        ];
+        let mut offset = 0;
+        for ((array, size, ty), &bytes) in arrays.iter_mut().zip(&bytes) {
+            array.enabled = *size != 0;
+            if array.enabled {
+                array.size = *size;
+                array.ty = *ty;
+                array.stride = stride;
+                array.pointer = pointer as usize + offset;
+            }
+            offset += bytes as usize;
+        }
+        self.index.enabled = false;
+        self.edge_flag.enabled = false;
+
+        Ok(())
+    }
+
+    /// Read the element at `index` from all the enabled arrays, as the
+    /// commands that `glArrayElement(index)` is equivalent to.
+    ///
+    /// # Safety
+    ///
+    /// All the enabled arrays must contain (at least) `index + 1` elements.
+    pub unsafe fn element(&self, index: usize) -> SmallVec<[Command; 6]> {
+        use Command::*;
+        let mut cmds = SmallVec::new();
+        if self.edge_flag.enabled {
+            let flag = self.edge_flag.read(index, Vec4::zero(), false).x != 0.0;
+            cmds.push(glEdgeFlag(flag));
+        }
+        if self.texcoord.enabled {
+            let t = self.texcoord.read(index, Vec4::unit_w(), false);
+            cmds.push(glTexCoord4f(t.x, t.y, t.z, t.w));
+        }
+        if self.color.enabled {
+            let c = self.color.read(index, Vec4::unit_w(), true);
+            cmds.push(glColor4f(c.x, c.y, c.z, c.w));
+        }
+        if self.index.enabled {
+            cmds.push(glIndexf(self.index.read(index, Vec4::zero(), false).x));
+        }
+        if self.normal.enabled {
+            let n: Vec3 = self.normal.read(index, Vec4::zero(), true).truncate();
+            cmds.push(glNormal3f(n.x, n.y, n.z));
+        }
+        if self.vertex.enabled {
+            let v = self.vertex.read(index, Vec4::unit_w(), false);
+            cmds.push(glVertex4f(v.x, v.y, v.z, v.w));
+        }
+        cmds
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gl::api_1_0::Command::*;
+
+    #[test]
+    fn interleaved_formats() {
+        let mut arrays = VertexArrays::default();
+        arrays.index.enabled = true;
+        arrays.normal.enabled = true;
+
+        // Two `GL_T2F_C4UB_V3F` elements, tightly packed.
+        let mut data = vec![];
+        for i in 0..2 {
+            let i = i as f32;
+            data.extend([i, 0.5].iter().flat_map(|x| x.to_ne_bytes().to_vec()));
+            data.extend(&[255, 0, 51, 255]);
+            data.extend([1.0, 2.0, i].iter().flat_map(|x| x.to_ne_bytes().to_vec()));
+        }
+        let pointer = data.as_ptr() as usize;
+        arrays
+            .set_interleaved(Enum::T2F_C4UB_V3F as u32, 0, pointer as *const _)
+            .unwrap();
+
+        // Arrays not used by the format are disabled, and the stride defaults
+        // to the size of a whole element, instead of just each component.
+        assert!(!arrays.index.enabled && !arrays.normal.enabled);
+        let layout = |a: &Array| (a.enabled, a.size, a.ty, a.stride, a.pointer - pointer);
+        assert_eq!(layout(&arrays.texcoord), (true, 2, Enum::FLOAT, 24, 0));
+        assert_eq!(layout(&arrays.color), (true, 4, Enum::UNSIGNED_BYTE, 24, 8));
+        assert_eq!(layout(&arrays.vertex), (true, 3, Enum::FLOAT, 24, 12));
+
+        let element = unsafe { arrays.element(1) };
+        assert!(matches!(
+            element[..],
+            [
+                glTexCoord4f(1.0, 0.5, 0.0, 1.0),
+                glColor4f(1.0, 0.0, b, 1.0),
+                glVertex4f(1.0, 2.0, 1.0, 1.0),
+            ] if b == 0.2
+        ));
+
+        // Explicit strides are used as-is, and normals are always after colors.
+        arrays
+            .set_interleaved(Enum::T4F_C4F_N3F_V4F as u32, 64, pointer as *const _)
+            .unwrap();
+        assert_eq!(layout(&arrays.texcoord), (true, 4, Enum::FLOAT, 64, 0));
+        assert_eq!(layout(&arrays.color), (true, 4, Enum::FLOAT, 64, 16));
+        assert_eq!(layout(&arrays.normal), (true, 3, Enum::FLOAT, 64, 32));
+        assert_eq!(layout(&arrays.vertex), (true, 4, Enum::FLOAT, 64, 44));
+
+        arrays
+            .set_interleaved(Enum::N3F_V3F as u32, 0, pointer as *const _)
+            .unwrap();
+        assert!(!arrays.texcoord.enabled && !arrays.color.enabled);
+        assert_eq!(layout(&arrays.normal), (true, 3, Enum::FLOAT, 24, 0));
+        assert_eq!(layout(&arrays.vertex), (true, 3, Enum::FLOAT, 24, 12));
+
+        let null = std::ptr::null();
+        assert_eq!(
+            arrays.set_interleaved(Enum::RGBA as u32, 0, null),
+            Err(Error::InvalidEnum)
+        );
+        assert_eq!(
+            arrays.set_interleaved(Enum::V3F as u32, -1, null),
+            Err(Error::InvalidValue)
+        );
+    }
+}
@@ -1,6 +1,6 @@
 use derive_try_from_primitive::TryFromPrimitive;
 
-#[allow(non_camel_case_types)]
+#[allow(dead_code, non_camel_case_types, clippy::upper_case_acronyms)]
 #[derive(TryFromPrimitive)]
 #[repr(u32)]
 enum Enum {
@@ -0,0 +1,335 @@
+//! Per-fragment operations which combine fragments with the framebuffer
+//! (stencil operations, blending, dithering and logical operations, see
+//! `raster::Rasterizer`).
+
+use crate::gl::api_1_0::Enum;
+use crate::gl::Error;
+use glam::Vec4;
+
+/// The action taken on the stencil buffer value of a fragment, based on the
+/// outcome of the stencil and depth tests (see `glStencilOp`).
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum StencilOp {
+    Keep,
+    Zero,
+    Replace,
+    Incr,
+    Decr,
+    Invert,
+}
+
+impl StencilOp {
+    pub fn from_enum(op: Enum) -> Result<Self, Error> {
+        use Enum::*;
+        Ok(match op {
+            KEEP => StencilOp::Keep,
+            Enum::ZERO => StencilOp::Zero,
+            REPLACE => StencilOp::Replace,
+            INCR => StencilOp::Incr,
+            DECR => StencilOp::Decr,
+            INVERT => StencilOp::Invert,
+            _ => return Err(Error::InvalidEnum),
+        })
+    }
+
+    pub fn to_enum(self) -> Enum {
+        match self {
+            StencilOp::Keep => Enum::KEEP,
+            StencilOp::Zero => Enum::ZERO,
+            StencilOp::Replace => Enum::REPLACE,
+            StencilOp::Incr => Enum::INCR,
+            StencilOp::Decr => Enum::DECR,
+            StencilOp::Invert => Enum::INVERT,
+        }
+    }
+
+    /// The new stencil buffer value, replacing `value`, with `reference` being
+    /// the reference value (see `glStencilFunc`), and `max` the largest value
+    /// representable in the stencil buffer (`Incr` and `Decr` saturate).
+    pub fn apply(self, value: u8, reference: u8, max: u8) -> u8 {
+        match self {
+            StencilOp::Keep => value,
+            StencilOp::Zero => 0,
+            StencilOp::Replace => reference,
+            StencilOp::Incr => value.saturating_add(1).min(max),
+            StencilOp::Decr => value.saturating_sub(1),
+            StencilOp::Invert => !value & max,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum BlendFactor {
+    Zero,
+    One,
+    SrcColor,
+    OneMinusSrcColor,
+    DstColor,
+    OneMinusDstColor,
+    SrcAlpha,
+    OneMinusSrcAlpha,
+    DstAlpha,
+    OneMinusDstAlpha,
+    SrcAlphaSaturate,
+}
+
+impl BlendFactor {
+    fn from_enum(factor: Enum) -> Result<Self, Error> {
+        use Enum::*;
+        Ok(match factor {
+            Enum::ZERO => BlendFactor::Zero,
+            Enum::ONE => BlendFactor::One,
+            SRC_COLOR => BlendFactor::SrcColor,
+            ONE_MINUS_SRC_COLOR => BlendFactor::OneMinusSrcColor,
+            DST_COLOR => BlendFactor::DstColor,
+            ONE_MINUS_DST_COLOR => BlendFactor::OneMinusDstColor,
+            SRC_ALPHA => BlendFactor::SrcAlpha,
+            ONE_MINUS_SRC_ALPHA => BlendFactor::OneMinusSrcAlpha,
+            DST_ALPHA => BlendFactor::DstAlpha,
+            ONE_MINUS_DST_ALPHA => BlendFactor::OneMinusDstAlpha,
+            SRC_ALPHA_SATURATE => BlendFactor::SrcAlphaSaturate,
+            _ => return Err(Error::InvalidEnum),
+        })
+    }
+
+    /// Parse the source and destination factors passed to `glBlendFunc`
+    /// (where the source can't use its own color, nor the destination its
+    /// own color, and `GL_SRC_ALPHA_SATURATE` is only valid for the source).
+    pub fn from_enums(src: Enum, dst: Enum) -> Result<(Self, Self), Error> {
+        use BlendFactor::*;
+        let src = match Self::from_enum(src)? {
+            SrcColor | OneMinusSrcColor => return Err(Error::InvalidEnum),
+            src => src,
+        };
+        let dst = match Self::from_enum(dst)? {
+            DstColor | OneMinusDstColor | SrcAlphaSaturate => return Err(Error::InvalidEnum),
+            dst => dst,
+        };
+        Ok((src, dst))
+    }
+
+    pub fn to_enum(self) -> Enum {
+        match self {
+            BlendFactor::Zero => Enum::ZERO,
+            BlendFactor::One => Enum::ONE,
+            BlendFactor::SrcColor => Enum::SRC_COLOR,
+            BlendFactor::OneMinusSrcColor => Enum::ONE_MINUS_SRC_COLOR,
+            BlendFactor::DstColor => Enum::DST_COLOR,
+            BlendFactor::OneMinusDstColor => Enum::ONE_MINUS_DST_COLOR,
+            BlendFactor::SrcAlpha => Enum::SRC_ALPHA,
+            BlendFactor::OneMinusSrcAlpha => Enum::ONE_MINUS_SRC_ALPHA,
+            BlendFactor::DstAlpha => Enum::DST_ALPHA,
+            BlendFactor::OneMinusDstAlpha => Enum::ONE_MINUS_DST_ALPHA,
+            BlendFactor::SrcAlphaSaturate => Enum::SRC_ALPHA_SATURATE,
+        }
+    }
+
+    /// The (per-component) factor, for the `src` (incoming fragment) and
+    /// `dst` (color buffer) colors.
+    fn factor(self, src: Vec4, dst: Vec4) -> Vec4 {
+        match self {
+            BlendFactor::Zero => Vec4::zero(),
+            BlendFactor::One => Vec4::one(),
+            BlendFactor::SrcColor => src,
+            BlendFactor::OneMinusSrcColor => Vec4::one() - src,
+            BlendFactor::DstColor => dst,
+            BlendFactor::OneMinusDstColor => Vec4::one() - dst,
+            BlendFactor::SrcAlpha => Vec4::splat(src.w),
+            BlendFactor::OneMinusSrcAlpha => Vec4::splat(1.0 - src.w),
+            BlendFactor::DstAlpha => Vec4::splat(dst.w),
+            BlendFactor::OneMinusDstAlpha => Vec4::splat(1.0 - dst.w),
+            BlendFactor::SrcAlphaSaturate => {
+                let f = src.w.min(1.0 - dst.w);
+                Vec4::new(f, f, f, 1.0)
+            }
+        }
+    }
+}
+
+/// Blend the `src` (incoming fragment) and `dst` (color buffer) colors.
+pub fn blend(factors: (BlendFactor, BlendFactor), src: Vec4, dst: Vec4) -> Vec4 {
+    let (src_factor, dst_factor) = (factors.0.factor(src, dst), factors.1.factor(src, dst));
+    (src * src_factor + dst * dst_factor)
+        .max(Vec4::zero())
+        .min(Vec4::one())
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum LogicOp {
+    Clear,
+    And,
+    AndReverse,
+    Copy,
+    AndInverted,
+    Noop,
+    Xor,
+    Or,
+    Nor,
+    Equiv,
+    Invert,
+    OrReverse,
+    CopyInverted,
+    OrInverted,
+    Nand,
+    Set,
+}
+
+impl LogicOp {
+    pub fn from_enum(op: Enum) -> Result<Self, Error> {
+        use Enum::*;
+        Ok(match op {
+            CLEAR => LogicOp::Clear,
+            AND => LogicOp::And,
+            AND_REVERSE => LogicOp::AndReverse,
+            COPY => LogicOp::Copy,
+            AND_INVERTED => LogicOp::AndInverted,
+            NOOP => LogicOp::Noop,
+            XOR => LogicOp::Xor,
+            OR => LogicOp::Or,
+            NOR => LogicOp::Nor,
+            EQUIV => LogicOp::Equiv,
+            INVERT => LogicOp::Invert,
+            OR_REVERSE => LogicOp::OrReverse,
+            COPY_INVERTED => LogicOp::CopyInverted,
+            OR_INVERTED => LogicOp::OrInverted,
+            NAND => LogicOp::Nand,
+            SET => LogicOp::Set,
+            _ => return Err(Error::InvalidEnum),
+        })
+    }
+
+    pub fn to_enum(self) -> Enum {
+        match self {
+            LogicOp::Clear => Enum::CLEAR,
+            LogicOp::And => Enum::AND,
+            LogicOp::AndReverse => Enum::AND_REVERSE,
+            LogicOp::Copy => Enum::COPY,
+            LogicOp::AndInverted => Enum::AND_INVERTED,
+            LogicOp::Noop => Enum::NOOP,
+            LogicOp::Xor => Enum::XOR,
+            LogicOp::Or => Enum::OR,
+            LogicOp::Nor => Enum::NOR,
+            LogicOp::Equiv => Enum::EQUIV,
+            LogicOp::Invert => Enum::INVERT,
+            LogicOp::OrReverse => Enum::OR_REVERSE,
+            LogicOp::CopyInverted => Enum::COPY_INVERTED,
+            LogicOp::OrInverted => Enum::OR_INVERTED,
+            LogicOp::Nand => Enum::NAND,
+            LogicOp::Set => Enum::SET,
+        }
+    }
+
+    /// Combine the bits of `s` (incoming fragment) and `d` (color buffer).
+    pub fn apply(self, s: u8, d: u8) -> u8 {
+        match self {
+            LogicOp::Clear => 0,
+            LogicOp::And => s & d,
+            LogicOp::AndReverse => s & !d,
+            LogicOp::Copy => s,
+            LogicOp::AndInverted => !s & d,
+            LogicOp::Noop => d,
+            LogicOp::Xor => s ^ d,
+            LogicOp::Or => s | d,
+            LogicOp::Nor => !(s | d),
+            LogicOp::Equiv => !(s ^ d),
+            LogicOp::Invert => !d,
+            LogicOp::OrReverse => s | !d,
+            LogicOp::CopyInverted => !s,
+            LogicOp::OrInverted => !s | d,
+            LogicOp::Nand => !(s & d),
+            LogicOp::Set => !0,
+        }
+    }
+}
+
+/// Convert `color` to RGBA8 (like `raster::color_to_rgba8`), but choosing
+/// between the two nearest values of each component based on the window
+/// position `(x, y)` (using a 4x4 ordered dither pattern), instead of rounding.
+pub fn dither(color: Vec4, x: i32, y: i32) -> [u8; 4] {
+    const BAYER: [[u8; 4]; 4] = [[0, 8, 2, 10], [12, 4, 14, 6], [3, 11, 1, 9], [15, 7, 13, 5]];
+
+    // NOTE(eddyb) the threshold is never `0` or `1`, so that colors which are
+    // exactly representable (i.e. multiples of `1/255`) are left unchanged.
+    let threshold = (BAYER[(y & 3) as usize][(x & 3) as usize] as f32 + 0.5) / 16.0;
+    let c = color.max(Vec4::zero()).min(Vec4::one()) * 255.0;
+    [c.x, c.y, c.z, c.w].map(|c| (c + threshold).floor().min(255.0) as u8)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::convert::TryFrom;
+
+    #[test]
+    fn stencil_op_saturates() {
+        assert_eq!(StencilOp::Incr.apply(3, 0, 3), 3);
+        assert_eq!(StencilOp::Incr.apply(2, 0, 3), 3);
+        assert_eq!(StencilOp::Decr.apply(0, 0, 3), 0);
+        assert_eq!(StencilOp::Invert.apply(1, 0, 3), 2);
+        assert_eq!(StencilOp::Replace.apply(1, 2, 3), 2);
+    }
+
+    #[test]
+    fn blend_func_validation() {
+        use Enum::*;
+        assert_eq!(
+            BlendFactor::from_enums(SRC_ALPHA, ONE_MINUS_SRC_ALPHA),
+            Ok((BlendFactor::SrcAlpha, BlendFactor::OneMinusSrcAlpha))
+        );
+        assert_eq!(
+            BlendFactor::from_enums(SRC_COLOR, Enum::ZERO),
+            Err(Error::InvalidEnum)
+        );
+        assert_eq!(
+            BlendFactor::from_enums(Enum::ONE, DST_COLOR),
+            Err(Error::InvalidEnum)
+        );
+        assert_eq!(
+            BlendFactor::from_enums(Enum::ONE, SRC_ALPHA_SATURATE),
+            Err(Error::InvalidEnum)
+        );
+    }
+
+    #[test]
+    fn blend_clamps() {
+        use BlendFactor::*;
+        let src = Vec4::new(1.0, 0.5, 0.0, 0.5);
+        let dst = Vec4::new(0.0, 0.5, 1.0, 1.0);
+        assert_eq!(
+            blend((SrcAlpha, OneMinusSrcAlpha), src, dst),
+            Vec4::new(0.5, 0.5, 0.5, 0.75)
+        );
+        assert_eq!(blend((One, One), src, dst), Vec4::new(1.0, 1.0, 1.0, 1.0));
+        assert_eq!(
+            blend((SrcAlphaSaturate, Zero), src, dst),
+            Vec4::new(0.0, 0.0, 0.0, 0.5)
+        );
+    }
+
+    #[test]
+    fn logic_op_truth_tables() {
+        // NOTE(eddyb) the low 4 bits of each logic op enum are its truth table,
+        // i.e. its result for these `s` and `d` bits.
+        let (s, d) = (0b0011, 0b0101);
+        for value in Enum::CLEAR as u32..=Enum::SET as u32 {
+            let op = LogicOp::from_enum(Enum::try_from(value).unwrap()).unwrap();
+            assert_eq!(op.apply(s, d) & 0xf, (value & 0xf) as u8, "{:?}", op);
+        }
+    }
+
+    #[test]
+    fn dither_preserves_exact_colors() {
+        let color = Vec4::new(0.0, 51.0, 204.0, 255.0) / 255.0;
+        for (x, y) in (0..4).flat_map(|y| (0..4).map(move |x| (x, y))) {
+            assert_eq!(dither(color, x, y), [0, 51, 204, 255]);
+        }
+    }
+
+    #[test]
+    fn dither_averages_to_the_color() {
+        // Halfway between `127` and `128`, so each should be used equally.
+        let color = Vec4::splat(127.5 / 255.0);
+        let pixels = (0..4).flat_map(|y| (0..4).map(move |x| dither(color, x, y)[0] as u32));
+        assert_eq!(pixels.sum::<u32>(), 127 * 8 + 128 * 8);
+    }
+}
@@ -0,0 +1,651 @@
+//! State queries (`glGetBooleanv`, `glGetIntegerv`, `glGetFloatv`, `glGetDoublev`,
+//! and also `glGetTexParameter*`/`glGetTexLevelParameter*`/`glGetTexEnv*`/
+//! `glGetTexGen*`/`glGetLight*`/`glGetMaterial*`/`glGetClipPlane`/`glGetPixelMap*`).
+
+use crate::gl::api_1_0::Enum;
+use crate::gl::client::MAX_CLIENT_ATTRIB_STACK_DEPTH;
+use crate::gl::state::{
+    Capability, MAX_ATTRIB_STACK_DEPTH, MAX_CLIP_PLANES, MAX_LIST_NESTING,
+    MAX_MODELVIEW_STACK_DEPTH, MAX_PROJECTION_STACK_DEPTH, MAX_TEXTURE_STACK_DEPTH,
+    MAX_VIEWPORT_DIMS,
+};
+use crate::gl::texture::{self, Target};
+use crate::gl::{light, raster, Context, Error};
+use glam::Mat4;
+use smallvec::{smallvec, SmallVec};
+
+/// A single value of queryable state, in its "natural" type (which decides
+/// how it's converted to the type requested by each `glGet*` command).
+#[derive(Copy, Clone, Debug)]
+pub enum Value {
+    Boolean(bool),
+    Integer(i32),
+    Float(f64),
+
+    /// Floating-point value which is converted to an integer by linearly
+    /// mapping `[-1, 1]` to the full range of `i32` (instead of rounding),
+    /// i.e. color components, normal coordinates and depth values.
+    Normalized(f64),
+}
+
+impl Value {
+    pub fn to_bool(self) -> bool {
+        match self {
+            Value::Boolean(b) => b,
+            Value::Integer(i) => i != 0,
+            Value::Float(f) | Value::Normalized(f) => f != 0.0,
+        }
+    }
+
+    pub fn to_i32(self) -> i32 {
+        match self {
+            Value::Boolean(b) => b as i32,
+            Value::Integer(i) => i,
+            // NOTE(eddyb) `as` saturates, which is the best we can do here.
+            Value::Float(f) => f.round() as i32,
+            // NOTE(eddyb) this is the inverse of `Normalize` (for `i32`), rounding
+            // halves up, so that `0.0` maps to `0` (instead of `-1`).
+            Value::Normalized(f) => ((f * u32::MAX as f64 - 1.0) / 2.0 + 0.5).floor() as i32,
+        }
+    }
+
+    pub fn to_f64(self) -> f64 {
+        match self {
+            Value::Boolean(b) => b as i32 as f64,
+            Value::Integer(i) => i as f64,
+            Value::Float(f) | Value::Normalized(f) => f,
+        }
+    }
+}
+
+impl From<bool> for Value {
+    fn from(b: bool) -> Self {
+        Value::Boolean(b)
+    }
+}
+
+impl From<i32> for Value {
+    fn from(i: i32) -> Self {
+        Value::Integer(i)
+    }
+}
+
+impl From<u32> for Value {
+    fn from(i: u32) -> Self {
+        Value::Integer(i.min(i32::MAX as u32) as i32)
+    }
+}
+
+impl From<usize> for Value {
+    fn from(i: usize) -> Self {
+        Value::Integer(i.min(i32::MAX as usize) as i32)
+    }
+}
+
+impl From<f32> for Value {
+    fn from(f: f32) -> Self {
+        Value::Float(f as f64)
+    }
+}
+
+impl From<Enum> for Value {
+    fn from(e: Enum) -> Self {
+        Value::Integer(e as i32)
+    }
+}
+
+pub type Values = SmallVec<[Value; 16]>;
+
+fn floats(values: &[f32]) -> Values {
+    values.iter().map(|&f| f.into()).collect()
+}
+
+fn normalized(values: &[f32]) -> Values {
+    values
+        .iter()
+        .map(|&f| Value::Normalized(f as f64))
+        .collect()
+}
+
+/// Bitmasks are returned as-is (i.e. with all bits set being `-1`), instead
+/// of being clamped to the range of `i32` (like other unsigned integers).
+fn mask(mask: u32) -> Value {
+    Value::Integer(mask as i32)
+}
+
+fn matrix(mat: &Mat4) -> Values {
+    mat.to_cols_array().iter().map(|&f| f.into()).collect()
+}
+
+// Implementation-dependent limits, not (yet) enforced by anything else
+// (they're the spec minimums, as the features they limit aren't supported).
+const MAX_EVAL_ORDER: u32 = 8;
+const MAX_NAME_STACK_DEPTH: u32 = 64;
+const MAX_PIXEL_MAP_TABLE: u32 = 32;
+
+/// The range of point sizes (and line widths) supported, which is only `1`,
+/// as points and lines are always rasterized 1 pixel wide.
+const POINT_SIZE_RANGE: [f32; 2] = [1.0, 1.0];
+const POINT_SIZE_GRANULARITY: f32 = 1.0;
+
+impl Context {
+    /// Query the state named by `pname`, as a list of values.
+    pub fn get(&self, pname: Enum) -> Result<Values, Error> {
+        let state = &self.state;
+        let arrays = &self.client.arrays;
+        let (unpack, pack) = (
+            &self.client.pixel_store.unpack,
+            &self.client.pixel_store.pack,
+        );
+
+        if state.begin.is_some() {
+            return Err(Error::InvalidOperation);
+        }
+
+        if let Ok(cap) = Capability::from_enum(pname) {
+            return Ok(smallvec![state.caps.contains(cap).into()]);
+        }
+        if let Ok(array) = self.client.arrays.get(pname) {
+            return Ok(smallvec![array.enabled.into()]);
+        }
+
+        use Enum::*;
+        Ok(match pname {
+            CURRENT_COLOR => normalized(state.current.color.as_ref()),
+            CURRENT_INDEX => smallvec![state.current.index.into()],
+            CURRENT_NORMAL => normalized(state.current.normal.as_ref()),
+            CURRENT_TEXTURE_COORDS => floats(state.current.texcoord.as_ref()),
+            EDGE_FLAG => smallvec![state.current.edge_flag.into()],
+            CURRENT_RASTER_POSITION => floats(state.current.raster.position.as_ref()),
+            CURRENT_RASTER_DISTANCE => smallvec![state.current.raster.distance.into()],
+            CURRENT_RASTER_COLOR => normalized(state.current.raster.color.as_ref()),
+            CURRENT_RASTER_INDEX => smallvec![state.current.raster.index.into()],
+            CURRENT_RASTER_TEXTURE_COORDS => floats(state.current.raster.texcoord.as_ref()),
+            CURRENT_RASTER_POSITION_VALID => smallvec![state.current.raster.valid.into()],
+
+            MATRIX_MODE => smallvec![state.transform.matrix_mode.to_enum().into()],
+            MODELVIEW_MATRIX => matrix(&state.modelview.mat),
+            PROJECTION_MATRIX => matrix(&state.projection.mat),
+            MODELVIEW_STACK_DEPTH => smallvec![state.modelview.depth().into()],
+            PROJECTION_STACK_DEPTH => smallvec![state.projection.depth().into()],
+            TEXTURE_MATRIX => matrix(&state.texture.mat),
+            TEXTURE_STACK_DEPTH => smallvec![state.texture.depth().into()],
+
+            VIEWPORT => smallvec![
+                state.viewport.x.into(),
+                state.viewport.y.into(),
+                state.viewport.width.into(),
+                state.viewport.height.into(),
+            ],
+            DEPTH_RANGE => {
+                let (near, far) = state.viewport.depth_range;
+                smallvec![Value::Normalized(near), Value::Normalized(far)]
+            }
+
+            SHADE_MODEL => smallvec![state.lighting.shade_model.to_enum().into()],
+            COLOR_MATERIAL_FACE => smallvec![state.lighting.color_material_face.to_enum().into()],
+            COLOR_MATERIAL_PARAMETER => {
+                smallvec![state.lighting.color_material_param.to_enum().into()]
+            }
+            LIGHT_MODEL_AMBIENT => normalized(state.lighting.model.ambient.as_ref()),
+            LIGHT_MODEL_LOCAL_VIEWER => smallvec![state.lighting.model.local_viewer.into()],
+            LIGHT_MODEL_TWO_SIDE => smallvec![state.lighting.model.two_side.into()],
+
+            SCISSOR_BOX => smallvec![
+                state.scissor.x.into(),
+                state.scissor.y.into(),
+                state.scissor.width.into(),
+                state.scissor.height.into(),
+            ],
+
+            POINT_SIZE => smallvec![state.point.size.into()],
+            LINE_WIDTH => smallvec![state.line.width.into()],
+            LINE_STIPPLE_PATTERN => smallvec![(state.line.stipple_pattern as i32).into()],
+            LINE_STIPPLE_REPEAT => smallvec![state.line.stipple_repeat.into()],
+            CULL_FACE_MODE => smallvec![state.polygon.cull_face_mode.to_enum().into()],
+            FRONT_FACE => smallvec![state.polygon.front_face.to_enum().into()],
+            POLYGON_MODE => state
+                .polygon
+                .mode
+                .iter()
+                .map(|mode| mode.to_enum().into())
+                .collect(),
+            POLYGON_OFFSET_FACTOR => smallvec![state.polygon.offset_factor.into()],
+            POLYGON_OFFSET_UNITS => smallvec![state.polygon.offset_units.into()],
+
+            FOG_MODE => smallvec![state.fog.mode.to_enum().into()],
+            FOG_DENSITY => smallvec![state.fog.density.into()],
+            FOG_START => smallvec![state.fog.start.into()],
+            FOG_END => smallvec![state.fog.end.into()],
+            FOG_INDEX => smallvec![state.fog.index.into()],
+            FOG_COLOR => normalized(state.fog.color.as_ref()),
+
+            DRAW_BUFFER => smallvec![state.color_buffer.draw_buffer.into()],
+            COLOR_CLEAR_VALUE => normalized(state.color_buffer.clear_value.as_ref()),
+            INDEX_CLEAR_VALUE => smallvec![state.color_buffer.clear_index.into()],
+            ALPHA_TEST_FUNC => smallvec![state.color_buffer.alpha_func.0.to_enum().into()],
+            ALPHA_TEST_REF => smallvec![Value::Normalized(state.color_buffer.alpha_func.1 as f64)],
+            BLEND_SRC => smallvec![state.color_buffer.blend_func.0.to_enum().into()],
+            BLEND_DST => smallvec![state.color_buffer.blend_func.1.to_enum().into()],
+            LOGIC_OP_MODE => smallvec![state.color_buffer.logic_op.to_enum().into()],
+            COLOR_WRITEMASK => state
+                .color_buffer
+                .color_writemask
+                .iter()
+                .map(|&w| w.into())
+                .collect(),
+            INDEX_WRITEMASK => smallvec![mask(state.color_buffer.index_writemask)],
+            DEPTH_CLEAR_VALUE => smallvec![Value::Normalized(state.depth.clear_value)],
+            DEPTH_FUNC => smallvec![state.depth.func.to_enum().into()],
+            DEPTH_WRITEMASK => smallvec![state.depth.writemask.into()],
+            STENCIL_FUNC => smallvec![state.stencil.func.to_enum().into()],
+            STENCIL_REF => smallvec![state.stencil.reference.into()],
+            STENCIL_VALUE_MASK => smallvec![mask(state.stencil.value_mask)],
+            STENCIL_FAIL => smallvec![state.stencil.fail.to_enum().into()],
+            STENCIL_PASS_DEPTH_FAIL => smallvec![state.stencil.depth_fail.to_enum().into()],
+            STENCIL_PASS_DEPTH_PASS => smallvec![state.stencil.depth_pass.to_enum().into()],
+            STENCIL_WRITEMASK => smallvec![mask(state.stencil.writemask)],
+            STENCIL_CLEAR_VALUE => smallvec![state.stencil.clear_value.into()],
+            ACCUM_CLEAR_VALUE => normalized(state.accum.clear_value.as_ref()),
+
+            MAP_COLOR => smallvec![state.pixel_mode.map_color.into()],
+            MAP_STENCIL => smallvec![state.pixel_mode.map_stencil.into()],
+            INDEX_SHIFT => smallvec![state.pixel_mode.index_shift.into()],
+            INDEX_OFFSET => smallvec![state.pixel_mode.index_offset.into()],
+            RED_SCALE => smallvec![state.pixel_mode.scale.x.into()],
+            GREEN_SCALE => smallvec![state.pixel_mode.scale.y.into()],
+            BLUE_SCALE => smallvec![state.pixel_mode.scale.z.into()],
+            ALPHA_SCALE => smallvec![state.pixel_mode.scale.w.into()],
+            RED_BIAS => smallvec![state.pixel_mode.bias.x.into()],
+            GREEN_BIAS => smallvec![state.pixel_mode.bias.y.into()],
+            BLUE_BIAS => smallvec![state.pixel_mode.bias.z.into()],
+            ALPHA_BIAS => smallvec![state.pixel_mode.bias.w.into()],
+            DEPTH_SCALE => smallvec![state.pixel_mode.depth_scale.into()],
+            DEPTH_BIAS => smallvec![state.pixel_mode.depth_bias.into()],
+            ZOOM_X => smallvec![state.pixel_mode.zoom.0.into()],
+            ZOOM_Y => smallvec![state.pixel_mode.zoom.1.into()],
+            READ_BUFFER => smallvec![state.pixel_mode.read_buffer.into()],
+
+            MAP1_GRID_DOMAIN => floats(&state.eval.map1_grid_domain),
+            MAP1_GRID_SEGMENTS => smallvec![state.eval.map1_grid_segments.into()],
+            MAP2_GRID_DOMAIN => floats(&state.eval.map2_grid_domain),
+            MAP2_GRID_SEGMENTS => state
+                .eval
+                .map2_grid_segments
+                .iter()
+                .map(|&n| n.into())
+                .collect(),
+
+            LIST_BASE => smallvec![state.list.base.into()],
+            LIST_INDEX => smallvec![self.compile_list.as_ref().map_or(0, |&(n, _)| n).into()],
+            LIST_MODE => smallvec![match &self.compile_list {
+                Some(_) if self.execute_immediately => COMPILE_AND_EXECUTE.into(),
+                Some(_) => COMPILE.into(),
+                None => 0.into(),
+            }],
+
+            VERTEX_ARRAY_SIZE => smallvec![arrays.vertex.size.into()],
+            VERTEX_ARRAY_TYPE => smallvec![arrays.vertex.ty.into()],
+            VERTEX_ARRAY_STRIDE => smallvec![arrays.vertex.stride.into()],
+            NORMAL_ARRAY_TYPE => smallvec![arrays.normal.ty.into()],
+            NORMAL_ARRAY_STRIDE => smallvec![arrays.normal.stride.into()],
+            COLOR_ARRAY_SIZE => smallvec![arrays.color.size.into()],
+            COLOR_ARRAY_TYPE => smallvec![arrays.color.ty.into()],
+            COLOR_ARRAY_STRIDE => smallvec![arrays.color.stride.into()],
+            INDEX_ARRAY_TYPE => smallvec![arrays.index.ty.into()],
+            INDEX_ARRAY_STRIDE => smallvec![arrays.index.stride.into()],
+            TEXTURE_COORD_ARRAY_SIZE => smallvec![arrays.texcoord.size.into()],
+            TEXTURE_COORD_ARRAY_TYPE => smallvec![arrays.texcoord.ty.into()],
+            TEXTURE_COORD_ARRAY_STRIDE => smallvec![arrays.texcoord.stride.into()],
+            EDGE_FLAG_ARRAY_STRIDE => smallvec![arrays.edge_flag.stride.into()],
+
+            TEXTURE_BINDING_1D => smallvec![state.texturing.bindings.texture_1d.into()],
+            TEXTURE_BINDING_2D => smallvec![state.texturing.bindings.texture_2d.into()],
+
+            PERSPECTIVE_CORRECTION_HINT => {
+                smallvec![state.hints.perspective_correction.to_enum().into()]
+            }
+            POINT_SMOOTH_HINT => smallvec![state.hints.point_smooth.to_enum().into()],
+            LINE_SMOOTH_HINT => smallvec![state.hints.line_smooth.to_enum().into()],
+            POLYGON_SMOOTH_HINT => smallvec![state.hints.polygon_smooth.to_enum().into()],
+            FOG_HINT => smallvec![state.hints.fog.to_enum().into()],
+
+            UNPACK_SWAP_BYTES => smallvec![unpack.swap_bytes.into()],
+            UNPACK_LSB_FIRST => smallvec![unpack.lsb_first.into()],
+            UNPACK_ROW_LENGTH => smallvec![unpack.row_length.into()],
+            UNPACK_SKIP_ROWS => smallvec![unpack.skip_rows.into()],
+            UNPACK_SKIP_PIXELS => smallvec![unpack.skip_pixels.into()],
+            UNPACK_ALIGNMENT => smallvec![unpack.alignment.into()],
+            PACK_SWAP_BYTES => smallvec![pack.swap_bytes.into()],
+            PACK_LSB_FIRST => smallvec![pack.lsb_first.into()],
+            PACK_ROW_LENGTH => smallvec![pack.row_length.into()],
+            PACK_SKIP_ROWS => smallvec![pack.skip_rows.into()],
+            PACK_SKIP_PIXELS => smallvec![pack.skip_pixels.into()],
+            PACK_ALIGNMENT => smallvec![pack.alignment.into()],
+
+            // NOTE(eddyb) only the `GL_RENDER` mode is supported (see
+            // `glRenderMode`), so there's no feedback or selection buffer.
+            RENDER_MODE => smallvec![RENDER.into()],
+            FEEDBACK_BUFFER_SIZE | SELECTION_BUFFER_SIZE | NAME_STACK_DEPTH => {
+                smallvec![0.into()]
+            }
+            FEEDBACK_BUFFER_TYPE => smallvec![_2D.into()],
+
+            // NOTE(eddyb) `glPixelMap` isn't supported, so all the pixel maps
+            // always have their default size.
+            PIXEL_MAP_I_TO_I_SIZE
+            | PIXEL_MAP_S_TO_S_SIZE
+            | PIXEL_MAP_I_TO_R_SIZE
+            | PIXEL_MAP_I_TO_G_SIZE
+            | PIXEL_MAP_I_TO_B_SIZE
+            | PIXEL_MAP_I_TO_A_SIZE
+            | PIXEL_MAP_R_TO_R_SIZE
+            | PIXEL_MAP_G_TO_G_SIZE
+            | PIXEL_MAP_B_TO_B_SIZE
+            | PIXEL_MAP_A_TO_A_SIZE => smallvec![1.into()],
+
+            ATTRIB_STACK_DEPTH => smallvec![state.attrib_stack_depth().into()],
+            CLIENT_ATTRIB_STACK_DEPTH => smallvec![self.client.attrib_stack_depth().into()],
+
+            // Implementation-dependent limits.
+            MAX_ATTRIB_STACK_DEPTH => smallvec![self::MAX_ATTRIB_STACK_DEPTH.into()],
+            MAX_CLIENT_ATTRIB_STACK_DEPTH => {
+                smallvec![self::MAX_CLIENT_ATTRIB_STACK_DEPTH.into()]
+            }
+            MAX_LIGHTS => smallvec![light::MAX_LIGHTS.into()],
+            MAX_LIST_NESTING => smallvec![self::MAX_LIST_NESTING.into()],
+            MAX_CLIP_PLANES => smallvec![self::MAX_CLIP_PLANES.into()],
+            MAX_EVAL_ORDER => smallvec![self::MAX_EVAL_ORDER.into()],
+            MAX_NAME_STACK_DEPTH => smallvec![self::MAX_NAME_STACK_DEPTH.into()],
+            MAX_PIXEL_MAP_TABLE => smallvec![self::MAX_PIXEL_MAP_TABLE.into()],
+            MAX_MODELVIEW_STACK_DEPTH => smallvec![self::MAX_MODELVIEW_STACK_DEPTH.into()],
+            MAX_PROJECTION_STACK_DEPTH => smallvec![self::MAX_PROJECTION_STACK_DEPTH.into()],
+            MAX_TEXTURE_STACK_DEPTH => smallvec![self::MAX_TEXTURE_STACK_DEPTH.into()],
+            MAX_TEXTURE_SIZE => smallvec![texture::MAX_TEXTURE_SIZE.into()],
+            MAX_VIEWPORT_DIMS => {
+                smallvec![
+                    self::MAX_VIEWPORT_DIMS.into(),
+                    self::MAX_VIEWPORT_DIMS.into()
+                ]
+            }
+            SUBPIXEL_BITS => smallvec![raster::SUBPIXEL_BITS.into()],
+            POINT_SIZE_RANGE | LINE_WIDTH_RANGE => floats(&self::POINT_SIZE_RANGE),
+            POINT_SIZE_GRANULARITY | LINE_WIDTH_GRANULARITY => {
+                smallvec![self::POINT_SIZE_GRANULARITY.into()]
+            }
+
+            // Framebuffer configuration (see also the GLX configs).
+            RGBA_MODE | DOUBLEBUFFER => smallvec![true.into()],
+            INDEX_MODE | STEREO => smallvec![false.into()],
+            RED_BITS | GREEN_BITS | BLUE_BITS | ALPHA_BITS => smallvec![8.into()],
+            DEPTH_BITS => smallvec![raster::DEPTH_BITS.into()],
+            STENCIL_BITS => smallvec![raster::STENCIL_BITS.into()],
+            INDEX_BITS | ACCUM_RED_BITS | ACCUM_GREEN_BITS | ACCUM_BLUE_BITS | ACCUM_ALPHA_BITS
+            | AUX_BUFFERS => smallvec![0.into()],
+
+            _ => return Err(Error::InvalidEnum),
+        })
+    }
+
+    /// Query the parameter `pname` of the texture bound to `target`.
+    pub fn get_tex_parameter(&self, target: Enum, pname: Enum) -> Result<Values, Error> {
+        if self.state.begin.is_some() {
+            return Err(Error::InvalidOperation);
+        }
+        let target = Target::from_enum(target)?;
+        let share_group = self.share_group.lock().unwrap();
+        let params = &self.state.texture(&share_group, target).params;
+
+        use Enum::*;
+        Ok(match pname {
+            TEXTURE_MIN_FILTER => smallvec![params.min_filter.to_enum().into()],
+            TEXTURE_MAG_FILTER => smallvec![params.mag_filter.to_enum().into()],
+            TEXTURE_WRAP_S => smallvec![params.wrap_s.to_enum().into()],
+            TEXTURE_WRAP_T => smallvec![params.wrap_t.to_enum().into()],
+            TEXTURE_BORDER_COLOR => normalized(params.border_color.as_ref()),
+            TEXTURE_PRIORITY => smallvec![params.priority.into()],
+            // NOTE(eddyb) all textures are always in (host) memory.
+            TEXTURE_RESIDENT => smallvec![true.into()],
+            _ => return Err(Error::InvalidEnum),
+        })
+    }
+
+    /// Query the texture environment parameter `pname` (`target` has to be
+    /// `GL_TEXTURE_ENV`).
+    pub fn get_tex_env(&self, target: Enum, pname: Enum) -> Result<Values, Error> {
+        if self.state.begin.is_some() {
+            return Err(Error::InvalidOperation);
+        }
+        if target != Enum::TEXTURE_ENV {
+            return Err(Error::InvalidEnum);
+        }
+        let env = &self.state.texturing.env;
+        match pname {
+            Enum::TEXTURE_ENV_MODE => Ok(smallvec![env.mode.to_enum().into()]),
+            Enum::TEXTURE_ENV_COLOR => Ok(normalized(env.color.as_ref())),
+            _ => Err(Error::InvalidEnum),
+        }
+    }
+
+    /// Query the texture coordinate generation parameter `pname`, for the
+    /// coordinate `coord` (e.g. `GL_S`).
+    pub fn get_tex_gen(&self, coord: Enum, pname: Enum) -> Result<Values, Error> {
+        if self.state.begin.is_some() {
+            return Err(Error::InvalidOperation);
+        }
+        let gen = self.state.texturing.tex_gen.coord(coord)?;
+        match pname {
+            Enum::TEXTURE_GEN_MODE => Ok(smallvec![gen.mode.to_enum().into()]),
+            Enum::OBJECT_PLANE => Ok(floats(gen.object_plane.as_ref())),
+            Enum::EYE_PLANE => Ok(floats(gen.eye_plane.as_ref())),
+            _ => Err(Error::InvalidEnum),
+        }
+    }
+
+    /// Query the parameter `pname` of the mipmap level `level`, of the texture
+    /// bound to `target` (or of the proxy texture, for proxy targets).
+    pub fn get_tex_level_parameter(
+        &self,
+        target: Enum,
+        level: i32,
+        pname: Enum,
+    ) -> Result<Values, Error> {
+        if self.state.begin.is_some() {
+            return Err(Error::InvalidOperation);
+        }
+        let (target, proxy) = Target::from_enum_or_proxy(target)?;
+        if !(0..texture::MAX_LEVELS as i32).contains(&level) {
+            return Err(Error::InvalidValue);
+        }
+        let share_group = self.share_group.lock().unwrap();
+        let texture = if proxy {
+            self.state.proxy_textures.get(target)
+        } else {
+            self.state.texture(&share_group, target)
+        };
+        let image = texture.level(level as usize);
+
+        use Enum::*;
+        Ok(match pname {
+            TEXTURE_WIDTH => smallvec![image.width.into()],
+            TEXTURE_HEIGHT => smallvec![image.height.into()],
+            TEXTURE_BORDER => smallvec![image.border.into()],
+            TEXTURE_INTERNAL_FORMAT => smallvec![image.internal_format.into()],
+            TEXTURE_RED_SIZE
+            | TEXTURE_GREEN_SIZE
+            | TEXTURE_BLUE_SIZE
+            | TEXTURE_ALPHA_SIZE
+            | TEXTURE_LUMINANCE_SIZE
+            | TEXTURE_INTENSITY_SIZE => {
+                smallvec![image.component_size(pname).into()]
+            }
+            _ => return Err(Error::InvalidEnum),
+        })
+    }
+
+    /// Query the parameter `pname` of `light` (e.g. `GL_LIGHT0`).
+    pub fn get_light(&self, light: Enum, pname: Enum) -> Result<Values, Error> {
+        if self.state.begin.is_some() {
+            return Err(Error::InvalidOperation);
+        }
+
+        let light = &self.state.lighting.lights[light::Lighting::light_index(light)?];
+
+        use Enum::*;
+        Ok(match pname {
+            AMBIENT => normalized(light.ambient.as_ref()),
+            DIFFUSE => normalized(light.diffuse.as_ref()),
+            SPECULAR => normalized(light.specular.as_ref()),
+            POSITION => floats(light.position.as_ref()),
+            SPOT_DIRECTION => floats(light.spot_direction.as_ref()),
+            SPOT_EXPONENT => smallvec![light.spot_exponent.into()],
+            SPOT_CUTOFF => smallvec![light.spot_cutoff.into()],
+            CONSTANT_ATTENUATION => smallvec![light.attenuation[0].into()],
+            LINEAR_ATTENUATION => smallvec![light.attenuation[1].into()],
+            QUADRATIC_ATTENUATION => smallvec![light.attenuation[2].into()],
+            _ => return Err(Error::InvalidEnum),
+        })
+    }
+
+    /// Query the parameter `pname` of the material of `face` (which can only
+    /// be `GL_FRONT` or `GL_BACK`).
+    pub fn get_material(&self, face: Enum, pname: Enum) -> Result<Values, Error> {
+        if self.state.begin.is_some() {
+            return Err(Error::InvalidOperation);
+        }
+
+        let material = match light::Face::from_enum(face)? {
+            light::Face::Front => &self.state.lighting.materials[0],
+            light::Face::Back => &self.state.lighting.materials[1],
+            light::Face::FrontAndBack => return Err(Error::InvalidEnum),
+        };
+
+        use Enum::*;
+        Ok(match pname {
+            AMBIENT => normalized(material.ambient.as_ref()),
+            DIFFUSE => normalized(material.diffuse.as_ref()),
+            SPECULAR => normalized(material.specular.as_ref()),
+            EMISSION => normalized(material.emission.as_ref()),
+            SHININESS => smallvec![material.shininess.into()],
+            COLOR_INDEXES => floats(&material.color_indexes),
+            _ => return Err(Error::InvalidEnum),
+        })
+    }
+
+    /// Query the equation of the clip plane `plane` (e.g. `GL_CLIP_PLANE0`).
+    pub fn get_clip_plane(&self, plane: Enum) -> Result<Values, Error> {
+        if self.state.begin.is_some() {
+            return Err(Error::InvalidOperation);
+        }
+
+        let i = (plane as u32).wrapping_sub(Enum::CLIP_PLANE0 as u32) as usize;
+        let plane = self
+            .state
+            .transform
+            .clip_planes
+            .get(i)
+            .ok_or(Error::InvalidEnum)?;
+        Ok(plane.as_ref().iter().map(|&x| Value::Float(x)).collect())
+    }
+
+    /// Query the contents of the pixel map `map` (e.g. `GL_PIXEL_MAP_I_TO_R`).
+    pub fn get_pixel_map(&self, map: Enum) -> Result<Values, Error> {
+        if self.state.begin.is_some() {
+            return Err(Error::InvalidOperation);
+        }
+
+        use Enum::*;
+        match map {
+            // NOTE(eddyb) `glPixelMap` isn't supported, so all the pixel maps
+            // always have their default contents, i.e. a single `0`.
+            PIXEL_MAP_I_TO_I | PIXEL_MAP_S_TO_S | PIXEL_MAP_I_TO_R | PIXEL_MAP_I_TO_G
+            | PIXEL_MAP_I_TO_B | PIXEL_MAP_I_TO_A | PIXEL_MAP_R_TO_R | PIXEL_MAP_G_TO_G
+            | PIXEL_MAP_B_TO_B | PIXEL_MAP_A_TO_A => Ok(smallvec![0.into()]),
+            _ => Err(Error::InvalidEnum),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gl::api_1_0::{self, Command::*, Normalize};
+
+    #[test]
+    fn value_conversions() {
+        assert!(!Value::Float(0.0).to_bool());
+        assert!(Value::Float(0.25).to_bool());
+        assert!(Value::Normalized(-0.5).to_bool());
+        assert!(Value::Integer(-1).to_bool());
+
+        assert_eq!(Value::Boolean(true).to_i32(), 1);
+        assert_eq!(Value::Float(2.5).to_i32(), 3);
+        assert_eq!(Value::Float(-2.4).to_i32(), -2);
+        assert_eq!(Value::Float(1e20).to_i32(), i32::MAX);
+
+        // Normalized values map `[-1, 1]` linearly to the full range of `i32`.
+        assert_eq!(Value::Normalized(1.0).to_i32(), i32::MAX);
+        assert_eq!(Value::Normalized(-1.0).to_i32(), i32::MIN);
+        assert_eq!(Value::Normalized(0.0).to_i32(), 0);
+        assert_eq!(Value::Normalized(0.5).to_i32(), i32::MAX / 2);
+        for &c in &[i32::MIN, -12345, 0, 1, 1 << 20, i32::MAX] {
+            let f = c.normalize() as f64;
+            assert!((Value::Normalized(f).to_i32() - c).abs() <= 128);
+        }
+
+        assert_eq!(Value::Boolean(true).to_f64(), 1.0);
+        assert_eq!(Value::Integer(-7).to_f64(), -7.0);
+        assert_eq!(Value::Normalized(0.75).to_f64(), 0.75);
+
+        assert_eq!(Value::from(u32::MAX).to_i32(), i32::MAX);
+        assert_eq!(Value::from(Enum::CCW).to_i32(), Enum::CCW as i32);
+    }
+
+    #[test]
+    fn get_entry_points() {
+        Box::new(Context::new(Default::default())).enter();
+        Context::with("test", |gl| {
+            gl.execute(glClearColor(1.0, 0.0, 0.5, 0.0));
+            gl.execute(glFrontFace(Enum::CW));
+            gl.execute(glEnable(Enum::CULL_FACE));
+        });
+        unsafe {
+            let mut ints = [0; 4];
+            api_1_0::glGetIntegerv(Enum::COLOR_CLEAR_VALUE as u32, ints.as_mut_ptr());
+            assert_eq!(ints, [i32::MAX, 0, i32::MAX / 2, 0]);
+
+            let mut bools = [2; 4];
+            api_1_0::glGetBooleanv(Enum::COLOR_CLEAR_VALUE as u32, bools.as_mut_ptr());
+            assert_eq!(bools, [1, 0, 1, 0]);
+            api_1_0::glGetBooleanv(Enum::CULL_FACE as u32, bools.as_mut_ptr());
+            assert_eq!(bools[0], 1);
+
+            let mut floats = [0.0; 4];
+            api_1_0::glGetFloatv(Enum::FRONT_FACE as u32, floats.as_mut_ptr());
+            assert_eq!(floats[0], Enum::CW as u32 as f32);
+            api_1_0::glGetLightfv(
+                Enum::LIGHT1 as u32,
+                Enum::DIFFUSE as u32,
+                floats.as_mut_ptr(),
+            );
+            assert_eq!(floats, [0.0, 0.0, 0.0, 1.0]);
+
+            let mut doubles = [0.0; 2];
+            api_1_0::glGetDoublev(Enum::DEPTH_RANGE as u32, doubles.as_mut_ptr());
+            assert_eq!(doubles, [0.0, 1.0]);
+            assert_eq!(api_1_0::glGetError(), 0);
+
+            // Errors leave `params` untouched.
+            api_1_0::glGetIntegerv(Enum::FLAT as u32, ints.as_mut_ptr());
+            assert_eq!(api_1_0::glGetError(), Enum::INVALID_ENUM as u32);
+            api_1_0::glGetMaterialiv(
+                Enum::FRONT_AND_BACK as u32,
+                Enum::AMBIENT as u32,
+                ints.as_mut_ptr(),
+            );
+            assert_eq!(api_1_0::glGetError(), Enum::INVALID_ENUM as u32);
+            assert_eq!(ints, [i32::MAX, 0, i32::MAX / 2, 0]);
+        }
+        Context::leave();
+    }
+}
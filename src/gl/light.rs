@@ -0,0 +1,568 @@
+use crate::gl::api_1_0::{Enum, Normalize};
+use crate::gl::state::{Capabilities, Capability};
+use crate::gl::Error;
+use glam::{Mat4, Vec3, Vec4};
+use smallvec::SmallVec;
+
+pub const MAX_LIGHTS: usize = 8;
+
+/// Convert the integer parameters of e.g. `glLightiv` (or `glTexParameteriv`)
+/// to floating-point, treating color parameters as such (see `Normalize`).
+pub fn int_params_to_float(pname: Enum, params: &[i32]) -> SmallVec<[f32; 4]> {
+    use Enum::*;
+    match pname {
+        AMBIENT | DIFFUSE | SPECULAR | EMISSION | AMBIENT_AND_DIFFUSE | LIGHT_MODEL_AMBIENT
+        | FOG_COLOR | TEXTURE_BORDER_COLOR | TEXTURE_ENV_COLOR => {
+            params.iter().map(|&c| c.normalize()).collect()
+        }
+        _ => params.iter().map(|&x| x as f32).collect(),
+    }
+}
+
+pub fn light_params_len(pname: Enum) -> Result<usize, Error> {
+    use Enum::*;
+    match pname {
+        AMBIENT | DIFFUSE | SPECULAR | POSITION => Ok(4),
+
+        SPOT_DIRECTION => Ok(3),
+
+        SPOT_EXPONENT
+        | SPOT_CUTOFF
+        | CONSTANT_ATTENUATION
+        | LINEAR_ATTENUATION
+        | QUADRATIC_ATTENUATION => Ok(1),
+
+        _ => Err(Error::InvalidEnum),
+    }
+}
+
+pub fn material_params_len(pname: Enum) -> Result<usize, Error> {
+    use Enum::*;
+    match pname {
+        AMBIENT | DIFFUSE | AMBIENT_AND_DIFFUSE | SPECULAR | EMISSION => Ok(4),
+
+        SHININESS => Ok(1),
+
+        COLOR_INDEXES => Ok(3),
+
+        _ => Err(Error::InvalidEnum),
+    }
+}
+
+pub fn light_model_params_len(pname: Enum) -> Result<usize, Error> {
+    use Enum::*;
+    match pname {
+        LIGHT_MODEL_AMBIENT => Ok(4),
+
+        LIGHT_MODEL_LOCAL_VIEWER | LIGHT_MODEL_TWO_SIDE => Ok(1),
+
+        _ => Err(Error::InvalidEnum),
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Face {
+    Front,
+    Back,
+    FrontAndBack,
+}
+
+impl Face {
+    pub fn from_enum(face: Enum) -> Result<Self, Error> {
+        match face {
+            Enum::FRONT => Ok(Face::Front),
+            Enum::BACK => Ok(Face::Back),
+            Enum::FRONT_AND_BACK => Ok(Face::FrontAndBack),
+            _ => Err(Error::InvalidEnum),
+        }
+    }
+
+    pub fn to_enum(self) -> Enum {
+        match self {
+            Face::Front => Enum::FRONT,
+            Face::Back => Enum::BACK,
+            Face::FrontAndBack => Enum::FRONT_AND_BACK,
+        }
+    }
+
+    /// Indices into `[front, back]` arrays, for the faces this `Face` covers.
+    pub fn indices(self) -> &'static [usize] {
+        match self {
+            Face::Front => &[0],
+            Face::Back => &[1],
+            Face::FrontAndBack => &[0, 1],
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct Light {
+    pub ambient: Vec4,
+    pub diffuse: Vec4,
+    pub specular: Vec4,
+
+    /// Position in eye coordinates (i.e. transformed by the modelview matrix
+    /// in effect when it was specified), with `w = 0` for directional lights.
+    pub position: Vec4,
+
+    /// Direction in eye coordinates (see `position`).
+    pub spot_direction: Vec3,
+    pub spot_exponent: f32,
+    pub spot_cutoff: f32,
+
+    /// Constant, linear and quadratic attenuation factors.
+    pub attenuation: [f32; 3],
+}
+
+impl Light {
+    fn new(i: usize) -> Self {
+        // NOTE(eddyb) only the first light is white by default.
+        let (diffuse, specular) = if i == 0 {
+            (Vec4::one(), Vec4::one())
+        } else {
+            (Vec4::unit_w(), Vec4::unit_w())
+        };
+        Light {
+            ambient: Vec4::unit_w(),
+            diffuse,
+            specular,
+
+            position: Vec4::unit_z(),
+
+            spot_direction: -Vec3::unit_z(),
+            spot_exponent: 0.0,
+            spot_cutoff: 180.0,
+
+            attenuation: [1.0, 0.0, 0.0],
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct Material {
+    pub ambient: Vec4,
+    pub diffuse: Vec4,
+    pub specular: Vec4,
+    pub emission: Vec4,
+    pub shininess: f32,
+    pub color_indexes: [f32; 3],
+}
+
+impl Default for Material {
+    fn default() -> Self {
+        Material {
+            ambient: Vec4::new(0.2, 0.2, 0.2, 1.0),
+            diffuse: Vec4::new(0.8, 0.8, 0.8, 1.0),
+            specular: Vec4::unit_w(),
+            emission: Vec4::unit_w(),
+            shininess: 0.0,
+            color_indexes: [0.0, 1.0, 1.0],
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct LightModel {
+    pub ambient: Vec4,
+    pub local_viewer: bool,
+    pub two_side: bool,
+}
+
+impl Default for LightModel {
+    fn default() -> Self {
+        LightModel {
+            ambient: Vec4::new(0.2, 0.2, 0.2, 1.0),
+            local_viewer: false,
+            two_side: false,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ShadeModel {
+    Flat,
+    Smooth,
+}
+
+impl ShadeModel {
+    pub fn to_enum(self) -> Enum {
+        match self {
+            ShadeModel::Flat => Enum::FLAT,
+            ShadeModel::Smooth => Enum::SMOOTH,
+        }
+    }
+}
+
+/// The material parameter that tracks the current color, with `GL_COLOR_MATERIAL`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ColorMaterialParam {
+    Emission,
+    Ambient,
+    Diffuse,
+    Specular,
+    AmbientAndDiffuse,
+}
+
+impl ColorMaterialParam {
+    pub fn to_enum(self) -> Enum {
+        match self {
+            ColorMaterialParam::Emission => Enum::EMISSION,
+            ColorMaterialParam::Ambient => Enum::AMBIENT,
+            ColorMaterialParam::Diffuse => Enum::DIFFUSE,
+            ColorMaterialParam::Specular => Enum::SPECULAR,
+            ColorMaterialParam::AmbientAndDiffuse => Enum::AMBIENT_AND_DIFFUSE,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct Lighting {
+    pub lights: [Light; MAX_LIGHTS],
+
+    /// Front and back materials.
+    pub materials: [Material; 2],
+    pub model: LightModel,
+    pub shade_model: ShadeModel,
+
+    pub color_material_face: Face,
+    pub color_material_param: ColorMaterialParam,
+}
+
+impl Default for Lighting {
+    fn default() -> Self {
+        let mut lights = [Light::new(0); MAX_LIGHTS];
+        for (i, light) in lights.iter_mut().enumerate() {
+            *light = Light::new(i);
+        }
+        Lighting {
+            lights,
+
+            materials: [Material::default(); 2],
+            model: LightModel::default(),
+            shade_model: ShadeModel::Smooth,
+
+            color_material_face: Face::FrontAndBack,
+            color_material_param: ColorMaterialParam::AmbientAndDiffuse,
+        }
+    }
+}
+
+impl Lighting {
+    pub fn light_index(light: Enum) -> Result<usize, Error> {
+        (light as usize)
+            .checked_sub(Enum::LIGHT0 as usize)
+            .filter(|&i| i < MAX_LIGHTS)
+            .ok_or(Error::InvalidEnum)
+    }
+
+    /// Set a light parameter, where `modelview` is the current modelview
+    /// matrix, used to transform positions and directions to eye coordinates.
+    pub fn set_light(
+        &mut self,
+        light: Enum,
+        pname: Enum,
+        params: &[f32],
+        modelview: &Mat4,
+    ) -> Result<(), Error> {
+        let light = &mut self.lights[Self::light_index(light)?];
+        if params.len() != light_params_len(pname)? {
+            return Err(Error::InvalidEnum);
+        }
+        let vec4 = || Vec4::new(params[0], params[1], params[2], params[3]);
+
+        use Enum::*;
+        match pname {
+            AMBIENT => light.ambient = vec4(),
+            DIFFUSE => light.diffuse = vec4(),
+            SPECULAR => light.specular = vec4(),
+            POSITION => light.position = modelview.mul_vec4(vec4()),
+            SPOT_DIRECTION => {
+                light.spot_direction =
+                    modelview.transform_vector3(Vec3::new(params[0], params[1], params[2]));
+            }
+            SPOT_EXPONENT => {
+                if !(0.0..=128.0).contains(&params[0]) {
+                    return Err(Error::InvalidValue);
+                }
+                light.spot_exponent = params[0];
+            }
+            SPOT_CUTOFF => {
+                if !(0.0..=90.0).contains(&params[0]) && params[0] != 180.0 {
+                    return Err(Error::InvalidValue);
+                }
+                light.spot_cutoff = params[0];
+            }
+            CONSTANT_ATTENUATION | LINEAR_ATTENUATION | QUADRATIC_ATTENUATION => {
+                if params[0] < 0.0 {
+                    return Err(Error::InvalidValue);
+                }
+                light.attenuation[pname as usize - CONSTANT_ATTENUATION as usize] = params[0];
+            }
+            _ => unreachable!(),
+        }
+        Ok(())
+    }
+
+    pub fn set_material(&mut self, face: Enum, pname: Enum, params: &[f32]) -> Result<(), Error> {
+        let face = Face::from_enum(face)?;
+        if params.len() != material_params_len(pname)? {
+            return Err(Error::InvalidEnum);
+        }
+        if matches!(pname, Enum::SHININESS) && !(0.0..=128.0).contains(&params[0]) {
+            return Err(Error::InvalidValue);
+        }
+        let vec4 = || Vec4::new(params[0], params[1], params[2], params[3]);
+
+        for &i in face.indices() {
+            let material = &mut self.materials[i];
+
+            use Enum::*;
+            match pname {
+                AMBIENT => material.ambient = vec4(),
+                DIFFUSE => material.diffuse = vec4(),
+                AMBIENT_AND_DIFFUSE => {
+                    material.ambient = vec4();
+                    material.diffuse = vec4();
+                }
+                SPECULAR => material.specular = vec4(),
+                EMISSION => material.emission = vec4(),
+                SHININESS => material.shininess = params[0],
+                COLOR_INDEXES => material.color_indexes = [params[0], params[1], params[2]],
+                _ => unreachable!(),
+            }
+        }
+        Ok(())
+    }
+
+    pub fn set_light_model(&mut self, pname: Enum, params: &[f32]) -> Result<(), Error> {
+        if params.len() != light_model_params_len(pname)? {
+            return Err(Error::InvalidEnum);
+        }
+
+        use Enum::*;
+        match pname {
+            LIGHT_MODEL_AMBIENT => {
+                self.model.ambient = Vec4::new(params[0], params[1], params[2], params[3]);
+            }
+            LIGHT_MODEL_LOCAL_VIEWER => self.model.local_viewer = params[0] != 0.0,
+            LIGHT_MODEL_TWO_SIDE => self.model.two_side = params[0] != 0.0,
+            _ => unreachable!(),
+        }
+        Ok(())
+    }
+
+    pub fn set_color_material(&mut self, face: Enum, mode: Enum) -> Result<(), Error> {
+        let face = Face::from_enum(face)?;
+
+        use Enum::*;
+        self.color_material_param = match mode {
+            EMISSION => ColorMaterialParam::Emission,
+            AMBIENT => ColorMaterialParam::Ambient,
+            DIFFUSE => ColorMaterialParam::Diffuse,
+            SPECULAR => ColorMaterialParam::Specular,
+            AMBIENT_AND_DIFFUSE => ColorMaterialParam::AmbientAndDiffuse,
+            _ => return Err(Error::InvalidEnum),
+        };
+        self.color_material_face = face;
+        Ok(())
+    }
+
+    /// Update the material parameter selected by `glColorMaterial` to `color`.
+    pub fn track_color_material(&mut self, color: Vec4) {
+        for &i in self.color_material_face.indices() {
+            let material = &mut self.materials[i];
+            match self.color_material_param {
+                ColorMaterialParam::Emission => material.emission = color,
+                ColorMaterialParam::Ambient => material.ambient = color,
+                ColorMaterialParam::Diffuse => material.diffuse = color,
+                ColorMaterialParam::Specular => material.specular = color,
+                ColorMaterialParam::AmbientAndDiffuse => {
+                    material.ambient = color;
+                    material.diffuse = color;
+                }
+            }
+        }
+    }
+
+    /// Compute the front and back colors for a vertex, given its position and
+    /// normal, in eye coordinates (the latter assumed to be already normalized,
+    /// if `GL_NORMALIZE` is enabled).
+    pub fn shade(&self, caps: &Capabilities, position: Vec4, normal: Vec3) -> [Vec4; 2] {
+        let front = self.shade_face(caps, &self.materials[0], position, normal);
+        let back = if self.model.two_side {
+            self.shade_face(caps, &self.materials[1], position, -normal)
+        } else {
+            front
+        };
+        [front, back]
+    }
+
+    fn shade_face(
+        &self,
+        caps: &Capabilities,
+        material: &Material,
+        position: Vec4,
+        normal: Vec3,
+    ) -> Vec4 {
+        let position = position.truncate() / position.w;
+
+        let mut color = material.emission + material.ambient * self.model.ambient;
+        let enabled_lights = self
+            .lights
+            .iter()
+            .zip(&Capability::LIGHTS)
+            .filter(|&(_, &cap)| caps.contains(cap))
+            .map(|(light, _)| light);
+        for light in enabled_lights {
+            // Direction from the vertex to the light, and distance between them.
+            let (to_light, attenuation) = if light.position.w != 0.0 {
+                let v = light.position.truncate() / light.position.w - position;
+                let d = v.length();
+                let [k0, k1, k2] = light.attenuation;
+                (v / d, 1.0 / (k0 + k1 * d + k2 * d * d))
+            } else {
+                (light.position.truncate().normalize(), 1.0)
+            };
+
+            let spot = if light.spot_cutoff == 180.0 {
+                1.0
+            } else {
+                let cos = (-to_light).dot(light.spot_direction.normalize());
+                if cos < light.spot_cutoff.to_radians().cos() {
+                    0.0
+                } else {
+                    cos.max(0.0).powf(light.spot_exponent)
+                }
+            };
+
+            let diffuse = normal.dot(to_light).max(0.0);
+            let specular = if diffuse > 0.0 {
+                let to_eye = if self.model.local_viewer {
+                    (-position).normalize()
+                } else {
+                    Vec3::unit_z()
+                };
+                let half = (to_light + to_eye).normalize();
+                normal.dot(half).max(0.0).powf(material.shininess)
+            } else {
+                0.0
+            };
+
+            color += (material.ambient * light.ambient
+                + material.diffuse * light.diffuse * diffuse
+                + material.specular * light.specular * specular)
+                * (attenuation * spot);
+        }
+
+        // NOTE(eddyb) the resulting alpha is always the diffuse material alpha.
+        let mut color = color.max(Vec4::zero()).min(Vec4::one());
+        color.w = material.diffuse.w.clamp(0.0, 1.0);
+        color
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn light0() -> Capabilities {
+        let mut caps = Capabilities::default();
+        caps.set(Capability::Light0, true);
+        caps
+    }
+
+    fn assert_approx_eq(a: Vec4, b: Vec4) {
+        assert!((a - b).abs().max_element() < 1e-6, "{:?} != {:?}", a, b);
+    }
+
+    #[test]
+    fn int_params_are_normalized_only_for_colors() {
+        assert_eq!(&int_params_to_float(Enum::DIFFUSE, &[i32::MAX])[..], &[1.0]);
+        assert_eq!(&int_params_to_float(Enum::SPOT_CUTOFF, &[45])[..], &[45.0]);
+    }
+
+    #[test]
+    fn default_light_and_material() {
+        let lighting = Lighting::default();
+        let position = Vec4::new(0.0, 0.0, -1.0, 1.0);
+
+        // Only the global ambient light contributes while `GL_LIGHT0` is off.
+        let caps = Capabilities::default();
+        let [front, back] = lighting.shade(&caps, position, Vec3::unit_z());
+        assert_approx_eq(front, Vec4::new(0.04, 0.04, 0.04, 1.0));
+        assert_eq!(front, back);
+
+        // `GL_LIGHT0` is directional, towards `-Z`, and white.
+        let [front, _] = lighting.shade(&light0(), position, Vec3::unit_z());
+        assert_approx_eq(front, Vec4::new(0.84, 0.84, 0.84, 1.0));
+        let [front, _] = lighting.shade(&light0(), position, -Vec3::unit_z());
+        assert_approx_eq(front, Vec4::new(0.04, 0.04, 0.04, 1.0));
+    }
+
+    #[test]
+    fn two_sided_lighting_flips_the_normal() {
+        let mut lighting = Lighting::default();
+        lighting.model.two_side = true;
+        lighting
+            .set_material(Enum::BACK, Enum::DIFFUSE, &[1.0, 0.0, 0.0, 0.5])
+            .unwrap();
+
+        let [front, back] = lighting.shade(&light0(), Vec4::unit_w(), -Vec3::unit_z());
+        assert_approx_eq(front, Vec4::new(0.04, 0.04, 0.04, 1.0));
+        // Colors are clamped to `[0, 1]`, after adding all the terms.
+        assert_approx_eq(back, Vec4::new(1.0, 0.04, 0.04, 0.5));
+    }
+
+    #[test]
+    fn spot_lights_and_attenuation() {
+        let mut lighting = Lighting::default();
+        let modelview = Mat4::identity();
+        let set = |lighting: &mut Lighting, pname, params: &[f32]| {
+            lighting.set_light(Enum::LIGHT0, pname, params, &modelview)
+        };
+        lighting.materials[0].ambient = Vec4::unit_w();
+        set(&mut lighting, Enum::POSITION, &[0.0, 0.0, 2.0, 1.0]).unwrap();
+        set(&mut lighting, Enum::LINEAR_ATTENUATION, &[1.0]).unwrap();
+
+        // Distance 2 (from the origin), with attenuation `1 / (1 + 2)`.
+        let [color, _] = lighting.shade(&light0(), Vec4::unit_w(), Vec3::unit_z());
+        assert_approx_eq(color, Vec4::new(0.8, 0.8, 0.8, 3.0) / 3.0);
+
+        // Outside the spot cone, only the (black) ambient term remains.
+        set(&mut lighting, Enum::SPOT_CUTOFF, &[10.0]).unwrap();
+        set(&mut lighting, Enum::SPOT_DIRECTION, &[0.0, 0.0, 1.0]).unwrap();
+        let [color, _] = lighting.shade(&light0(), Vec4::unit_w(), Vec3::unit_z());
+        assert_approx_eq(color, Vec4::unit_w());
+
+        assert_eq!(
+            set(&mut lighting, Enum::SPOT_CUTOFF, &[91.0]),
+            Err(Error::InvalidValue)
+        );
+        assert_eq!(
+            set(&mut lighting, Enum::SPOT_EXPONENT, &[-1.0]),
+            Err(Error::InvalidValue)
+        );
+        assert_eq!(
+            lighting.set_light(Enum::AMBIENT, Enum::AMBIENT, &[0.0; 4], &modelview),
+            Err(Error::InvalidEnum)
+        );
+    }
+
+    #[test]
+    fn color_material_tracks_the_current_color() {
+        let mut lighting = Lighting::default();
+        let red = Vec4::new(1.0, 0.0, 0.0, 1.0);
+
+        lighting
+            .set_color_material(Enum::BACK, Enum::EMISSION)
+            .unwrap();
+        lighting.track_color_material(red);
+        assert_eq!(lighting.materials[0].emission, Material::default().emission);
+        assert_eq!(lighting.materials[1].emission, red);
+
+        assert_eq!(
+            lighting.set_color_material(Enum::BACK, Enum::SHININESS),
+            Err(Error::InvalidEnum)
+        );
+    }
+}
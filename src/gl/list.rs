@@ -0,0 +1,418 @@
+//! Display list names (`glGenLists`, `glCallLists`), and the compilation of
+//! display lists into an optimized form (see `DisplayList::compile`).
+
+use crate::gl::api_1_0::{Command, Command::*, Enum};
+use crate::gl::{raster, state, DisplayList, Error};
+use glam::{DMat4, DVec3, Mat4, Vec3, Vec4};
+use indexmap::IndexMap;
+use std::ffi::c_void;
+use std::slice;
+use std::sync::Arc;
+
+/// Find the first range of `count` consecutive list names that are all unused
+/// (i.e. not in `lists`), returning the first name in that range.
+pub fn find_unused_range(lists: &IndexMap<u32, Arc<DisplayList>>, count: u32) -> Option<u32> {
+    let mut used: Vec<u32> = lists.keys().copied().collect();
+    used.sort_unstable();
+
+    // NOTE(eddyb) `u64` is used to avoid overflow near the end of the range.
+    let mut start = 1;
+    for &n in &used {
+        if n as u64 >= start + count as u64 {
+            break;
+        }
+        start = start.max(n as u64 + 1);
+    }
+    if start + count as u64 - 1 <= u32::MAX as u64 {
+        Some(start as u32)
+    } else {
+        None
+    }
+}
+
+/// Read the `n` list names passed to `glCallLists`, of the type given by `ty`.
+///
+/// Signed names are sign-extended, so that adding them to the list base
+/// (with wrapping) behaves like subtraction, for negative names.
+///
+/// # Safety
+///
+/// `names` must point to `n` values of the type given by `ty`.
+pub unsafe fn read_names(n: u32, ty: Enum, names: *const c_void) -> Result<Vec<u32>, Error> {
+    let n = n as usize;
+    let read = |size: usize| slice::from_raw_parts(names as *const u8, n * size);
+
+    // Names made out of multiple bytes, with the first being the most significant.
+    let read_bytes = |size: usize| {
+        read(size)
+            .chunks_exact(size)
+            .map(|bytes| bytes.iter().fold(0, |name, &b| (name << 8) | b as u32))
+            .collect()
+    };
+
+    use Enum::*;
+    Ok(match ty {
+        BYTE => read(1).iter().map(|&b| b as i8 as u32).collect(),
+        UNSIGNED_BYTE => read(1).iter().map(|&b| b as u32).collect(),
+        SHORT => slice::from_raw_parts(names as *const i16, n)
+            .iter()
+            .map(|&s| s as u32)
+            .collect(),
+        UNSIGNED_SHORT => slice::from_raw_parts(names as *const u16, n)
+            .iter()
+            .map(|&s| s as u32)
+            .collect(),
+        INT | UNSIGNED_INT => slice::from_raw_parts(names as *const u32, n).to_vec(),
+        // NOTE(eddyb) `as` saturates, which is the best we can do here.
+        FLOAT => slice::from_raw_parts(names as *const f32, n)
+            .iter()
+            .map(|&f| f as i32 as u32)
+            .collect(),
+        _2_BYTES => read_bytes(2),
+        _3_BYTES => read_bytes(3),
+        _4_BYTES => read_bytes(4),
+        _ => return Err(Error::InvalidEnum),
+    })
+}
+
+/// Optimized form of the commands in a display list (see `DisplayList::compile`).
+#[derive(Clone, Debug)]
+pub enum Op {
+    /// A command to execute as-is.
+    Command(Command),
+
+    /// A sequence of matrix commands (e.g. `glTranslatef` or `glRotatef`),
+    /// pre-multiplied into one matrix, which either replaces the current
+    /// matrix (if the sequence starts with `glLoadIdentity`/`glLoadMatrix`),
+    /// or is multiplied into it.
+    Matrix { load: bool, mat: DMat4 },
+
+    /// All the commands from a `glBegin` to its `glEnd`, as long as they only
+    /// specify vertices and their attributes.
+    Primitive(PackedPrimitive),
+}
+
+/// Values for the current per-vertex attributes, specified before a vertex
+/// (or `glEnd`), with the ones left unspecified keeping their current value.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct Attribs {
+    pub color: Option<Vec4>,
+    pub index: Option<f32>,
+    pub normal: Option<Vec3>,
+    pub texcoord: Option<Vec4>,
+    pub edge_flag: Option<bool>,
+}
+
+impl Attribs {
+    /// Record the attribute value specified by `cmd`, returning `false` if it
+    /// isn't a per-vertex attribute command.
+    fn specify(&mut self, cmd: &Command) -> bool {
+        match *cmd {
+            glColor4f(r, g, b, a) => self.color = Some(Vec4::new(r, g, b, a)),
+            glIndexf(c) => self.index = Some(c),
+            glNormal3f(x, y, z) => self.normal = Some(Vec3::new(x, y, z)),
+            glTexCoord4f(s, t, r, q) => self.texcoord = Some(Vec4::new(s, t, r, q)),
+            glEdgeFlag(flag) => self.edge_flag = Some(flag),
+            _ => return false,
+        }
+        true
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct PackedPrimitive {
+    /// The primitive mode passed to `glBegin` (always valid).
+    pub mode: Enum,
+
+    /// Vertices (in object coordinates), with the attributes specified since
+    /// the previous vertex (or `glBegin`).
+    pub vertices: Vec<(Attribs, Vec4)>,
+
+    /// Attributes specified after the last vertex, before `glEnd`.
+    pub end: Attribs,
+}
+
+impl PackedPrimitive {
+    /// Pack the commands between `glBegin(mode)` and `glEnd`, if possible.
+    fn pack(mode: Enum, cmds: &[Command]) -> Option<Self> {
+        if !raster::is_primitive_mode(mode) {
+            return None;
+        }
+        let mut vertices = vec![];
+        let mut attribs = Attribs::default();
+        for cmd in cmds {
+            match *cmd {
+                glVertex4f(x, y, z, w) => {
+                    vertices.push((attribs, Vec4::new(x, y, z, w)));
+                    attribs = Attribs::default();
+                }
+                _ if attribs.specify(cmd) => {}
+                _ => return None,
+            }
+        }
+        Some(PackedPrimitive {
+            mode,
+            vertices,
+            end: attribs,
+        })
+    }
+}
+
+/// The matrix loaded, or multiplied in, by a matrix command (with `true` for
+/// loading), if `cmd` is a matrix command that can't fail.
+fn matrix_op(cmd: &Command) -> Option<(bool, DMat4)> {
+    let rotate = |angle: f64, axis: DVec3| {
+        // NOTE(eddyb) this matches `glRotate`'s treatment of zero-length axes.
+        if axis.length_squared() > 0.0 {
+            DMat4::from_axis_angle(axis.normalize(), angle.to_radians())
+        } else {
+            DMat4::identity()
+        }
+    };
+    let f32_to_f64 = |m: &[f32; 16]| Mat4::from_cols_array(m).as_f64();
+    Some(match *cmd {
+        glLoadIdentity => (true, DMat4::identity()),
+        glLoadMatrixf(ref m) => (true, f32_to_f64(m)),
+        glLoadMatrixd(ref m) => (true, DMat4::from_cols_array(m)),
+        glMultMatrixf(ref m) => (false, f32_to_f64(m)),
+        glMultMatrixd(ref m) => (false, DMat4::from_cols_array(m)),
+        glRotatef(angle, x, y, z) => (
+            false,
+            rotate(angle as f64, DVec3::new(x as f64, y as f64, z as f64)),
+        ),
+        glRotated(angle, x, y, z) => (false, rotate(angle, DVec3::new(x, y, z))),
+        glScalef(x, y, z) => (
+            false,
+            DMat4::from_scale(DVec3::new(x as f64, y as f64, z as f64)),
+        ),
+        glScaled(x, y, z) => (false, DMat4::from_scale(DVec3::new(x, y, z))),
+        glTranslatef(x, y, z) => (
+            false,
+            DMat4::from_translation(DVec3::new(x as f64, y as f64, z as f64)),
+        ),
+        glTranslated(x, y, z) => (false, DMat4::from_translation(DVec3::new(x, y, z))),
+        glFrustum(l, r, b, t, n, f) => (false, state::frustum(l, r, b, t, n, f).ok()?),
+        glOrtho(l, r, b, t, n, f) => (false, state::ortho(l, r, b, t, n, f).ok()?),
+        _ => return None,
+    })
+}
+
+/// Display list calls replaced with the contents of the called lists.
+#[derive(Debug)]
+pub struct Flattened {
+    /// The lists that were called, which have to still be defined (and not
+    /// replaced since) for `ops` to be used, instead of `DisplayList::ops`.
+    pub called: Vec<(u32, Arc<DisplayList>)>,
+
+    pub ops: Vec<Op>,
+}
+
+impl Flattened {
+    pub fn is_valid(&self, lists: &IndexMap<u32, Arc<DisplayList>>) -> bool {
+        self.called
+            .iter()
+            .all(|(name, list)| lists.get(name).is_some_and(|l| Arc::ptr_eq(l, list)))
+    }
+}
+
+#[derive(Default)]
+struct Compiler<'a> {
+    /// Lists to flatten calls into (see `Flattened`), if any.
+    lists: Option<&'a IndexMap<u32, Arc<DisplayList>>>,
+
+    called: Vec<(u32, Arc<DisplayList>)>,
+    ops: Vec<Op>,
+}
+
+impl Compiler<'_> {
+    fn push(&mut self, op: Op) {
+        if let (
+            Some(Op::Matrix { mat, .. }),
+            Op::Matrix {
+                load: false,
+                mat: other,
+            },
+        ) = (self.ops.last_mut(), &op)
+        {
+            *mat = mat.mul_mat4(other);
+            return;
+        }
+        if let (Some(last @ Op::Matrix { .. }), Op::Matrix { load: true, .. }) =
+            (self.ops.last_mut(), &op)
+        {
+            *last = op;
+            return;
+        }
+        self.ops.push(op);
+    }
+
+    fn compile(&mut self, mut cmds: &[Command]) {
+        while let Some((cmd, rest)) = cmds.split_first() {
+            cmds = rest;
+
+            if let glBegin(mode) = *cmd {
+                let end = cmds.iter().position(|cmd| matches!(cmd, glEnd));
+                if let Some(primitive) =
+                    end.and_then(|end| PackedPrimitive::pack(mode, &cmds[..end]))
+                {
+                    self.push(Op::Primitive(primitive));
+                    cmds = &cmds[end.unwrap() + 1..];
+                    continue;
+                }
+            }
+
+            if let glCallList(name) = *cmd {
+                if let Some(list) = self.lists.and_then(|lists| lists.get(&name)) {
+                    // NOTE(eddyb) only lists which don't call other lists
+                    // are flattened, so that they'd never be nested deeper
+                    // than one level below the list being compiled.
+                    let is_leaf = list.ops.iter().all(|op| {
+                        !matches!(
+                            op,
+                            Op::Command(glCallList(_)) | Op::Command(glCallLists(..))
+                        )
+                    });
+                    if is_leaf {
+                        for op in &list.ops {
+                            self.push(op.clone());
+                        }
+                        self.called.push((name, list.clone()));
+                        continue;
+                    }
+                }
+            }
+
+            match matrix_op(cmd) {
+                Some((load, mat)) => self.push(Op::Matrix { load, mat }),
+                None => self.push(Op::Command(cmd.clone())),
+            }
+        }
+    }
+}
+
+impl DisplayList {
+    /// Compile the commands between `glNewList` and `glEndList` into a list,
+    /// flattening calls to lists currently defined in `lists` (if possible).
+    pub fn compile(cmds: &[Command], lists: &IndexMap<u32, Arc<DisplayList>>) -> Self {
+        let mut compiler = Compiler::default();
+        compiler.compile(cmds);
+        let ops = compiler.ops;
+
+        let mut compiler = Compiler {
+            lists: Some(lists),
+            ..Compiler::default()
+        };
+        compiler.compile(cmds);
+        let flattened = if compiler.called.is_empty() {
+            None
+        } else {
+            Some(Flattened {
+                called: compiler.called,
+                ops: compiler.ops,
+            })
+        };
+
+        DisplayList { ops, flattened }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lists(names: &[u32]) -> IndexMap<u32, Arc<DisplayList>> {
+        names.iter().map(|&n| (n, Arc::default())).collect()
+    }
+
+    #[test]
+    fn unused_range_skips_used_names() {
+        assert_eq!(find_unused_range(&lists(&[]), 3), Some(1));
+        assert_eq!(find_unused_range(&lists(&[4, 1, 2]), 1), Some(3));
+        assert_eq!(find_unused_range(&lists(&[4, 1, 2]), 2), Some(5));
+        assert_eq!(find_unused_range(&lists(&[2, 3]), 1), Some(1));
+    }
+
+    #[test]
+    fn unused_range_near_the_end() {
+        assert_eq!(find_unused_range(&lists(&[]), u32::MAX), Some(1));
+        assert_eq!(find_unused_range(&lists(&[1]), u32::MAX), None);
+        assert_eq!(find_unused_range(&lists(&[1, u32::MAX]), 2), Some(2));
+        assert_eq!(find_unused_range(&lists(&[u32::MAX - 1]), 1), Some(1));
+    }
+
+    #[test]
+    fn read_names_of_every_type() {
+        let read = |n, ty, names: *const u8| unsafe { read_names(n, ty, names as *const c_void) };
+
+        assert_eq!(
+            read(2, Enum::BYTE, [0xff, 1].as_ptr()),
+            Ok(vec![u32::MAX, 1])
+        );
+        assert_eq!(
+            read(2, Enum::UNSIGNED_BYTE, [0xff, 1].as_ptr()),
+            Ok(vec![0xff, 1])
+        );
+        let shorts = [-2i16, 3];
+        assert_eq!(
+            read(2, Enum::SHORT, shorts.as_ptr().cast()),
+            Ok(vec![-2i32 as u32, 3])
+        );
+        assert_eq!(
+            read(2, Enum::UNSIGNED_SHORT, shorts.as_ptr().cast()),
+            Ok(vec![0xfffe, 3])
+        );
+        let floats = [-2.5f32, 7.9];
+        assert_eq!(
+            read(2, Enum::FLOAT, floats.as_ptr().cast()),
+            Ok(vec![-2i32 as u32, 7])
+        );
+
+        // Multi-byte names are always big-endian, regardless of the platform.
+        let bytes = [1, 2, 3, 4, 5, 6];
+        assert_eq!(
+            read(3, Enum::_2_BYTES, bytes.as_ptr()),
+            Ok(vec![0x0102, 0x0304, 0x0506])
+        );
+        assert_eq!(
+            read(2, Enum::_3_BYTES, bytes.as_ptr()),
+            Ok(vec![0x010203, 0x040506])
+        );
+        assert_eq!(
+            read(1, Enum::_4_BYTES, bytes.as_ptr()),
+            Ok(vec![0x01020304])
+        );
+
+        assert_eq!(read(0, Enum::RGBA, bytes.as_ptr()), Err(Error::InvalidEnum));
+    }
+
+    #[test]
+    fn flattened_calls_are_invalidated_by_redefinition() {
+        let mut lists = IndexMap::new();
+        lists.insert(
+            2,
+            Arc::new(DisplayList::compile(&[glPointSize(3.0)], &lists)),
+        );
+        let caller = DisplayList::compile(&[glCallList(2), glCallList(3)], &lists);
+
+        // Only calls to lists defined at compile time are flattened.
+        let flattened = caller.flattened.as_ref().unwrap();
+        assert!(matches!(
+            flattened.ops[..],
+            [Op::Command(glPointSize(_)), Op::Command(glCallList(3))]
+        ));
+        assert!(flattened.is_valid(&lists));
+
+        // Defining other lists doesn't affect the flattened calls.
+        lists.insert(3, Arc::default());
+        assert!(flattened.is_valid(&lists));
+
+        // Recompiling a called list (even with the same commands) does.
+        lists.insert(
+            2,
+            Arc::new(DisplayList::compile(&[glPointSize(3.0)], &lists)),
+        );
+        assert!(!flattened.is_valid(&lists));
+        lists.shift_remove(&2);
+        assert!(!flattened.is_valid(&lists));
+    }
+}
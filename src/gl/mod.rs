@@ -1,27 +1,84 @@
 use indexmap::IndexMap;
 use std::cell::Cell;
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 
 pub mod api_1_0;
+pub mod client;
 mod debug;
+pub mod fragment;
+pub mod get;
+pub mod light;
+pub mod list;
+pub mod pixel;
+pub mod raster;
 pub mod state;
+pub mod tex_gen;
+pub mod texture;
 
+/// GL errors, as reported by `glGetError`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Error {
+    InvalidEnum,
+    InvalidValue,
+    InvalidOperation,
+    StackOverflow,
+    StackUnderflow,
+    OutOfMemory,
+}
+
+impl From<Error> for api_1_0::Enum {
+    fn from(error: Error) -> Self {
+        match error {
+            Error::InvalidEnum => api_1_0::Enum::INVALID_ENUM,
+            Error::InvalidValue => api_1_0::Enum::INVALID_VALUE,
+            Error::InvalidOperation => api_1_0::Enum::INVALID_OPERATION,
+            Error::StackOverflow => api_1_0::Enum::STACK_OVERFLOW,
+            Error::StackUnderflow => api_1_0::Enum::STACK_UNDERFLOW,
+            Error::OutOfMemory => api_1_0::Enum::OUT_OF_MEMORY,
+        }
+    }
+}
+
+/// A display list, compiled from the commands between `glNewList` and
+/// `glEndList` (see `DisplayList::compile`).
 #[derive(Debug, Default)]
 pub struct DisplayList {
-    pub cmds: Vec<api_1_0::Command>,
+    pub ops: Vec<list::Op>,
+
+    /// Alternative version of `ops`, with calls to other lists flattened,
+    /// usable only as long as those lists are left unchanged.
+    pub flattened: Option<list::Flattened>,
+}
+
+/// Objects shared between all the contexts created to share them (see
+/// `glXCreateContext`), which may be current on different threads.
+#[derive(Debug, Default)]
+pub struct ShareGroup {
+    pub lists: IndexMap<u32, Arc<DisplayList>>,
+    pub textures: IndexMap<u32, texture::Texture>,
 }
 
 #[derive(Debug)]
 pub struct Context {
-    pub pending_cmds: Vec<api_1_0::Command>,
+    /// The framebuffer of the drawable this context is current to (if any),
+    /// which is shared with all other contexts current to the same drawable.
+    framebuffer: Option<Arc<Mutex<raster::Framebuffer>>>,
+
+    /// Whether this context has ever been made current to a drawable.
+    attached: bool,
+
+    pub share_group: Arc<Mutex<ShareGroup>>,
 
     // Display lists.
-    pub first_unused_list: u32,
-    pub lists: IndexMap<u32, Arc<DisplayList>>,
-    pub compile_list: Option<(u32, DisplayList)>,
+    pub compile_list: Option<(u32, Vec<api_1_0::Command>)>,
     pub execute_immediately: bool,
 
     pub state: state::State,
+    pub client: client::ClientState,
+
+    /// The sticky error flag: only the first error is recorded, and it's kept
+    /// until `glGetError` is called, even if more errors happen in between.
+    pub error: Option<Error>,
 }
 
 enum TlsState {
@@ -32,22 +89,122 @@ enum TlsState {
 
 impl TlsState {
     fn swap_in(self) -> Self {
-        thread_local!(static CURRENT_CX: Cell<TlsState> = Cell::new(TlsState::Empty));
+        thread_local!(static CURRENT_CX: Cell<TlsState> = const { Cell::new(TlsState::Empty) });
         CURRENT_CX.with(|current| current.replace(self))
     }
 }
 
 impl Context {
-    pub fn new() -> Self {
+    pub fn new(share_group: Arc<Mutex<ShareGroup>>) -> Self {
         Context {
-            pending_cmds: vec![],
+            framebuffer: None,
+            attached: false,
+
+            share_group,
 
-            first_unused_list: 1,
-            lists: IndexMap::new(),
             compile_list: None,
             execute_immediately: true,
 
             state: state::State::default(),
+            client: client::ClientState::default(),
+
+            error: None,
+        }
+    }
+
+    /// Set the framebuffer to render into (see `Context::framebuffer`), which
+    /// also initializes the viewport, the first time a framebuffer is set.
+    pub fn set_framebuffer(&mut self, framebuffer: Option<Arc<Mutex<raster::Framebuffer>>>) {
+        if let Some(framebuffer) = &framebuffer {
+            if !self.attached {
+                self.attached = true;
+
+                let framebuffer = framebuffer.lock().unwrap();
+                let viewport = &mut self.state.viewport;
+                viewport.width = framebuffer.width.min(state::MAX_VIEWPORT_DIMS);
+                viewport.height = framebuffer.height.min(state::MAX_VIEWPORT_DIMS);
+                let scissor = &mut self.state.scissor;
+                scissor.width = framebuffer.width;
+                scissor.height = framebuffer.height;
+            }
+        }
+        self.framebuffer = framebuffer;
+    }
+
+    pub fn record_error(&mut self, error: Error) {
+        self.error.get_or_insert(error);
+    }
+
+    pub fn execute(&mut self, cmd: api_1_0::Command) {
+        let mut share_group = self.share_group.lock().unwrap();
+        let framebuffer = &mut self.framebuffer;
+        let result = self
+            .state
+            .apply(cmd, &mut share_group, &mut |state, shared, cmd| {
+                use api_1_0::Command::*;
+                match cmd {
+                    glClear(mask) => {
+                        if mask & !api_1_0::CLEAR_BUFFER_BITS != 0 {
+                            return Err(Error::InvalidValue);
+                        }
+                        if let Some(framebuffer) = framebuffer {
+                            let mut framebuffer = framebuffer.lock().unwrap();
+                            let scissor = Some(&state.scissor)
+                                .filter(|_| state.caps.contains(state::Capability::ScissorTest));
+                            if mask & api_1_0::COLOR_BUFFER_BIT != 0 {
+                                let color_buffer = &state.color_buffer;
+                                framebuffer.clear_color(
+                                    color_buffer.clear_value,
+                                    color_buffer.color_writemask,
+                                    scissor,
+                                );
+                            }
+                            if mask & api_1_0::DEPTH_BUFFER_BIT != 0 && state.depth.writemask {
+                                framebuffer.clear_depth(state.depth.clear_value, scissor);
+                            }
+                            if mask & api_1_0::STENCIL_BUFFER_BIT != 0 {
+                                let stencil = &state.stencil;
+                                framebuffer.clear_stencil(
+                                    stencil.clear_value,
+                                    stencil.writemask,
+                                    scissor,
+                                );
+                            }
+                        }
+                    }
+                    glEnd => {
+                        let batch = state.begin.take().ok_or(Error::InvalidOperation)?;
+                        if let Some(framebuffer) = framebuffer {
+                            let mut rasterizer = raster::Rasterizer {
+                                state,
+                                texture: state.enabled_texture(shared),
+                                framebuffer: &mut framebuffer.lock().unwrap(),
+                                line_stipple_counter: 0,
+                            };
+                            raster::assemble(
+                                batch.mode,
+                                &batch.vertices,
+                                state.lighting.shade_model,
+                                |primitive| {
+                                    // NOTE(eddyb) the line stipple only continues
+                                    // across the connected segments of strips/loops.
+                                    if batch.mode == api_1_0::Enum::LINES {
+                                        rasterizer.line_stipple_counter = 0;
+                                    }
+                                    rasterizer.draw(primitive)
+                                },
+                            );
+                        }
+                    }
+                    // FIXME(eddyb) implement the remaining commands, which
+                    // are currently ignored (without even tracking their state).
+                    _ => {}
+                }
+                Ok(())
+            });
+        drop(share_group);
+        if let Err(error) = result {
+            self.record_error(error);
         }
     }
 
@@ -119,3 +276,282 @@ impl Context {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::api_1_0::{self, Command::*, Enum};
+    use super::*;
+
+    #[test]
+    fn only_the_first_error_is_kept() {
+        Box::new(Context::new(Default::default())).enter();
+        Context::with("test", |gl| {
+            gl.execute(glPopMatrix);
+            gl.execute(glMatrixMode(Enum::FLAT));
+        });
+        assert_eq!(
+            unsafe { api_1_0::glGetError() },
+            Enum::STACK_UNDERFLOW as u32
+        );
+
+        // Reading the error clears it, and valid commands don't set it.
+        assert_eq!(unsafe { api_1_0::glGetError() }, 0);
+        Context::with("test", |gl| gl.execute(glPushMatrix));
+        assert_eq!(unsafe { api_1_0::glGetError() }, 0);
+        Context::leave();
+    }
+
+    #[test]
+    fn viewport_is_initialized_on_first_attach() {
+        let framebuffer = |w, h| Some(Arc::new(Mutex::new(raster::Framebuffer::new(w, h))));
+        let mut gl = Context::new(Default::default());
+        let size = |gl: &Context| (gl.state.viewport.width, gl.state.viewport.height);
+
+        gl.set_framebuffer(None);
+        assert_eq!(size(&gl), (0, 0));
+        gl.set_framebuffer(framebuffer(300, 200));
+        assert_eq!(size(&gl), (300, 200));
+
+        // Later drawables (e.g. after resizing) don't affect the viewport.
+        gl.set_framebuffer(framebuffer(400, 400));
+        assert_eq!(size(&gl), (300, 200));
+
+        gl.execute(glViewport(0, 0, 10_000, 100));
+        assert_eq!(size(&gl), (state::MAX_VIEWPORT_DIMS, 100));
+    }
+
+    #[test]
+    fn enable_disable_and_is_enabled() {
+        let is_enabled = |cap: Enum| unsafe { api_1_0::glIsEnabled(cap as u32) };
+        Box::new(Context::new(Default::default())).enter();
+        assert_eq!(is_enabled(Enum::DITHER), 1);
+        assert_eq!(is_enabled(Enum::CULL_FACE), 0);
+
+        Context::with("test", |gl| {
+            gl.execute(glEnable(Enum::CULL_FACE));
+            gl.execute(glEnable(Enum::LIGHT7));
+            gl.execute(glEnable(Enum::TEXTURE_GEN_Q));
+            gl.execute(glDisable(Enum::DITHER));
+        });
+        assert_eq!(is_enabled(Enum::CULL_FACE), 1);
+        assert_eq!(is_enabled(Enum::LIGHT7), 1);
+        assert_eq!(is_enabled(Enum::LIGHT6), 0);
+        assert_eq!(is_enabled(Enum::TEXTURE_GEN_Q), 1);
+        assert_eq!(is_enabled(Enum::DITHER), 0);
+        assert_eq!(unsafe { api_1_0::glGetError() }, 0);
+
+        // Unknown capabilities are rejected, without affecting any others.
+        Context::with("test", |gl| gl.execute(glEnable(Enum::CULL_FACE_MODE)));
+        assert_eq!(unsafe { api_1_0::glGetError() }, Enum::INVALID_ENUM as u32);
+        Context::with("test", |gl| gl.execute(glDisable(Enum::FLAT)));
+        assert_eq!(unsafe { api_1_0::glGetError() }, Enum::INVALID_ENUM as u32);
+        assert_eq!(is_enabled(Enum::FLAT), 0);
+        assert_eq!(unsafe { api_1_0::glGetError() }, Enum::INVALID_ENUM as u32);
+        assert_eq!(unsafe { api_1_0::glIsEnabled(0xdead) }, 0);
+        assert_eq!(unsafe { api_1_0::glGetError() }, Enum::INVALID_ENUM as u32);
+        Context::with("test", |gl| {
+            assert_eq!(gl.state.caps, {
+                let mut caps = state::Capabilities::default();
+                caps.set(state::Capability::CullFace, true);
+                caps.set(state::Capability::Light7, true);
+                caps.set(state::Capability::TextureGenQ, true);
+                caps.set(state::Capability::Dither, false);
+                caps
+            });
+        });
+        Context::leave();
+    }
+
+    #[test]
+    fn commands_between_begin_and_end() {
+        let get_error = || unsafe { api_1_0::glGetError() };
+        Box::new(Context::new(Default::default())).enter();
+        api_1_0::glBegin(Enum::TRIANGLES as u32);
+        api_1_0::glColor3f(1.0, 0.0, 0.0);
+        api_1_0::glVertex3f(0.0, 0.0, 0.0);
+        api_1_0::glMatrixMode(Enum::PROJECTION as u32);
+        let mut depth = -1;
+        unsafe { api_1_0::glGetIntegerv(Enum::MODELVIEW_STACK_DEPTH as u32, &mut depth) };
+        assert_eq!(depth, -1);
+        // `glGetError` itself isn't allowed either (and returns no error).
+        assert_eq!(get_error(), Enum::NO_ERROR as u32);
+        api_1_0::glEnd();
+        assert_eq!(get_error(), Enum::INVALID_OPERATION as u32);
+        Context::with("test", |gl| {
+            assert!(matches!(
+                gl.state.transform.matrix_mode,
+                state::MatrixMode::ModelView
+            ));
+            assert_eq!(gl.state.current.color, glam::Vec4::new(1.0, 0.0, 0.0, 1.0));
+        });
+
+        api_1_0::glEnd();
+        assert_eq!(get_error(), Enum::INVALID_OPERATION as u32);
+        api_1_0::glBegin(Enum::POLYGON as u32);
+        api_1_0::glBegin(Enum::POLYGON as u32);
+        assert_eq!(get_error(), Enum::NO_ERROR as u32);
+        api_1_0::glEnd();
+        assert_eq!(get_error(), Enum::INVALID_OPERATION as u32);
+        Context::leave();
+    }
+
+    #[test]
+    fn stencil_buffer_is_drawn_to_and_read_back() {
+        let framebuffer = Arc::new(Mutex::new(raster::Framebuffer::new(4, 2)));
+        let mut gl = Box::new(Context::new(Default::default()));
+        gl.set_framebuffer(Some(framebuffer.clone()));
+        gl.enter();
+        Context::with("test", |gl| {
+            gl.execute(glClearStencil(3));
+            gl.execute(glClear(api_1_0::STENCIL_BUFFER_BIT));
+
+            // Increment the stencil value of the left half of the window.
+            gl.execute(glEnable(Enum::STENCIL_TEST));
+            gl.execute(glStencilFunc(Enum::EQUAL, 3, !0));
+            gl.execute(glStencilOp(Enum::KEEP, Enum::KEEP, Enum::INCR));
+            gl.execute(glBegin(Enum::QUADS));
+            for &(x, y) in &[(-1.0, -1.0), (0.0, -1.0), (0.0, 1.0), (-1.0, 1.0)] {
+                gl.execute(glVertex4f(x, y, 0.0, 1.0));
+            }
+            gl.execute(glEnd);
+        });
+
+        let mut stencil = [0u8; 8];
+        let mut shifted = [0u16; 8];
+        unsafe {
+            let read = |ty: Enum, pixels: *mut u8| {
+                api_1_0::glReadPixels(
+                    0,
+                    0,
+                    4,
+                    2,
+                    Enum::STENCIL_INDEX as u32,
+                    ty as u32,
+                    pixels.cast(),
+                )
+            };
+            read(Enum::UNSIGNED_BYTE, stencil.as_mut_ptr());
+            api_1_0::glPixelTransferi(Enum::INDEX_SHIFT as u32, 2);
+            api_1_0::glPixelTransferi(Enum::INDEX_OFFSET as u32, -1);
+            read(Enum::UNSIGNED_SHORT, shifted.as_mut_ptr().cast());
+            assert_eq!(api_1_0::glGetError(), 0);
+
+            // There's no color index mode.
+            api_1_0::glReadPixels(
+                0,
+                0,
+                1,
+                1,
+                Enum::COLOR_INDEX as u32,
+                Enum::UNSIGNED_BYTE as u32,
+                stencil.as_mut_ptr().cast(),
+            );
+            assert_eq!(api_1_0::glGetError(), Enum::INVALID_OPERATION as u32);
+        }
+        Context::leave();
+
+        assert_eq!(stencil, [4, 4, 3, 3, 4, 4, 3, 3]);
+        assert_eq!(shifted, [15, 15, 11, 11, 15, 15, 11, 11]);
+        assert_eq!(framebuffer.lock().unwrap().stencil, stencil);
+    }
+
+    #[test]
+    fn lists_are_shared_between_contexts() {
+        let shared = Arc::<Mutex<ShareGroup>>::default();
+        let point_size = || Context::with("test", |gl| gl.state.point.size);
+
+        Box::new(Context::new(shared.clone())).enter();
+        let list = unsafe { api_1_0::glGenLists(1) };
+        unsafe { api_1_0::glNewList(list, Enum::COMPILE as u32) };
+        api_1_0::glPointSize(3.0);
+        unsafe { api_1_0::glEndList() };
+        assert_eq!(point_size(), 1.0);
+        Context::leave();
+
+        // Lists compiled in one context can be called from any other context
+        // in the same share group, and their names aren't reused.
+        Box::new(Context::new(shared)).enter();
+        assert_eq!(unsafe { api_1_0::glIsList(list) }, 1);
+        assert_ne!(unsafe { api_1_0::glGenLists(1) }, list);
+        api_1_0::glCallList(list);
+        assert_eq!(point_size(), 3.0);
+        Context::leave();
+
+        // Contexts in other share groups don't see them.
+        Box::new(Context::new(Default::default())).enter();
+        assert_eq!(unsafe { api_1_0::glIsList(list) }, 0);
+        api_1_0::glCallList(list);
+        assert_eq!(point_size(), 1.0);
+        Context::leave();
+    }
+
+    #[test]
+    fn texture_names_and_default_textures() {
+        use api_1_0::{glBindTexture, glPopAttrib, glPushAttrib, glTexParameteri};
+        let tex_2d = Enum::TEXTURE_2D as u32;
+        let min_filter = || {
+            let mut filter = 0;
+            let pname = Enum::TEXTURE_MIN_FILTER as u32;
+            unsafe { api_1_0::glGetTexParameteriv(tex_2d, pname, &mut filter) };
+            filter as u32
+        };
+        let binding = || {
+            let mut name = -1;
+            unsafe { api_1_0::glGetIntegerv(Enum::TEXTURE_BINDING_2D as u32, &mut name) };
+            name as u32
+        };
+        let gen = || {
+            let mut names = [0; 2];
+            unsafe { api_1_0::glGenTextures(2, names.as_mut_ptr()) };
+            names
+        };
+
+        let shared = Arc::<Mutex<ShareGroup>>::default();
+        Box::new(Context::new(shared.clone())).enter();
+        assert_eq!(gen(), [1, 2]);
+
+        // Names are only textures after being bound (to a single target).
+        assert_eq!(unsafe { api_1_0::glIsTexture(1) }, 0);
+        glBindTexture(tex_2d, 1);
+        assert_eq!(unsafe { api_1_0::glIsTexture(1) }, 1);
+        glBindTexture(Enum::TEXTURE_1D as u32, 1);
+        assert_eq!(
+            unsafe { api_1_0::glGetError() },
+            Enum::INVALID_OPERATION as u32
+        );
+        glTexParameteri(
+            tex_2d,
+            Enum::TEXTURE_MIN_FILTER as u32,
+            Enum::NEAREST as i32,
+        );
+        assert_eq!(min_filter(), Enum::NEAREST as u32);
+
+        // Deleting a bound texture reverts to the (unmodified) default texture,
+        // and its name is reused, but not the names that are still reserved.
+        unsafe { api_1_0::glDeleteTextures(1, &1) };
+        assert_eq!(binding(), 0);
+        assert_eq!(min_filter(), Enum::NEAREST_MIPMAP_LINEAR as u32);
+        assert_eq!(gen(), [1, 3]);
+
+        // `GL_TEXTURE_BIT` saves the parameters of the bound textures (but
+        // they're restored into the textures that were bound at the time).
+        glBindTexture(tex_2d, 3);
+        glPushAttrib(api_1_0::TEXTURE_BIT);
+        glTexParameteri(tex_2d, Enum::TEXTURE_MIN_FILTER as u32, Enum::LINEAR as i32);
+        glBindTexture(tex_2d, 0);
+        glTexParameteri(tex_2d, Enum::TEXTURE_MIN_FILTER as u32, Enum::LINEAR as i32);
+        glPopAttrib();
+        assert_eq!(binding(), 3);
+        assert_eq!(min_filter(), Enum::NEAREST_MIPMAP_LINEAR as u32);
+        glBindTexture(tex_2d, 0);
+        assert_eq!(min_filter(), Enum::LINEAR as u32);
+        assert_eq!(unsafe { api_1_0::glGetError() }, 0);
+        Context::leave();
+
+        // The default textures aren't shared with other contexts.
+        Box::new(Context::new(shared)).enter();
+        assert_eq!(min_filter(), Enum::NEAREST_MIPMAP_LINEAR as u32);
+        assert_eq!(unsafe { api_1_0::glIsTexture(3) }, 1);
+        Context::leave();
+    }
+}
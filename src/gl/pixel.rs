@@ -0,0 +1,692 @@
+//! Pixel transfer between client memory and the GL, i.e. unpacking images
+//! (e.g. for `glTexImage2D`) and packing them (e.g. for `glReadPixels`),
+//! according to the pixel storage modes (see `glPixelStore`).
+
+use crate::gl::api_1_0::Enum;
+use crate::gl::Error;
+use glam::Vec4;
+use std::ffi::c_void;
+
+/// Pixel storage modes, used either for unpacking, or for packing.
+#[derive(Copy, Clone, Debug)]
+pub struct PixelStoreModes {
+    pub swap_bytes: bool,
+    pub lsb_first: bool,
+
+    /// Number of pixels in each row, with `0` meaning the image width.
+    pub row_length: u32,
+
+    pub skip_rows: u32,
+    pub skip_pixels: u32,
+
+    /// Alignment of the start of each row, in bytes (`1`, `2`, `4` or `8`).
+    pub alignment: u32,
+}
+
+impl Default for PixelStoreModes {
+    fn default() -> Self {
+        PixelStoreModes {
+            swap_bytes: false,
+            lsb_first: false,
+            row_length: 0,
+            skip_rows: 0,
+            skip_pixels: 0,
+            alignment: 4,
+        }
+    }
+}
+
+/// The `GL_UNPACK_*` and `GL_PACK_*` pixel storage modes.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct PixelStore {
+    pub unpack: PixelStoreModes,
+    pub pack: PixelStoreModes,
+}
+
+impl PixelStore {
+    /// Set the pixel storage mode `pname` (see `glPixelStoref`/`glPixelStorei`).
+    pub fn set(&mut self, pname: Enum, param: f64) -> Result<(), Error> {
+        use Enum::*;
+        let modes = match pname {
+            UNPACK_SWAP_BYTES | UNPACK_LSB_FIRST | UNPACK_ROW_LENGTH | UNPACK_SKIP_ROWS
+            | UNPACK_SKIP_PIXELS | UNPACK_ALIGNMENT => &mut self.unpack,
+            PACK_SWAP_BYTES | PACK_LSB_FIRST | PACK_ROW_LENGTH | PACK_SKIP_ROWS
+            | PACK_SKIP_PIXELS | PACK_ALIGNMENT => &mut self.pack,
+            _ => return Err(Error::InvalidEnum),
+        };
+        let int_param = || {
+            let param = param.round();
+            if param < 0.0 {
+                return Err(Error::InvalidValue);
+            }
+            // NOTE(eddyb) `as` saturates, which is the best we can do here.
+            Ok(param as u32)
+        };
+        match pname {
+            UNPACK_SWAP_BYTES | PACK_SWAP_BYTES => modes.swap_bytes = param != 0.0,
+            UNPACK_LSB_FIRST | PACK_LSB_FIRST => modes.lsb_first = param != 0.0,
+            UNPACK_ROW_LENGTH | PACK_ROW_LENGTH => modes.row_length = int_param()?,
+            UNPACK_SKIP_ROWS | PACK_SKIP_ROWS => modes.skip_rows = int_param()?,
+            UNPACK_SKIP_PIXELS | PACK_SKIP_PIXELS => modes.skip_pixels = int_param()?,
+            UNPACK_ALIGNMENT | PACK_ALIGNMENT => {
+                modes.alignment = match int_param()? {
+                    alignment @ 1 | alignment @ 2 | alignment @ 4 | alignment @ 8 => alignment,
+                    _ => return Err(Error::InvalidValue),
+                };
+            }
+            _ => unreachable!(),
+        }
+        Ok(())
+    }
+}
+
+/// Number of elements in each pixel (i.e. group of elements), for `format`.
+fn components(format: Enum) -> Result<usize, Error> {
+    use Enum::*;
+    match format {
+        COLOR_INDEX | STENCIL_INDEX | DEPTH_COMPONENT | RED | GREEN | BLUE | ALPHA | LUMINANCE => {
+            Ok(1)
+        }
+        LUMINANCE_ALPHA => Ok(2),
+        RGB => Ok(3),
+        RGBA => Ok(4),
+        _ => Err(Error::InvalidEnum),
+    }
+}
+
+/// Where each element of an image is located, in client memory.
+struct Layout {
+    modes: PixelStoreModes,
+    components: usize,
+    ty: Enum,
+
+    /// Size of each element in bytes, or `None` for `GL_BITMAP` (1 bit each).
+    element_size: Option<usize>,
+
+    /// Offset between the starts of consecutive rows, in bytes.
+    row_stride: usize,
+}
+
+impl Layout {
+    fn new(modes: &PixelStoreModes, width: u32, format: Enum, ty: Enum) -> Result<Self, Error> {
+        let components = components(format)?;
+        use Enum::*;
+        let element_size = match ty {
+            BITMAP if matches!(format, COLOR_INDEX | STENCIL_INDEX) => None,
+            UNSIGNED_BYTE | BYTE => Some(1),
+            UNSIGNED_SHORT | SHORT => Some(2),
+            UNSIGNED_INT | INT | FLOAT => Some(4),
+            _ => return Err(Error::InvalidEnum),
+        };
+
+        let row_length = match modes.row_length {
+            0 => width,
+            row_length => row_length,
+        } as usize;
+        let row_bytes = match element_size {
+            Some(size) => row_length * components * size,
+            None => row_length.div_ceil(8),
+        };
+        // NOTE(eddyb) this is equivalent to the spec's definition, as rows
+        // of elements at least as large as the alignment are always aligned.
+        let alignment = modes.alignment as usize;
+        let row_stride = row_bytes.next_multiple_of(alignment);
+
+        Ok(Layout {
+            modes: *modes,
+            components,
+            ty,
+            element_size,
+            row_stride,
+        })
+    }
+
+    /// The byte offset of element `c` of the pixel at `(x, y)`, and the mask
+    /// of the bit within that byte (only used for bitmaps).
+    fn offset(&self, x: u32, y: u32, c: usize) -> (usize, u8) {
+        let row = (self.modes.skip_rows + y) as usize * self.row_stride;
+        let x = (self.modes.skip_pixels + x) as usize;
+        match self.element_size {
+            Some(size) => (row + (x * self.components + c) * size, 0),
+            None => {
+                let mask = if self.modes.lsb_first {
+                    1 << (x % 8)
+                } else {
+                    0x80 >> (x % 8)
+                };
+                (row + x / 8, mask)
+            }
+        }
+    }
+
+    /// Read element `c` of the pixel at `(x, y)`, converting integers by mapping
+    /// their range to `[0, 1]`/`[-1, 1]` (see `Normalize`).
+    unsafe fn read(&self, data: *const u8, x: u32, y: u32, c: usize) -> f32 {
+        use crate::gl::api_1_0::Normalize;
+
+        let (offset, mask) = self.offset(x, y, c);
+        let ptr = data.add(offset);
+        macro_rules! read {
+            (normalized $ty:ty) => {
+                read!($ty).normalize()
+            };
+            ($ty:ty) => {
+                <$ty>::from_ne_bytes(self.read_bytes(ptr))
+            };
+        }
+        use Enum::*;
+        match self.ty {
+            BITMAP => (ptr.read() & mask != 0) as u8 as f32,
+            UNSIGNED_BYTE => read!(normalized u8),
+            BYTE => read!(normalized i8),
+            UNSIGNED_SHORT => read!(normalized u16),
+            SHORT => read!(normalized i16),
+            UNSIGNED_INT => read!(normalized u32),
+            INT => read!(normalized i32),
+            FLOAT => read!(f32),
+            _ => unreachable!(),
+        }
+    }
+
+    /// Read the color index of the pixel at `(x, y)`, without normalizing it.
+    unsafe fn read_index(&self, data: *const u8, x: u32, y: u32) -> f32 {
+        let (offset, _) = self.offset(x, y, 0);
+        let ptr = data.add(offset);
+        use Enum::*;
+        match self.ty {
+            BITMAP => self.read(data, x, y, 0),
+            UNSIGNED_BYTE => u8::from_ne_bytes(self.read_bytes(ptr)) as f32,
+            BYTE => i8::from_ne_bytes(self.read_bytes(ptr)) as f32,
+            UNSIGNED_SHORT => u16::from_ne_bytes(self.read_bytes(ptr)) as f32,
+            SHORT => i16::from_ne_bytes(self.read_bytes(ptr)) as f32,
+            UNSIGNED_INT => u32::from_ne_bytes(self.read_bytes(ptr)) as f32,
+            INT => i32::from_ne_bytes(self.read_bytes(ptr)) as f32,
+            FLOAT => f32::from_ne_bytes(self.read_bytes(ptr)),
+            _ => unreachable!(),
+        }
+    }
+
+    /// Read the bytes of an element at `ptr`, swapping them if requested.
+    unsafe fn read_bytes<const N: usize>(&self, ptr: *const u8) -> [u8; N] {
+        let mut bytes = ptr.cast::<[u8; N]>().read_unaligned();
+        if self.modes.swap_bytes {
+            bytes.reverse();
+        }
+        bytes
+    }
+
+    /// Write element `c` of the pixel at `(x, y)`, converting `[0, 1]`/`[-1, 1]`
+    /// to the full range of integer types (i.e. the inverse of `Normalize`),
+    /// or setting the bit for non-zero values (for bitmaps).
+    unsafe fn write(&self, data: *mut u8, x: u32, y: u32, c: usize, value: f32) {
+        let (offset, mask) = self.offset(x, y, c);
+        let ptr = data.add(offset);
+        macro_rules! write {
+            ($ty:ty = $value:expr) => {
+                self.write_bytes(ptr, <$ty>::to_ne_bytes($value))
+            };
+            (unsigned $ty:ty) => {
+                write!($ty = (value.clamp(0.0, 1.0) as f64 * <$ty>::MAX as f64).round() as $ty)
+            };
+            (signed $ty:ty: $unsigned:ty) => {{
+                let value = value.clamp(-1.0, 1.0) as f64 * <$unsigned>::MAX as f64;
+                write!($ty = ((value - 1.0) / 2.0).round() as $ty)
+            }};
+        }
+        use Enum::*;
+        match self.ty {
+            BITMAP => {
+                let byte = ptr.read();
+                ptr.write(if value != 0.0 {
+                    byte | mask
+                } else {
+                    byte & !mask
+                });
+            }
+            UNSIGNED_BYTE => write!(unsigned u8),
+            BYTE => write!(signed i8: u8),
+            UNSIGNED_SHORT => write!(unsigned u16),
+            SHORT => write!(signed i16: u16),
+            UNSIGNED_INT => write!(unsigned u32),
+            INT => write!(signed i32: u32),
+            FLOAT => write!(f32 = value),
+            _ => unreachable!(),
+        }
+    }
+
+    /// Write the color or stencil index `index` as the pixel at `(x, y)`,
+    /// without normalizing it, but instead masking it to the bits of integer
+    /// types (excluding the sign bit, for signed types).
+    unsafe fn write_index(&self, data: *mut u8, x: u32, y: u32, index: f64) {
+        let (offset, _) = self.offset(x, y, 0);
+        let ptr = data.add(offset);
+        // NOTE(eddyb) `as` saturates, which is the best we can do here.
+        let i = index.floor() as i64;
+        use Enum::*;
+        match self.ty {
+            BITMAP => self.write(data, x, y, 0, (i & 1) as f32),
+            UNSIGNED_BYTE => self.write_bytes(ptr, (i as u8).to_ne_bytes()),
+            BYTE => self.write_bytes(ptr, ((i & 0x7f) as i8).to_ne_bytes()),
+            UNSIGNED_SHORT => self.write_bytes(ptr, (i as u16).to_ne_bytes()),
+            SHORT => self.write_bytes(ptr, ((i & 0x7fff) as i16).to_ne_bytes()),
+            UNSIGNED_INT => self.write_bytes(ptr, (i as u32).to_ne_bytes()),
+            INT => self.write_bytes(ptr, ((i & 0x7fff_ffff) as i32).to_ne_bytes()),
+            FLOAT => self.write_bytes(ptr, (index as f32).to_ne_bytes()),
+            _ => unreachable!(),
+        }
+    }
+
+    /// Write the bytes of an element at `ptr`, swapping them if requested.
+    unsafe fn write_bytes<const N: usize>(&self, ptr: *mut u8, mut bytes: [u8; N]) {
+        if self.modes.swap_bytes {
+            bytes.reverse();
+        }
+        ptr.cast::<[u8; N]>().write_unaligned(bytes);
+    }
+}
+
+/// Unpack a `width`x`height` image from client memory (`data`), converting
+/// every pixel to RGBA (see `glTexImage2D`), with rows ordered bottom-to-top.
+///
+/// Only color formats are supported, with `GL_COLOR_INDEX` pixels keeping
+/// their index in the first component (see `PixelMode::transfer_color_index`).
+///
+/// # Safety
+///
+/// `data` must point to an image of the given size, format and type, laid out
+/// according to `modes` (i.e. the `GL_UNPACK_*` pixel storage modes).
+pub unsafe fn unpack(
+    modes: &PixelStoreModes,
+    width: u32,
+    height: u32,
+    format: Enum,
+    ty: Enum,
+    data: *const c_void,
+) -> Result<Vec<Vec4>, Error> {
+    let layout = Layout::new(modes, width, format, ty)?;
+    let data = data as *const u8;
+
+    let mut pixels = Vec::with_capacity(width as usize * height as usize);
+    for y in 0..height {
+        for x in 0..width {
+            let element = |c| layout.read(data, x, y, c);
+            use Enum::*;
+            pixels.push(match format {
+                COLOR_INDEX => Vec4::new(layout.read_index(data, x, y), 0.0, 0.0, 0.0),
+                RED => Vec4::new(element(0), 0.0, 0.0, 1.0),
+                GREEN => Vec4::new(0.0, element(0), 0.0, 1.0),
+                BLUE => Vec4::new(0.0, 0.0, element(0), 1.0),
+                ALPHA => Vec4::new(0.0, 0.0, 0.0, element(0)),
+                LUMINANCE => {
+                    let l = element(0);
+                    Vec4::new(l, l, l, 1.0)
+                }
+                LUMINANCE_ALPHA => {
+                    let l = element(0);
+                    Vec4::new(l, l, l, element(1))
+                }
+                RGB => Vec4::new(element(0), element(1), element(2), 1.0),
+                RGBA => Vec4::new(element(0), element(1), element(2), element(3)),
+                _ => return Err(Error::InvalidEnum),
+            });
+        }
+    }
+    // NOTE(eddyb) the pixel transfer operations are applied later, by the
+    // commands using the pixels (see `PixelMode::transfer_color`).
+    Ok(pixels)
+}
+
+/// Unpack a polygon stipple pattern (see `state::PolygonStipple`) from client
+/// memory (`data`), where it's laid out like a 32x32 `GL_COLOR_INDEX` bitmap.
+///
+/// # Safety
+///
+/// `data` must point to a 32x32 bitmap, laid out according to `modes`.
+pub unsafe fn unpack_stipple(modes: &PixelStoreModes, data: *const u8) -> [u32; 32] {
+    let layout = Layout::new(modes, 32, Enum::COLOR_INDEX, Enum::BITMAP).unwrap();
+    let mut pattern = [0; 32];
+    for (y, row) in pattern.iter_mut().enumerate() {
+        for x in 0..32 {
+            if layout.read(data, x, y as u32, 0) != 0.0 {
+                *row |= 1 << x;
+            }
+        }
+    }
+    pattern
+}
+
+/// Pack a polygon stipple pattern (see `unpack_stipple`) into client memory.
+///
+/// # Safety
+///
+/// `data` must point to enough memory for a 32x32 bitmap, laid out according
+/// to `modes` (i.e. the `GL_PACK_*` pixel storage modes).
+pub unsafe fn pack_stipple(modes: &PixelStoreModes, pattern: &[u32; 32], data: *mut u8) {
+    let layout = Layout::new(modes, 32, Enum::COLOR_INDEX, Enum::BITMAP).unwrap();
+    for (y, &row) in pattern.iter().enumerate() {
+        for x in 0..32 {
+            layout.write(data, x, y as u32, 0, (row >> x & 1) as f32);
+        }
+    }
+}
+
+/// Like `unpack`, but only validating the format and type, without reading
+/// any pixels, for proxy targets (see `glTexImage2D`) or null `data`.
+///
+/// # Safety
+///
+/// See `unpack`.
+pub unsafe fn unpack_image(
+    modes: &PixelStoreModes,
+    target: Enum,
+    width: u32,
+    height: u32,
+    format: Enum,
+    ty: Enum,
+    data: *const c_void,
+) -> Result<Option<Vec<Vec4>>, Error> {
+    Layout::new(modes, width, format, ty)?;
+    if data.is_null() || matches!(target, Enum::PROXY_TEXTURE_1D | Enum::PROXY_TEXTURE_2D) {
+        return Ok(None);
+    }
+    unpack(modes, width, height, format, ty, data).map(Some)
+}
+
+/// Pack a `width`x`height` image into client memory (`data`), with `pixel`
+/// returning the RGBA color (or the depth, in the first component, for
+/// `GL_DEPTH_COMPONENT`) of the pixel at `(x, y)` (see `glReadPixels`).
+///
+/// # Safety
+///
+/// `data` must point to enough memory for an image of the given size, format
+/// and type, laid out according to `modes` (i.e. `GL_PACK_*` storage modes).
+pub unsafe fn pack(
+    modes: &PixelStoreModes,
+    width: u32,
+    height: u32,
+    format: Enum,
+    ty: Enum,
+    data: *mut c_void,
+    pixel: impl Fn(u32, u32) -> Vec4,
+) -> Result<(), Error> {
+    use Enum::*;
+    if ty == BITMAP || matches!(format, COLOR_INDEX | STENCIL_INDEX) {
+        return Err(Error::InvalidEnum);
+    }
+    let layout = Layout::new(modes, width, format, ty)?;
+    let data = data as *mut u8;
+
+    for y in 0..height {
+        for x in 0..width {
+            let p = pixel(x, y);
+            let luminance = || (p.x + p.y + p.z).min(1.0);
+            let elements = match format {
+                RED | DEPTH_COMPONENT => [p.x, 0.0, 0.0, 0.0],
+                GREEN => [p.y, 0.0, 0.0, 0.0],
+                BLUE => [p.z, 0.0, 0.0, 0.0],
+                ALPHA => [p.w, 0.0, 0.0, 0.0],
+                LUMINANCE => [luminance(), 0.0, 0.0, 0.0],
+                LUMINANCE_ALPHA => [luminance(), p.w, 0.0, 0.0],
+                RGB => [p.x, p.y, p.z, 0.0],
+                RGBA => [p.x, p.y, p.z, p.w],
+                _ => unreachable!(),
+            };
+            for (c, &value) in elements.iter().take(layout.components).enumerate() {
+                layout.write(data, x, y, c, value);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Pack a `width`x`height` image of indices (i.e. `GL_STENCIL_INDEX`) into
+/// client memory (`data`), with `index` returning the index of the pixel at
+/// `(x, y)`, after the pixel transfer operations (see `glReadPixels`).
+///
+/// # Safety
+///
+/// See `pack`.
+pub unsafe fn pack_indices(
+    modes: &PixelStoreModes,
+    width: u32,
+    height: u32,
+    format: Enum,
+    ty: Enum,
+    data: *mut c_void,
+    index: impl Fn(u32, u32) -> f64,
+) -> Result<(), Error> {
+    if !matches!(format, Enum::COLOR_INDEX | Enum::STENCIL_INDEX) {
+        return Err(Error::InvalidEnum);
+    }
+    let layout = Layout::new(modes, width, format, ty)?;
+    let data = data as *mut u8;
+
+    for y in 0..height {
+        for x in 0..width {
+            layout.write_index(data, x, y, index(x, y));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn modes(f: impl FnOnce(&mut PixelStoreModes)) -> PixelStoreModes {
+        let mut modes = PixelStoreModes::default();
+        f(&mut modes);
+        modes
+    }
+
+    fn unpack_bytes(modes: &PixelStoreModes, width: u32, height: u32, data: &[u8]) -> Vec<Vec4> {
+        unsafe {
+            unpack(
+                modes,
+                width,
+                height,
+                Enum::LUMINANCE,
+                Enum::UNSIGNED_BYTE,
+                data.as_ptr().cast(),
+            )
+            .unwrap()
+        }
+    }
+
+    fn luminance(pixels: &[Vec4]) -> Vec<u8> {
+        pixels.iter().map(|p| (p.x * 255.0).round() as u8).collect()
+    }
+
+    #[test]
+    fn pixel_store_validation() {
+        let mut store = PixelStore::default();
+        assert_eq!(
+            store.set(Enum::UNPACK_ALIGNMENT, 3.0),
+            Err(Error::InvalidValue)
+        );
+        assert_eq!(
+            store.set(Enum::PACK_ROW_LENGTH, -1.0),
+            Err(Error::InvalidValue)
+        );
+        assert_eq!(store.set(Enum::TEXTURE_2D, 0.0), Err(Error::InvalidEnum));
+        assert_eq!(store.set(Enum::PACK_ALIGNMENT, 8.0), Ok(()));
+        assert_eq!(store.set(Enum::UNPACK_SKIP_ROWS, 2.4), Ok(()));
+        assert_eq!((store.pack.alignment, store.unpack.alignment), (8, 4));
+        assert_eq!(store.unpack.skip_rows, 2);
+    }
+
+    #[test]
+    fn rows_are_aligned() {
+        let data: Vec<u8> = (0..16).collect();
+        let pixels = unpack_bytes(&PixelStoreModes::default(), 3, 2, &data);
+        assert_eq!(luminance(&pixels), [0, 1, 2, 4, 5, 6]);
+
+        let pixels = unpack_bytes(&modes(|m| m.alignment = 1), 3, 2, &data);
+        assert_eq!(luminance(&pixels), [0, 1, 2, 3, 4, 5]);
+
+        let pixels = unpack_bytes(&modes(|m| m.alignment = 8), 3, 2, &data);
+        assert_eq!(luminance(&pixels), [0, 1, 2, 8, 9, 10]);
+    }
+
+    #[test]
+    fn row_length_and_skips() {
+        let data: Vec<u8> = (0..32).collect();
+        let modes = modes(|m| {
+            m.row_length = 5;
+            m.skip_pixels = 1;
+            m.skip_rows = 2;
+            m.alignment = 1;
+        });
+        let pixels = unpack_bytes(&modes, 2, 2, &data);
+        assert_eq!(luminance(&pixels), [11, 12, 16, 17]);
+    }
+
+    #[test]
+    fn swap_bytes() {
+        let data = [0x00ffu16.swap_bytes(), 0xff00u16.swap_bytes()];
+        let pixels = unsafe {
+            unpack(
+                &modes(|m| m.swap_bytes = true),
+                2,
+                1,
+                Enum::LUMINANCE,
+                Enum::UNSIGNED_SHORT,
+                data.as_ptr().cast(),
+            )
+            .unwrap()
+        };
+        assert_eq!(pixels[0].x, 0x00ff as f32 / 65535.0);
+        assert_eq!(pixels[1].x, 0xff00 as f32 / 65535.0);
+
+        let mut packed = [0u16; 2];
+        unsafe {
+            pack(
+                &modes(|m| m.swap_bytes = true),
+                2,
+                1,
+                Enum::RED,
+                Enum::UNSIGNED_SHORT,
+                packed.as_mut_ptr().cast(),
+                |x, _| pixels[x as usize],
+            )
+            .unwrap();
+        }
+        assert_eq!(packed, data);
+    }
+
+    #[test]
+    fn pack_signed_and_unsigned() {
+        let color = Vec4::new(1.0, -1.0, 0.5, 2.0);
+        let pack_rgba = |ty| {
+            let mut packed = [0u8; 4];
+            unsafe {
+                pack(
+                    &PixelStoreModes::default(),
+                    1,
+                    1,
+                    Enum::RGBA,
+                    ty,
+                    packed.as_mut_ptr().cast(),
+                    |_, _| color,
+                )
+                .unwrap();
+            }
+            packed
+        };
+        assert_eq!(pack_rgba(Enum::UNSIGNED_BYTE), [255, 0, 128, 255]);
+        assert_eq!(pack_rgba(Enum::BYTE).map(|b| b as i8), [127, -128, 63, 127]);
+
+        let mut packed = [0u8; 1];
+        let pack_index = |format, ty, packed: &mut [u8]| unsafe {
+            pack(
+                &PixelStoreModes::default(),
+                1,
+                1,
+                format,
+                ty,
+                packed.as_mut_ptr().cast(),
+                |_, _| color,
+            )
+        };
+        assert_eq!(
+            pack_index(Enum::COLOR_INDEX, Enum::UNSIGNED_BYTE, &mut packed),
+            Err(Error::InvalidEnum)
+        );
+        assert_eq!(
+            pack_index(Enum::RED, Enum::BITMAP, &mut packed),
+            Err(Error::InvalidEnum)
+        );
+    }
+
+    #[test]
+    fn indices_are_not_normalized() {
+        let data = [-3i16, 300];
+        let unpacked = unsafe {
+            unpack(
+                &PixelStoreModes::default(),
+                2,
+                1,
+                Enum::COLOR_INDEX,
+                Enum::SHORT,
+                data.as_ptr().cast(),
+            )
+            .unwrap()
+        };
+        assert_eq!(
+            unpacked,
+            [
+                Vec4::new(-3.0, 0.0, 0.0, 0.0),
+                Vec4::new(300.0, 0.0, 0.0, 0.0)
+            ]
+        );
+
+        // Packing masks indices to the bits of the type (except the sign bit).
+        let pack_index = |ty, index| {
+            let mut packed = [0u8; 4];
+            unsafe {
+                pack_indices(
+                    &PixelStoreModes::default(),
+                    1,
+                    1,
+                    Enum::STENCIL_INDEX,
+                    ty,
+                    packed.as_mut_ptr().cast(),
+                    |_, _| index,
+                )
+                .unwrap();
+            }
+            packed
+        };
+        assert_eq!(pack_index(Enum::UNSIGNED_BYTE, 300.0)[0], 300u32 as u8);
+        assert_eq!(pack_index(Enum::BYTE, 255.0)[0], 0x7f);
+        assert_eq!(pack_index(Enum::UNSIGNED_BYTE, 2.75)[0], 2);
+        assert_eq!(pack_index(Enum::BITMAP, 3.0)[0], 0x80);
+        assert_eq!(f32::from_ne_bytes(pack_index(Enum::FLOAT, 2.75)), 2.75);
+    }
+
+    #[test]
+    fn stipple_bit_order() {
+        let mut data = [0u8; 32 * 4];
+        data[0] = 0x01;
+        data[4] = 0x80;
+
+        let msb_first = unsafe { unpack_stipple(&PixelStoreModes::default(), data.as_ptr()) };
+        assert_eq!(msb_first[..2], [1 << 7, 1 << 0]);
+
+        let lsb_first = modes(|m| m.lsb_first = true);
+        let pattern = unsafe { unpack_stipple(&lsb_first, data.as_ptr()) };
+        assert_eq!(pattern[..2], [1 << 0, 1 << 7]);
+
+        // Packing with different modes, and unpacking again, is lossless.
+        let mut packed = [0xaau8; 32 * 4];
+        let modes = modes(|m| {
+            m.lsb_first = true;
+            m.alignment = 1;
+        });
+        unsafe {
+            pack_stipple(&modes, &msb_first, packed.as_mut_ptr());
+            assert_eq!(unpack_stipple(&modes, packed.as_ptr()), msb_first);
+        }
+        assert_eq!(packed[..4], [0x80, 0, 0, 0]);
+    }
+}
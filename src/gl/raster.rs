@@ -0,0 +1,1130 @@
+use crate::gl::api_1_0::Enum;
+use crate::gl::fragment;
+use crate::gl::light::ShadeModel;
+use crate::gl::state::{Capability, FrontFace, HintMode, Scissor, State, MAX_CLIP_PLANES};
+use crate::gl::texture::{Target, Texture};
+use glam::{Vec2, Vec3, Vec4};
+use smallvec::SmallVec;
+
+/// Vertex after transformation, in clip coordinates.
+#[derive(Copy, Clone, Debug)]
+pub struct Vertex {
+    pub clip: Vec4,
+
+    /// Front and back colors (only differing with two-sided lighting).
+    pub colors: [Vec4; 2],
+
+    /// Position in eye coordinates, used for user clip planes and fog.
+    pub eye: Vec4,
+
+    /// Texture coordinates, after transformation by the texture matrix.
+    pub texcoord: Vec4,
+
+    /// Whether the edge starting at this vertex is a boundary edge of the
+    /// polygon it's part of (see `glEdgeFlag` and `assemble`).
+    pub edge_flag: bool,
+}
+
+impl Vertex {
+    fn lerp(&self, other: &Self, t: f32) -> Self {
+        Vertex {
+            clip: self.clip.lerp(other.clip, t),
+            colors: [
+                self.colors[0].lerp(other.colors[0], t),
+                self.colors[1].lerp(other.colors[1], t),
+            ],
+            eye: self.eye.lerp(other.eye, t),
+            texcoord: self.texcoord.lerp(other.texcoord, t),
+            edge_flag: self.edge_flag,
+        }
+    }
+
+    fn with_edge_flag(self, edge_flag: bool) -> Self {
+        Vertex { edge_flag, ..self }
+    }
+
+    /// Signed distances to the view volume's planes (`-w <= x, y, z <= w`),
+    /// followed by the user `clip_planes` (in eye coordinates), all of them
+    /// being non-negative for vertices inside the clip volume.
+    fn clip_distances(&self, clip_planes: &[Vec4]) -> SmallVec<[f32; 6 + MAX_CLIP_PLANES]> {
+        let (x, y, z, w) = (self.clip.x, self.clip.y, self.clip.z, self.clip.w);
+        let mut distances = SmallVec::from_slice(&[w + x, w - x, w + y, w - y, w + z, w - z]);
+        distances.extend(clip_planes.iter().map(|plane| plane.dot(self.eye)));
+        distances
+    }
+
+    pub fn is_inside_clip_volume(&self, clip_planes: &[Vec4]) -> bool {
+        self.clip_distances(clip_planes).iter().all(|&d| d >= 0.0)
+    }
+}
+
+#[derive(Copy, Clone, Debug)]
+pub enum Primitive {
+    Point(Vertex),
+    Line(Vertex, Vertex),
+    Triangle(Vertex, Vertex, Vertex),
+}
+
+impl Primitive {
+    /// Replace the colors of all vertices with those of the provoking vertex,
+    /// which is either the first or the last vertex.
+    fn flatten(&mut self, provoking_is_first: bool) {
+        match self {
+            Primitive::Point(_) => {}
+            Primitive::Line(a, b) => {
+                let colors = if provoking_is_first {
+                    a.colors
+                } else {
+                    b.colors
+                };
+                a.colors = colors;
+                b.colors = colors;
+            }
+            Primitive::Triangle(a, b, c) => {
+                let colors = if provoking_is_first {
+                    a.colors
+                } else {
+                    c.colors
+                };
+                a.colors = colors;
+                b.colors = colors;
+                c.colors = colors;
+            }
+        }
+    }
+}
+
+/// Whether `mode` is a valid primitive mode for `glBegin`.
+pub fn is_primitive_mode(mode: Enum) -> bool {
+    use Enum::*;
+    matches!(
+        mode,
+        POINTS
+            | LINES
+            | LINE_STRIP
+            | LINE_LOOP
+            | TRIANGLES
+            | TRIANGLE_STRIP
+            | TRIANGLE_FAN
+            | QUADS
+            | QUAD_STRIP
+            | POLYGON
+    )
+}
+
+/// Decompose the vertices between `glBegin(mode)` and `glEnd` into primitives.
+///
+/// Incomplete primitives (e.g. a trailing vertex for `GL_LINES`) are ignored.
+///
+/// With flat shading, all vertices of each primitive get the colors of its
+/// "provoking" vertex, which is its last vertex (or the first, for polygons).
+///
+/// The edge flags of triangles are only taken from the original vertices for
+/// independent triangles, quads and polygons, with the edges introduced by
+/// splitting quads and polygons into triangles never being boundary edges,
+/// while all the other edges of strips and fans are always boundary edges.
+pub fn assemble(
+    mode: Enum,
+    vertices: &[Vertex],
+    shade_model: ShadeModel,
+    mut f: impl FnMut(Primitive),
+) {
+    use {Enum::*, Primitive::*};
+    let mut f = |mut primitive: Primitive| {
+        if shade_model == ShadeModel::Flat {
+            primitive.flatten(matches!(mode, POLYGON));
+        }
+        f(primitive)
+    };
+    let boundary = |v: Vertex| v.with_edge_flag(true);
+    let interior = |v: Vertex| v.with_edge_flag(false);
+    match mode {
+        POINTS => vertices.iter().for_each(|&v| f(Point(v))),
+        LINES => vertices.chunks_exact(2).for_each(|l| f(Line(l[0], l[1]))),
+        LINE_STRIP | LINE_LOOP => {
+            vertices.windows(2).for_each(|l| f(Line(l[0], l[1])));
+            if let (LINE_LOOP, [first, .., last]) = (mode, vertices) {
+                f(Line(*last, *first));
+            }
+        }
+        TRIANGLES => vertices
+            .chunks_exact(3)
+            .for_each(|t| f(Triangle(t[0], t[1], t[2]))),
+        TRIANGLE_STRIP => {
+            let t = |a, b, c| Triangle(boundary(a), boundary(b), boundary(c));
+            for (i, v) in vertices.windows(3).enumerate() {
+                // NOTE(eddyb) every other triangle has its first two vertices
+                // swapped, so that all triangles have the same orientation.
+                if i % 2 == 0 {
+                    f(t(v[0], v[1], v[2]));
+                } else {
+                    f(t(v[1], v[0], v[2]));
+                }
+            }
+        }
+        TRIANGLE_FAN => {
+            if let [first, rest @ ..] = vertices {
+                rest.windows(2)
+                    .for_each(|v| f(Triangle(boundary(*first), boundary(v[0]), boundary(v[1]))));
+            }
+        }
+        POLYGON => {
+            if let [first, rest @ ..] = vertices {
+                let last = rest.len().saturating_sub(2);
+                for (i, v) in rest.windows(2).enumerate() {
+                    // NOTE(eddyb) only the first (and last) triangle contains
+                    // the first (and last) edge of the polygon, respectively.
+                    let first = if i == 0 { *first } else { interior(*first) };
+                    let end = if i == last { v[1] } else { interior(v[1]) };
+                    f(Triangle(first, v[0], end));
+                }
+            }
+        }
+        QUADS => vertices.chunks_exact(4).for_each(|q| {
+            // NOTE(eddyb) both triangles end in the last vertex, which is the
+            // provoking vertex of the quad (see flat shading above).
+            f(Triangle(q[0], interior(q[1]), q[3]));
+            f(Triangle(q[1], q[2], interior(q[3])));
+        }),
+        QUAD_STRIP => {
+            for q in vertices.windows(4).step_by(2) {
+                // NOTE(eddyb) the quad's vertices are `q[0], q[1], q[3], q[2]`,
+                // and both triangles end in the provoking vertex, `q[3]`.
+                f(Triangle(boundary(q[0]), boundary(q[1]), interior(q[3])));
+                f(Triangle(boundary(q[2]), interior(q[0]), boundary(q[3])));
+            }
+        }
+        _ => unreachable!("glOOF: invalid primitive mode {:?}", mode),
+    }
+}
+
+/// Vertex after perspective division and viewport transformation.
+#[derive(Copy, Clone, Debug)]
+struct WindowVertex {
+    pos: Vec3,
+    color: Vec4,
+    texcoord: Vec4,
+
+    /// Distance from the eye (in eye coordinates), used for fog.
+    distance: f32,
+}
+
+impl WindowVertex {
+    fn lerp(&self, other: &Self, t: f32) -> Self {
+        WindowVertex {
+            pos: self.pos.lerp(other.pos, t),
+            color: self.color.lerp(other.color, t),
+            texcoord: self.texcoord.lerp(other.texcoord, t),
+            distance: self.distance + (other.distance - self.distance) * t,
+        }
+    }
+}
+
+/// The `(s, t)` coordinates used to sample textures, i.e. `(s/q, t/q)`.
+fn texcoord_st(texcoord: Vec4) -> Vec2 {
+    Vec2::new(texcoord.x, texcoord.y) / texcoord.w
+}
+
+/// Number of fractional bits used for window coordinates during rasterization.
+pub const SUBPIXEL_BITS: u32 = 4;
+
+/// Number of bits per depth buffer value.
+pub const DEPTH_BITS: u32 = 16;
+
+/// Number of bits per stencil buffer value.
+pub const STENCIL_BITS: u32 = 8;
+
+#[derive(Debug)]
+pub struct Framebuffer {
+    pub width: u32,
+    pub height: u32,
+
+    /// RGBA color buffer, with rows stored bottom-to-top (like GL window
+    /// coordinates, which have their origin in the lower left corner).
+    pub color: Vec<[u8; 4]>,
+
+    /// Depth buffer, with the same layout as `color`, and each value being
+    /// a fixed-point fraction (with `DEPTH_BITS` bits) of the `[0, 1]` range.
+    pub depth: Vec<u16>,
+
+    /// Stencil buffer, with the same layout as `color`.
+    pub stencil: Vec<u8>,
+}
+
+pub fn color_to_rgba8(color: Vec4) -> [u8; 4] {
+    let c = color.max(Vec4::zero()).min(Vec4::one()) * 255.0;
+    [c.x, c.y, c.z, c.w].map(|c| c.round() as u8)
+}
+
+fn depth_to_fixed(depth: f64) -> u16 {
+    (depth.clamp(0.0, 1.0) * ((1 << DEPTH_BITS) - 1) as f64).round() as u16
+}
+
+impl Framebuffer {
+    pub fn new(width: u32, height: u32) -> Self {
+        Framebuffer {
+            width,
+            height,
+            color: vec![[0; 4]; (width * height) as usize],
+            depth: vec![depth_to_fixed(1.0); (width * height) as usize],
+            stencil: vec![0; (width * height) as usize],
+        }
+    }
+
+    /// Indices of the pixels inside `scissor` (or all of them, for `None`).
+    fn pixels(&self, scissor: Option<&Scissor>) -> impl Iterator<Item = usize> {
+        let range = |start: i32, len: u32, max: u32| {
+            let (start, end) = (start as i64, start as i64 + len as i64);
+            start.clamp(0, max as i64) as u32..end.clamp(0, max as i64) as u32
+        };
+        let (xs, ys) = match scissor {
+            Some(s) => (
+                range(s.x, s.width, self.width),
+                range(s.y, s.height, self.height),
+            ),
+            None => (0..self.width, 0..self.height),
+        };
+        let width = self.width;
+        ys.flat_map(move |y| xs.clone().map(move |x| (y * width + x) as usize))
+    }
+
+    /// Clear the components of the color buffer enabled in `writemask`, for
+    /// the pixels inside `scissor` (if any, see `Framebuffer::pixels`).
+    pub fn clear_color(&mut self, color: Vec4, writemask: [bool; 4], scissor: Option<&Scissor>) {
+        let color = color_to_rgba8(color);
+        for i in self.pixels(scissor) {
+            let old = self.color[i];
+            self.color[i] = [0, 1, 2, 3].map(|c| if writemask[c] { color[c] } else { old[c] });
+        }
+    }
+
+    pub fn clear_depth(&mut self, depth: f64, scissor: Option<&Scissor>) {
+        let depth = depth_to_fixed(depth);
+        for i in self.pixels(scissor) {
+            self.depth[i] = depth;
+        }
+    }
+
+    /// Clear the bits of the stencil buffer enabled in `writemask`, for the
+    /// pixels inside `scissor` (if any, see `Framebuffer::pixels`).
+    pub fn clear_stencil(&mut self, value: i32, writemask: u32, scissor: Option<&Scissor>) {
+        let max = (1u32 << STENCIL_BITS) - 1;
+        let (value, writemask) = ((value as u32 & max) as u8, writemask as u8);
+        for i in self.pixels(scissor) {
+            self.stencil[i] = (self.stencil[i] & !writemask) | (value & writemask);
+        }
+    }
+}
+
+/// Rasterization (and per-fragment operations) of primitives into a
+/// `Framebuffer`, according to the GL `State`.
+pub struct Rasterizer<'a> {
+    pub state: &'a State,
+    pub framebuffer: &'a mut Framebuffer,
+
+    /// The texture applied to fragments, if any (see `State::enabled_texture`).
+    pub texture: Option<(Target, &'a Texture)>,
+
+    /// Number of fragments produced so far for the current line strip (or
+    /// loop), indexing into the line stipple pattern (see `State::line`),
+    /// which has to be reset before each independent line (see `glEnd`).
+    pub line_stipple_counter: u32,
+}
+
+impl Rasterizer<'_> {
+    /// Apply the enabled texture (if any) to a fragment's `color`, where `st`
+    /// computes the fragment's `(s, t)` coordinates, and their rates of change
+    /// along the window `x` and `y` axes (see `Texture::lod`).
+    fn apply_texture(&self, color: Vec4, st: impl FnOnce() -> (Vec2, Vec2, Vec2)) -> Vec4 {
+        match self.texture {
+            Some((target, texture)) => {
+                let (st, d_dx, d_dy) = st();
+                let lambda = texture.lod(target, d_dx, d_dy);
+                let texel = texture.sample(target, st, lambda);
+                let format = texture.level(0).base_internal_format();
+                self.state.texturing.env.apply(format, color, texel)
+            }
+            None => color,
+        }
+    }
+
+    /// Apply fog (if enabled) to a fragment's `color`, given its `distance`
+    /// from the eye (see `Fog::apply`).
+    fn apply_fog(&self, color: Vec4, distance: f32) -> Vec4 {
+        if self.state.caps.contains(Capability::Fog) {
+            self.state.fog.apply(color, distance)
+        } else {
+            color
+        }
+    }
+
+    /// Apply the per-fragment operations (in order: the scissor test, alpha
+    /// test, stencil test, depth test, blending, dithering and logical
+    /// operations) to a fragment, and write the result to the framebuffer
+    /// (only updating the color components enabled by `glColorMask`).
+    fn fragment(&mut self, x: i32, y: i32, z: f32, color: Vec4) {
+        let fb = &mut *self.framebuffer;
+        if x < 0 || y < 0 || x as u32 >= fb.width || y as u32 >= fb.height {
+            return;
+        }
+        let i = (y as u32 * fb.width + x as u32) as usize;
+
+        let caps = &self.state.caps;
+        if caps.contains(Capability::ScissorTest) && !self.state.scissor.contains(x, y) {
+            return;
+        }
+
+        let color = color.max(Vec4::zero()).min(Vec4::one());
+        let color_buffer = &self.state.color_buffer;
+        let (alpha_func, alpha_ref) = color_buffer.alpha_func;
+        if caps.contains(Capability::AlphaTest) && !alpha_func.compare(color.w, alpha_ref) {
+            return;
+        }
+
+        // NOTE(eddyb) the stencil buffer is only updated while the stencil
+        // test is enabled (even if fragments pass it unconditionally).
+        let stencil = &self.state.stencil;
+        let stencil_test = caps.contains(Capability::StencilTest);
+        if stencil_test && !stencil.test(fb.stencil[i]) {
+            stencil.update(&mut fb.stencil[i], stencil.fail);
+            return;
+        }
+
+        let depth = &self.state.depth;
+        let z = depth_to_fixed(z as f64);
+        let depth_pass =
+            !caps.contains(Capability::DepthTest) || depth.func.compare(z, fb.depth[i]);
+        if stencil_test {
+            let op = if depth_pass {
+                stencil.depth_pass
+            } else {
+                stencil.depth_fail
+            };
+            stencil.update(&mut fb.stencil[i], op);
+        }
+        if !depth_pass {
+            return;
+        }
+        if caps.contains(Capability::DepthTest) && depth.writemask {
+            fb.depth[i] = z;
+        }
+
+        let dst = fb.color[i];
+
+        // NOTE(eddyb) logical operations take precedence over blending.
+        let logic_op = caps.contains(Capability::ColorLogicOp);
+        let color = if caps.contains(Capability::Blend) && !logic_op {
+            let [r, g, b, a] = dst;
+            let dst = Vec4::new(r as f32, g as f32, b as f32, a as f32) / 255.0;
+            fragment::blend(color_buffer.blend_func, color, dst)
+        } else {
+            color
+        };
+
+        let rgba = if caps.contains(Capability::Dither) {
+            fragment::dither(color, x, y)
+        } else {
+            color_to_rgba8(color)
+        };
+        let component = |c: usize| match (color_buffer.color_writemask[c], logic_op) {
+            (false, _) => dst[c],
+            (true, false) => rgba[c],
+            (true, true) => color_buffer.logic_op.apply(rgba[c], dst[c]),
+        };
+        fb.color[i] = [0, 1, 2, 3].map(component);
+    }
+
+    /// Transform `v` to window coordinates, using either its front (`face = 0`)
+    /// or back (`face = 1`) color.
+    fn to_window(&self, v: &Vertex, face: usize) -> Option<WindowVertex> {
+        if v.clip.w == 0.0 {
+            return None;
+        }
+
+        // NOTE(eddyb) dividing all the texture coordinates (including `q`) by
+        // `w` makes interpolating them linearly in window coordinates correct
+        // with respect to perspective, without affecting `(s/q, t/q)` itself.
+        let texcoord = if self.state.hints.perspective_correction == HintMode::Fastest {
+            v.texcoord
+        } else {
+            v.texcoord / v.clip.w
+        };
+
+        Some(WindowVertex {
+            pos: self.state.viewport.to_window(v.clip.truncate() / v.clip.w),
+            color: v.colors[face],
+            texcoord,
+            // NOTE(eddyb) this distance is then interpolated linearly in window
+            // coordinates, which the spec allows as an approximation.
+            distance: v.eye.truncate().length(),
+        })
+    }
+
+    pub fn draw(&mut self, primitive: Primitive) {
+        let clip_planes = self.state.enabled_clip_planes();
+        match primitive {
+            Primitive::Point(v) => {
+                if !v.is_inside_clip_volume(&clip_planes) {
+                    return;
+                }
+                if let Some(v) = self.to_window(&v, 0) {
+                    let (x, y) = (v.pos.x.floor() as i32, v.pos.y.floor() as i32);
+                    let color = self.apply_texture(v.color, || {
+                        (texcoord_st(v.texcoord), Vec2::zero(), Vec2::zero())
+                    });
+                    let color = self.apply_fog(color, v.distance);
+                    self.fragment(x, y, v.pos.z, color);
+                }
+            }
+            Primitive::Line(a, b) => {
+                if let Some((a, b)) = clip_line(a, b, &clip_planes) {
+                    if let (Some(a), Some(b)) = (self.to_window(&a, 0), self.to_window(&b, 0)) {
+                        self.draw_line(a, b);
+                    }
+                }
+            }
+            Primitive::Triangle(a, b, c) => {
+                let polygon = clip_polygon([a, b, c].iter().copied().collect(), &clip_planes);
+
+                let polygon_state = &self.state.polygon;
+                let front_facing = self.is_counter_clockwise(&polygon)
+                    == (polygon_state.front_face == FrontFace::Ccw);
+                let face = if front_facing { 0 } else { 1 };
+                if self.state.caps.contains(Capability::CullFace)
+                    && polygon_state.cull_face_mode.indices().contains(&face)
+                {
+                    return;
+                }
+                // FIXME(eddyb) support `glPolygonMode`, which would draw only
+                // the boundary edges (see `Vertex::edge_flag`), or vertices.
+
+                let polygon = polygon
+                    .iter()
+                    .map(|v| self.to_window(v, face))
+                    .collect::<Option<SmallVec<[_; 9]>>>();
+                if let Some([first, rest @ ..]) = polygon.as_deref() {
+                    for t in rest.windows(2) {
+                        self.fill_triangle([*first, t[0], t[1]]);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Whether `polygon` (in clip coordinates) has its vertices in
+    /// counter-clockwise order, once projected into window coordinates.
+    fn is_counter_clockwise(&self, polygon: &[Vertex]) -> bool {
+        let ndc = |v: &Vertex| v.clip.truncate() / v.clip.w;
+        let area: f32 = (0..polygon.len())
+            .map(|i| {
+                let (a, b) = (ndc(&polygon[i]), ndc(&polygon[(i + 1) % polygon.len()]));
+                a.x * b.y - b.x * a.y
+            })
+            .sum();
+        area >= 0.0
+    }
+
+    fn draw_line(&mut self, a: WindowVertex, b: WindowVertex) {
+        let d = b.pos - a.pos;
+        let x_major = d.x.abs() >= d.y.abs();
+        let (major_start, major_len) = if x_major {
+            (a.pos.x, d.x)
+        } else {
+            (a.pos.y, d.y)
+        };
+        if major_len == 0.0 {
+            return;
+        }
+
+        // NOTE(eddyb) textures are sampled based on the rate of change of the
+        // texture coordinates along the line (i.e. per unit of its length).
+        let st = |t: f32| texcoord_st(a.texcoord.lerp(b.texcoord, t));
+        let dt = 1.0 / Vec2::new(d.x, d.y).length();
+
+        let (lo, hi) = (
+            major_start.min(major_start + major_len),
+            major_start.max(major_start + major_len),
+        );
+        for i in (lo - 0.5).floor() as i32..=(hi - 0.5).ceil() as i32 {
+            // NOTE(eddyb) only pixel centers in `[0, 1)` along the line are
+            // drawn, so that connected segments don't share any fragments.
+            let t = (i as f32 + 0.5 - major_start) / major_len;
+            if !(0.0..1.0).contains(&t) {
+                continue;
+            }
+
+            let line = &self.state.line;
+            let stipple_bit = (self.line_stipple_counter / line.stipple_repeat) % 16;
+            self.line_stipple_counter = self.line_stipple_counter.wrapping_add(1);
+            if self.state.caps.contains(Capability::LineStipple)
+                && line.stipple_pattern & (1 << stipple_bit) == 0
+            {
+                continue;
+            }
+
+            let v = a.lerp(&b, t);
+            let minor = if x_major { v.pos.y } else { v.pos.x }.floor() as i32;
+            let (x, y) = if x_major { (i, minor) } else { (minor, i) };
+            let color = self.apply_texture(v.color, || (st(t), st(t + dt) - st(t), Vec2::zero()));
+            let color = self.apply_fog(color, v.distance);
+            self.fragment(x, y, v.pos.z, color);
+        }
+    }
+
+    fn fill_triangle(&mut self, mut v: [WindowVertex; 3]) {
+        const ONE: i64 = 1 << SUBPIXEL_BITS;
+
+        let snap = |v: &WindowVertex| {
+            (
+                (v.pos.x * ONE as f32).round() as i64,
+                (v.pos.y * ONE as f32).round() as i64,
+            )
+        };
+        let mut p = [snap(&v[0]), snap(&v[1]), snap(&v[2])];
+
+        // Twice the signed area of the triangle `a b c`, positive if its
+        // vertices are in counter-clockwise order, and also used as an edge
+        // function (for the edge `a b`), when `c` is an arbitrary point.
+        let edge = |a: (i64, i64), b: (i64, i64), c: (i64, i64)| {
+            (b.0 - a.0) * (c.1 - a.1) - (b.1 - a.1) * (c.0 - a.0)
+        };
+
+        let mut area = edge(p[0], p[1], p[2]);
+        if area == 0 {
+            return;
+        }
+        if area < 0 {
+            p.swap(1, 2);
+            v.swap(1, 2);
+            area = -area;
+        }
+
+        // Fragments with their center exactly on an edge are only produced for
+        // "top" and "left" edges, so that adjacent triangles don't overlap.
+        // With the counter-clockwise order, the interior is to the left of each
+        // edge, so left edges go down, and top edges go right-to-left.
+        let bias = |a: (i64, i64), b: (i64, i64)| {
+            let (dx, dy) = (b.0 - a.0, b.1 - a.1);
+            if dy < 0 || (dy == 0 && dx < 0) {
+                0
+            } else {
+                -1
+            }
+        };
+        let edges = [(p[1], p[2]), (p[2], p[0]), (p[0], p[1])];
+        let biases = [bias(p[1], p[2]), bias(p[2], p[0]), bias(p[0], p[1])];
+
+        // Rates of change of the barycentric coordinates (see below), along
+        // the window `x` and `y` axes (i.e. per pixel), used for texturing.
+        let d_dx = edges.map(|(a, b)| ((a.1 - b.1) * ONE) as f32 / area as f32);
+        let d_dy = edges.map(|(a, b)| ((b.0 - a.0) * ONE) as f32 / area as f32);
+        let (d_dx, d_dy) = (Vec3::from(d_dx), Vec3::from(d_dy));
+        let st =
+            |b: Vec3| texcoord_st(v[0].texcoord * b.x + v[1].texcoord * b.y + v[2].texcoord * b.z);
+
+        let min = |f: fn(&(i64, i64)) -> i64| p.iter().map(f).min().unwrap();
+        let max = |f: fn(&(i64, i64)) -> i64| p.iter().map(f).max().unwrap();
+        let x_range = (min(|p| p.0) >> SUBPIXEL_BITS).max(0)
+            ..((max(|p| p.0) + ONE - 1) >> SUBPIXEL_BITS).min(self.framebuffer.width as i64);
+        let y_range = (min(|p| p.1) >> SUBPIXEL_BITS).max(0)
+            ..((max(|p| p.1) + ONE - 1) >> SUBPIXEL_BITS).min(self.framebuffer.height as i64);
+
+        let stipple = self.state.caps.contains(Capability::PolygonStipple);
+        for y in y_range {
+            for x in x_range.clone() {
+                let stipple_row = self.state.polygon_stipple.pattern[y as usize % 32];
+                if stipple && stipple_row & (1 << (x % 32)) == 0 {
+                    continue;
+                }
+
+                let center = (
+                    (x << SUBPIXEL_BITS) + ONE / 2,
+                    (y << SUBPIXEL_BITS) + ONE / 2,
+                );
+                let w = [
+                    edge(edges[0].0, edges[0].1, center),
+                    edge(edges[1].0, edges[1].1, center),
+                    edge(edges[2].0, edges[2].1, center),
+                ];
+                if (0..3).any(|i| w[i] + biases[i] < 0) {
+                    continue;
+                }
+
+                // Barycentric coordinates, used to interpolate vertex attributes.
+                let [b0, b1, b2] = w.map(|w| w as f32 / area as f32);
+                let z = v[0].pos.z * b0 + v[1].pos.z * b1 + v[2].pos.z * b2;
+                let color = v[0].color * b0 + v[1].color * b1 + v[2].color * b2;
+                let color = self.apply_texture(color, || {
+                    let b = Vec3::new(b0, b1, b2);
+                    (st(b), st(b + d_dx) - st(b), st(b + d_dy) - st(b))
+                });
+                let distance = v[0].distance * b0 + v[1].distance * b1 + v[2].distance * b2;
+                let color = self.apply_fog(color, distance);
+                self.fragment(x as i32, y as i32, z, color);
+            }
+        }
+    }
+}
+
+/// Clip a line against the view volume and the user `clip_planes` (see
+/// `Vertex::clip_distances`), returning `None` if it's fully outside.
+fn clip_line(a: Vertex, b: Vertex, clip_planes: &[Vec4]) -> Option<(Vertex, Vertex)> {
+    let (da, db) = (a.clip_distances(clip_planes), b.clip_distances(clip_planes));
+    let (mut t0, mut t1) = (0.0f32, 1.0f32);
+    for (&da, &db) in da.iter().zip(&db) {
+        if da < 0.0 && db < 0.0 {
+            return None;
+        }
+        if da < 0.0 {
+            t0 = t0.max(da / (da - db));
+        } else if db < 0.0 {
+            t1 = t1.min(da / (da - db));
+        }
+    }
+    if t0 > t1 {
+        return None;
+    }
+    Some((a.lerp(&b, t0), a.lerp(&b, t1)))
+}
+
+/// Clip a convex polygon against the view volume and the user `clip_planes`
+/// (see `Vertex::clip_distances`), using the Sutherland-Hodgman algorithm.
+fn clip_polygon(mut polygon: SmallVec<[Vertex; 9]>, clip_planes: &[Vec4]) -> SmallVec<[Vertex; 9]> {
+    if polygon.iter().all(|v| v.is_inside_clip_volume(clip_planes)) {
+        return polygon;
+    }
+    for plane in 0..6 + clip_planes.len() {
+        let mut clipped = SmallVec::new();
+        for (i, a) in polygon.iter().enumerate() {
+            let b = &polygon[(i + 1) % polygon.len()];
+            let (da, db) = (
+                a.clip_distances(clip_planes)[plane],
+                b.clip_distances(clip_planes)[plane],
+            );
+            if da >= 0.0 {
+                clipped.push(*a);
+            }
+            if (da >= 0.0) != (db >= 0.0) {
+                clipped.push(a.lerp(b, da / (da - db)));
+            }
+        }
+        polygon = clipped;
+    }
+    polygon
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gl::light::Face;
+    use glam::DVec4;
+
+    fn vertex(x: f32, y: f32) -> Vertex {
+        vertex_at_depth(x, y, 0.0)
+    }
+
+    fn vertex_at_depth(x: f32, y: f32, z: f32) -> Vertex {
+        Vertex {
+            clip: Vec4::new(x, y, z, 1.0),
+            colors: [Vec4::one(); 2],
+            eye: Vec4::new(x, y, z, 1.0),
+            texcoord: Vec4::unit_w(),
+            edge_flag: true,
+        }
+    }
+
+    /// Default `State`, with the viewport covering all of `framebuffer`.
+    fn state_for(framebuffer: &Framebuffer) -> State {
+        let mut state = State::default();
+        state.viewport.width = framebuffer.width;
+        state.viewport.height = framebuffer.height;
+        state
+    }
+
+    fn draw(state: &State, framebuffer: &mut Framebuffer, primitive: Primitive) {
+        Rasterizer {
+            state,
+            texture: None,
+            framebuffer,
+            line_stipple_counter: 0,
+        }
+        .draw(primitive);
+    }
+
+    fn count_primitives(mode: Enum, n: usize) -> usize {
+        let vertices: Vec<_> = (0..n).map(|i| vertex(i as f32, 0.0)).collect();
+        let mut count = 0;
+        assemble(mode, &vertices, ShadeModel::Smooth, |_| count += 1);
+        count
+    }
+
+    /// Count the boundary edges of the triangles `mode` is assembled into.
+    fn count_boundary_edges(mode: Enum, n: usize) -> usize {
+        let vertices: Vec<_> = (0..n).map(|i| vertex(i as f32, 0.0)).collect();
+        let mut count = 0;
+        assemble(mode, &vertices, ShadeModel::Smooth, |primitive| {
+            if let Primitive::Triangle(a, b, c) = primitive {
+                count += [a, b, c].iter().filter(|v| v.edge_flag).count();
+            }
+        });
+        count
+    }
+
+    fn covered(fb: &Framebuffer) -> usize {
+        fb.color.iter().filter(|&&c| c != [0; 4]).count()
+    }
+
+    #[test]
+    fn only_polygon_boundaries_are_boundary_edges() {
+        use Enum::*;
+        assert_eq!(count_boundary_edges(TRIANGLES, 6), 6);
+        assert_eq!(count_boundary_edges(QUADS, 8), 8);
+        assert_eq!(count_boundary_edges(POLYGON, 3), 3);
+        assert_eq!(count_boundary_edges(POLYGON, 6), 6);
+        assert_eq!(count_boundary_edges(TRIANGLE_FAN, 5), 9);
+        assert_eq!(count_boundary_edges(QUAD_STRIP, 6), 8);
+    }
+
+    #[test]
+    fn incomplete_primitives_are_ignored() {
+        use Enum::*;
+        assert_eq!(count_primitives(POINTS, 3), 3);
+        assert_eq!(count_primitives(LINES, 3), 1);
+        assert_eq!(count_primitives(LINE_STRIP, 1), 0);
+        assert_eq!(count_primitives(LINE_LOOP, 3), 3);
+        assert_eq!(count_primitives(TRIANGLES, 5), 1);
+        assert_eq!(count_primitives(TRIANGLE_STRIP, 5), 3);
+        assert_eq!(count_primitives(TRIANGLE_FAN, 2), 0);
+        assert_eq!(count_primitives(QUADS, 7), 2);
+        assert_eq!(count_primitives(QUAD_STRIP, 7), 4);
+        assert_eq!(count_primitives(POLYGON, 5), 3);
+    }
+
+    #[test]
+    fn flat_shading_uses_the_provoking_vertex() {
+        let vertices: Vec<_> = (0..4)
+            .map(|i| Vertex {
+                colors: [Vec4::splat(i as f32), -Vec4::splat(i as f32)],
+                ..vertex(i as f32, 0.0)
+            })
+            .collect();
+        let colors = |mode| {
+            let mut colors = vec![];
+            assemble(mode, &vertices, ShadeModel::Flat, |primitive| {
+                if let Primitive::Triangle(a, b, c) = primitive {
+                    assert_eq!([a.colors, b.colors], [c.colors; 2]);
+                    colors.push(a.colors[0].x);
+                }
+            });
+            colors
+        };
+        assert_eq!(colors(Enum::TRIANGLE_STRIP), [2.0, 3.0]);
+        assert_eq!(colors(Enum::TRIANGLE_FAN), [2.0, 3.0]);
+        assert_eq!(colors(Enum::QUADS), [3.0, 3.0]);
+        assert_eq!(colors(Enum::POLYGON), [0.0, 0.0]);
+    }
+
+    #[test]
+    fn adjacent_triangles_neither_overlap_nor_leave_gaps() {
+        let corners = [
+            vertex(-1.0, -1.0),
+            vertex(1.0, -1.0),
+            vertex(1.0, 1.0),
+            vertex(-1.0, 1.0),
+        ];
+        let triangles = [
+            Primitive::Triangle(corners[0], corners[1], corners[2]),
+            Primitive::Triangle(corners[0], corners[2], corners[3]),
+        ];
+
+        let mut counts = vec![];
+        for &triangle in &triangles {
+            let mut fb = Framebuffer::new(8, 8);
+            draw(&state_for(&fb), &mut fb, triangle);
+            counts.push(covered(&fb));
+        }
+        assert_eq!(counts[0] + counts[1], 8 * 8);
+
+        let mut fb = Framebuffer::new(8, 8);
+        triangles
+            .iter()
+            .for_each(|&t| draw(&state_for(&fb), &mut fb, t));
+        assert_eq!(covered(&fb), 8 * 8);
+    }
+
+    #[test]
+    fn triangles_are_clipped() {
+        // Only the part inside the view volume, the lower left half of
+        // the window, is drawn (the diagonal itself is a right edge).
+        let mut fb = Framebuffer::new(8, 8);
+        draw(
+            &state_for(&fb),
+            &mut fb,
+            Primitive::Triangle(vertex(-3.0, -1.0), vertex(1.0, -1.0), vertex(-3.0, 3.0)),
+        );
+        assert_eq!(covered(&fb), 8 * 7 / 2);
+    }
+
+    #[test]
+    fn connected_lines_share_no_fragments() {
+        let mut fb = Framebuffer::new(8, 8);
+        let (a, b, c) = (vertex(-1.0, 0.1), vertex(0.0, 0.1), vertex(1.0, 0.1));
+        draw(&state_for(&fb), &mut fb, Primitive::Line(a, b));
+        assert_eq!(covered(&fb), 4);
+        draw(&state_for(&fb), &mut fb, Primitive::Line(b, c));
+        assert_eq!(covered(&fb), 8);
+    }
+
+    #[test]
+    fn user_clip_planes() {
+        let mut fb = Framebuffer::new(8, 8);
+        let mut state = state_for(&fb);
+        state.transform.clip_planes[2] = DVec4::new(1.0, 0.0, 0.0, 0.0);
+        state.transform.clip_planes[3] = DVec4::new(0.0, -1.0, 0.0, 0.0);
+        state.caps.set(Capability::ClipPlane2, true);
+        state.caps.set(Capability::ClipPlane3, true);
+        let (a, b, c) = (vertex(-1.0, -1.0), vertex(1.0, -1.0), vertex(1.0, 1.0));
+        draw(&state, &mut fb, Primitive::Triangle(a, b, c));
+
+        // Only the lower right quadrant of the triangle remains.
+        for (i, &c) in fb.color.iter().enumerate() {
+            let (x, y) = (i % 8, i / 8);
+            assert_eq!(c != [0; 4], x >= 4 && y < 4 && y < x, "({}, {})", x, y);
+        }
+
+        // Lines and points are clipped as well.
+        let mut fb = Framebuffer::new(8, 8);
+        draw(
+            &state,
+            &mut fb,
+            Primitive::Line(vertex(-1.0, -0.9), vertex(1.0, -0.9)),
+        );
+        assert_eq!(covered(&fb), 4);
+        draw(&state, &mut fb, Primitive::Point(vertex(-0.5, -0.5)));
+        assert_eq!(covered(&fb), 4);
+    }
+
+    #[test]
+    fn line_stipple() {
+        let (a, b, c) = (vertex(-1.0, 0.1), vertex(0.0, 0.1), vertex(1.0, 0.1));
+        let drawn = |state: &State, lines: &[Primitive]| {
+            let mut fb = Framebuffer::new(8, 8);
+            let mut rasterizer = Rasterizer {
+                state,
+                texture: None,
+                framebuffer: &mut fb,
+                line_stipple_counter: 0,
+            };
+            lines.iter().for_each(|&line| rasterizer.draw(line));
+            (0..8)
+                .filter(|&x| fb.color[4 * 8 + x] != [0; 4])
+                .collect::<Vec<_>>()
+        };
+
+        let fb = Framebuffer::new(8, 8);
+        let mut state = state_for(&fb);
+        state.line.stipple_pattern = 0b1101;
+        state.line.stipple_repeat = 2;
+        assert_eq!(
+            drawn(&state, &[Primitive::Line(a, c)]),
+            (0..8).collect::<Vec<_>>()
+        );
+
+        state.caps.set(Capability::LineStipple, true);
+        assert_eq!(drawn(&state, &[Primitive::Line(a, c)]), [0, 1, 4, 5, 6, 7]);
+
+        // The pattern continues across connected segments.
+        state.line.stipple_pattern = 0b111;
+        state.line.stipple_repeat = 1;
+        let strip = [Primitive::Line(a, b), Primitive::Line(b, c)];
+        assert_eq!(drawn(&state, &strip), [0, 1, 2]);
+    }
+
+    #[test]
+    fn polygon_stipple() {
+        let mut fb = Framebuffer::new(4, 4);
+        let mut state = state_for(&fb);
+        for (y, row) in state.polygon_stipple.pattern.iter_mut().enumerate() {
+            *row = if y % 2 == 0 { 0x5555_5555 } else { 0xaaaa_aaaa };
+        }
+        state.caps.set(Capability::PolygonStipple, true);
+        let corners = [(-1.0, -1.0), (1.0, -1.0), (1.0, 1.0), (-1.0, 1.0)];
+        let [a, b, c, d] = corners.map(|(x, y)| vertex(x, y));
+        draw(&state, &mut fb, Primitive::Triangle(a, b, c));
+        draw(&state, &mut fb, Primitive::Triangle(a, c, d));
+
+        for (i, &c) in fb.color.iter().enumerate() {
+            let (x, y) = (i % 4, i / 4);
+            assert_eq!(c != [0; 4], (x + y) % 2 == 0, "({}, {})", x, y);
+        }
+    }
+
+    #[test]
+    fn fog() {
+        use crate::gl::state::FogMode;
+
+        let fogged_color = |state: &State| {
+            let mut fb = Framebuffer::new(1, 1);
+            let v = Vertex {
+                eye: Vec4::new(0.0, 0.0, -1.0, 1.0),
+                ..vertex(0.0, 0.0)
+            };
+            draw(state, &mut fb, Primitive::Point(v));
+            fb.color[0]
+        };
+
+        let mut state = state_for(&Framebuffer::new(1, 1));
+        state.caps.set(Capability::Dither, false);
+        state.fog.color = Vec4::new(0.0, 0.0, 1.0, 0.0);
+        assert_eq!(fogged_color(&state), [255; 4]);
+
+        // Only the RGB components are blended with the fog color.
+        state.caps.set(Capability::Fog, true);
+        assert_eq!(fogged_color(&state), [94, 94, 255, 255]);
+
+        state.fog.mode = FogMode::Linear;
+        state.fog.end = 4.0;
+        assert_eq!(fogged_color(&state), [191, 191, 255, 255]);
+    }
+
+    #[test]
+    fn depth_test_and_writemask() {
+        let draw_full_window = |state: &State, fb: &mut Framebuffer, z| {
+            let corners = [(-1.0, -1.0), (1.0, -1.0), (1.0, 1.0), (-1.0, 1.0)];
+            let [a, b, c, d] = corners.map(|(x, y)| vertex_at_depth(x, y, z));
+            draw(state, fb, Primitive::Triangle(a, b, c));
+            draw(state, fb, Primitive::Triangle(a, c, d));
+        };
+        let mut fb = Framebuffer::new(4, 4);
+        let mut state = state_for(&fb);
+
+        // With the depth test disabled, the depth buffer isn't written.
+        draw_full_window(&state, &mut fb, 0.0);
+        assert!(fb.depth.iter().all(|&d| d == u16::MAX));
+
+        state.caps.set(Capability::DepthTest, true);
+        draw_full_window(&state, &mut fb, 0.0);
+        assert!(fb.depth.iter().all(|&d| d == depth_to_fixed(0.5)));
+
+        // Farther fragments fail the default `GL_LESS` comparison.
+        fb.clear_color(Vec4::zero(), [true; 4], None);
+        draw_full_window(&state, &mut fb, 0.5);
+        assert_eq!(covered(&fb), 0);
+
+        // Nearer fragments pass, but only update depth if it's writable.
+        state.depth.writemask = false;
+        draw_full_window(&state, &mut fb, -0.5);
+        assert_eq!(covered(&fb), 4 * 4);
+        assert!(fb.depth.iter().all(|&d| d == depth_to_fixed(0.5)));
+    }
+
+    #[test]
+    fn stencil_test_and_ops() {
+        use crate::gl::fragment::StencilOp;
+        use crate::gl::state::CompareFunc;
+
+        let draw_full_window = |state: &State, fb: &mut Framebuffer, z| {
+            let corners = [(-1.0, -1.0), (1.0, -1.0), (1.0, 1.0), (-1.0, 1.0)];
+            let [a, b, c, d] = corners.map(|(x, y)| vertex_at_depth(x, y, z));
+            draw(state, fb, Primitive::Triangle(a, b, c));
+            draw(state, fb, Primitive::Triangle(a, c, d));
+        };
+        let mut fb = Framebuffer::new(4, 4);
+        let mut state = state_for(&fb);
+
+        // With the stencil test disabled, the stencil buffer isn't written.
+        state.stencil.depth_pass = StencilOp::Replace;
+        state.stencil.reference = 1;
+        draw_full_window(&state, &mut fb, 0.0);
+        assert!(fb.stencil.iter().all(|&s| s == 0));
+
+        // Mark the lower half of the window, then only draw outside of it.
+        state.caps.set(Capability::StencilTest, true);
+        state.scissor = Scissor {
+            x: 0,
+            y: 0,
+            width: 4,
+            height: 2,
+        };
+        state.caps.set(Capability::ScissorTest, true);
+        draw_full_window(&state, &mut fb, 0.0);
+        state.caps.set(Capability::ScissorTest, false);
+        assert_eq!(fb.stencil, [[1; 8], [0; 8]].concat());
+
+        fb.clear_color(Vec4::zero(), [true; 4], None);
+        state.stencil.func = CompareFunc::NotEqual;
+        state.stencil.fail = StencilOp::Incr;
+        state.stencil.depth_pass = StencilOp::Invert;
+        draw_full_window(&state, &mut fb, 0.0);
+        assert_eq!(covered(&fb), 4 * 2);
+        assert!(fb.color[..8].iter().all(|&c| c == [0; 4]));
+        assert_eq!(fb.stencil, [[2; 8], [!0; 8]].concat());
+
+        // The depth test failing uses the separate `depth_fail` op, and only
+        // the bits enabled by the writemask are ever updated.
+        state.caps.set(Capability::DepthTest, true);
+        fb.clear_depth(0.0, None);
+        state.stencil.func = CompareFunc::Always;
+        state.stencil.depth_fail = StencilOp::Zero;
+        state.stencil.writemask = 0xf0;
+        draw_full_window(&state, &mut fb, 0.0);
+        assert_eq!(fb.stencil, [[2; 8], [0x0f; 8]].concat());
+    }
+
+    #[test]
+    fn viewport_transform() {
+        let mut fb = Framebuffer::new(8, 8);
+        let mut state = state_for(&fb);
+        state.viewport.x = 2;
+        state.viewport.y = 4;
+        state.viewport.width = 4;
+        state.viewport.height = 8;
+        draw(
+            &state,
+            &mut fb,
+            Primitive::Triangle(vertex(-1.0, -1.0), vertex(1.0, -1.0), vertex(1.0, 1.0)),
+        );
+        draw(
+            &state,
+            &mut fb,
+            Primitive::Triangle(vertex(-1.0, -1.0), vertex(1.0, 1.0), vertex(-1.0, 1.0)),
+        );
+
+        // Only the lower half of the viewport is inside the window.
+        for (i, &c) in fb.color.iter().enumerate() {
+            let (x, y) = (i % 8, i / 8);
+            assert_eq!(c != [0; 4], (2..6).contains(&x) && y >= 4, "({}, {})", x, y);
+        }
+    }
+
+    #[test]
+    fn face_culling() {
+        let (a, b, c) = (vertex(-1.0, -1.0), vertex(1.0, -1.0), vertex(-1.0, 1.0));
+        let (ccw, cw) = (Primitive::Triangle(a, b, c), Primitive::Triangle(a, c, b));
+        let covered_by = |state: &State, triangle| {
+            let mut fb = Framebuffer::new(8, 8);
+            draw(state, &mut fb, triangle);
+            covered(&fb)
+        };
+
+        let mut fb = Framebuffer::new(8, 8);
+        let mut state = state_for(&fb);
+        assert_eq!(covered_by(&state, cw), covered_by(&state, ccw));
+        state.caps.set(Capability::CullFace, true);
+        assert_eq!(covered_by(&state, cw), 0);
+        assert_ne!(covered_by(&state, ccw), 0);
+
+        state.polygon.front_face = FrontFace::Cw;
+        assert_ne!(covered_by(&state, cw), 0);
+        assert_eq!(covered_by(&state, ccw), 0);
+
+        state.polygon.cull_face_mode = Face::FrontAndBack;
+        assert_eq!(covered_by(&state, cw), 0);
+        assert_eq!(covered_by(&state, ccw), 0);
+
+        // Lines and points are never culled.
+        draw(&state, &mut fb, Primitive::Line(a, b));
+        draw(&state, &mut fb, Primitive::Point(vertex(0.0, 0.0)));
+        assert_ne!(covered(&fb), 0);
+    }
+}
@@ -1,27 +1,1102 @@
-use crate::gl::api_1_0::{Command, Enum};
-use glam::{DMat4, DVec3, DVec4, Mat4, Vec3};
+use crate::gl::api_1_0::{self, Command, Enum};
+use crate::gl::fragment::{BlendFactor, LogicOp, StencilOp};
+use crate::gl::light::{self, Face, Lighting, ShadeModel};
+use crate::gl::texture::{self, Image, Target, Texture};
+use crate::gl::{list, raster, tex_gen};
+use crate::gl::{DisplayList, Error, ShareGroup};
+use glam::{DMat4, DVec3, DVec4, Mat4, Vec3, Vec4};
+use smallvec::SmallVec;
+use std::convert::TryFrom;
 use std::f32::consts::PI;
 
+/// Maximum depth of the attribute stack (see `glPushAttrib`).
+pub const MAX_ATTRIB_STACK_DEPTH: usize = 16;
+
+/// Maximum number of display lists being executed at the same time, i.e. the
+/// nesting depth of `glCallList`/`glCallLists` (any deeper calls are ignored).
+pub const MAX_LIST_NESTING: usize = 64;
+
+macro_rules! attrib_groups {
+    ($($group:ident: $ty:ty = $bit:ident),* $(,)?) => {
+        /// Attribute groups saved by `glPushAttrib`, with the exception of
+        /// enables (see `Capability::attrib_bit`), which are saved separately,
+        /// and the parameters of the bound textures (part of `GL_TEXTURE_BIT`,
+        /// but stored in the textures themselves, see `State::texture`).
+        #[derive(Debug)]
+        struct SavedAttribs {
+            mask: u32,
+            caps: Capabilities,
+            texture_params: Option<texture::PerTarget<texture::Params>>,
+            $($group: Option<$ty>),*
+        }
+
+        impl State {
+            fn push_attrib(&mut self, mask: u32, shared: &ShareGroup) -> Result<(), Error> {
+                if self.attrib_stack.len() >= MAX_ATTRIB_STACK_DEPTH {
+                    return Err(Error::StackOverflow);
+                }
+                let texture_params = if mask & api_1_0::TEXTURE_BIT != 0 {
+                    Some(texture::PerTarget {
+                        texture_1d: self.texture(shared, Target::Texture1D).params,
+                        texture_2d: self.texture(shared, Target::Texture2D).params,
+                    })
+                } else {
+                    None
+                };
+                self.attrib_stack.push(SavedAttribs {
+                    mask,
+                    caps: self.caps,
+                    texture_params,
+                    $($group: if mask & api_1_0::$bit != 0 {
+                        Some(self.$group.clone())
+                    } else {
+                        None
+                    }),*
+                });
+                Ok(())
+            }
+
+            fn pop_attrib(&mut self, shared: &mut ShareGroup) -> Result<(), Error> {
+                let saved = self.attrib_stack.pop().ok_or(Error::StackUnderflow)?;
+                for &cap in Capability::ALL {
+                    if saved.mask & (api_1_0::ENABLE_BIT | cap.attrib_bit()) != 0 {
+                        self.caps.set(cap, saved.caps.contains(cap));
+                    }
+                }
+                $(if let Some(group) = saved.$group {
+                    self.$group = group;
+                })*
+                // NOTE(eddyb) this has to come after restoring the bindings, so
+                // that the parameters go back to the textures they came from.
+                if let Some(params) = saved.texture_params {
+                    for &target in &[Target::Texture1D, Target::Texture2D] {
+                        self.texture_mut(shared, target).params = *params.get(target);
+                    }
+                }
+                Ok(())
+            }
+        }
+    };
+}
+
 #[derive(Debug, Default)]
 pub struct State {
+    /// Enables, which are part of `GL_ENABLE_BIT`, and also of the attribute
+    /// group that each capability is associated with (`Capability::attrib_bit`).
+    pub caps: Capabilities,
+
+    // Attribute groups.
+    pub current: Current,
+    pub transform: Transform,
+    pub viewport: Viewport,
+    pub scissor: Scissor,
+    pub point: Point,
+    pub line: Line,
+    pub polygon: Polygon,
+    pub polygon_stipple: PolygonStipple,
+    pub lighting: Lighting,
+    pub fog: Fog,
+    pub color_buffer: ColorBuffer,
+    pub depth: DepthBuffer,
+    pub stencil: Stencil,
+    pub accum: Accum,
+    pub pixel_mode: PixelMode,
+    pub eval: Eval,
+    pub list: List,
+    pub texturing: Texturing,
+    pub hints: Hints,
+
+    attrib_stack: Vec<SavedAttribs>,
+
+    /// Number of display lists currently being executed (see `MAX_LIST_NESTING`).
+    list_nesting: usize,
+
     // Matrices.
     pub modelview: MatrixStack,
     pub projection: MatrixStack,
-    matrix_mode: MatrixMode,
+    pub texture: MatrixStack,
+
+    /// The textures named `0`, which are specific to each context (unlike
+    /// all other textures, see `ShareGroup`).
+    pub default_textures: texture::PerTarget<Texture>,
+
+    /// The textures used by `glTexImage*` with proxy targets (which only
+    /// check whether a texture image is supported, without specifying one).
+    pub proxy_textures: texture::PerTarget<Texture>,
+
+    /// The vertices specified since `glBegin`, until `glEnd`.
+    pub begin: Option<VertexBatch>,
+}
+
+attrib_groups! {
+    current: Current = CURRENT_BIT,
+    transform: Transform = TRANSFORM_BIT,
+    viewport: Viewport = VIEWPORT_BIT,
+    scissor: Scissor = SCISSOR_BIT,
+    point: Point = POINT_BIT,
+    line: Line = LINE_BIT,
+    polygon: Polygon = POLYGON_BIT,
+    polygon_stipple: PolygonStipple = POLYGON_STIPPLE_BIT,
+    lighting: Lighting = LIGHTING_BIT,
+    fog: Fog = FOG_BIT,
+    color_buffer: ColorBuffer = COLOR_BUFFER_BIT,
+    depth: DepthBuffer = DEPTH_BUFFER_BIT,
+    stencil: Stencil = STENCIL_BUFFER_BIT,
+    accum: Accum = ACCUM_BUFFER_BIT,
+    pixel_mode: PixelMode = PIXEL_MODE_BIT,
+    eval: Eval = EVAL_BIT,
+    list: List = LIST_BIT,
+    texturing: Texturing = TEXTURE_BIT,
+    hints: Hints = HINT_BIT,
+}
+
+macro_rules! capabilities {
+    ($($cap:ident = $enum:ident in $bit:ident),* $(,)?) => {
+        /// Capabilities that can be enabled/disabled by `glEnable`/`glDisable`.
+        #[derive(Copy, Clone, Debug, PartialEq, Eq)]
+        pub enum Capability {
+            $($cap),*
+        }
+
+        impl Capability {
+            pub const ALL: &'static [Self] = &[$(Capability::$cap),*];
+
+            pub fn from_enum(cap: Enum) -> Result<Self, Error> {
+                match cap {
+                    $(Enum::$enum => Ok(Capability::$cap),)*
+                    _ => Err(Error::InvalidEnum),
+                }
+            }
+
+            /// The attribute group (other than `GL_ENABLE_BIT`) this belongs to.
+            pub fn attrib_bit(self) -> u32 {
+                match self {
+                    $(Capability::$cap => api_1_0::$bit),*
+                }
+            }
+        }
+    };
+}
+
+capabilities! {
+    AlphaTest = ALPHA_TEST in COLOR_BUFFER_BIT,
+    AutoNormal = AUTO_NORMAL in EVAL_BIT,
+    Blend = BLEND in COLOR_BUFFER_BIT,
+    ClipPlane0 = CLIP_PLANE0 in TRANSFORM_BIT,
+    ClipPlane1 = CLIP_PLANE1 in TRANSFORM_BIT,
+    ClipPlane2 = CLIP_PLANE2 in TRANSFORM_BIT,
+    ClipPlane3 = CLIP_PLANE3 in TRANSFORM_BIT,
+    ClipPlane4 = CLIP_PLANE4 in TRANSFORM_BIT,
+    ClipPlane5 = CLIP_PLANE5 in TRANSFORM_BIT,
+    ColorLogicOp = COLOR_LOGIC_OP in COLOR_BUFFER_BIT,
+    ColorMaterial = COLOR_MATERIAL in LIGHTING_BIT,
+    CullFace = CULL_FACE in POLYGON_BIT,
+    DepthTest = DEPTH_TEST in DEPTH_BUFFER_BIT,
+    Dither = DITHER in COLOR_BUFFER_BIT,
+    Fog = FOG in FOG_BIT,
+    IndexLogicOp = INDEX_LOGIC_OP in COLOR_BUFFER_BIT,
+    Light0 = LIGHT0 in LIGHTING_BIT,
+    Light1 = LIGHT1 in LIGHTING_BIT,
+    Light2 = LIGHT2 in LIGHTING_BIT,
+    Light3 = LIGHT3 in LIGHTING_BIT,
+    Light4 = LIGHT4 in LIGHTING_BIT,
+    Light5 = LIGHT5 in LIGHTING_BIT,
+    Light6 = LIGHT6 in LIGHTING_BIT,
+    Light7 = LIGHT7 in LIGHTING_BIT,
+    Lighting = LIGHTING in LIGHTING_BIT,
+    LineSmooth = LINE_SMOOTH in LINE_BIT,
+    LineStipple = LINE_STIPPLE in LINE_BIT,
+    Map1Color4 = MAP1_COLOR_4 in EVAL_BIT,
+    Map1Index = MAP1_INDEX in EVAL_BIT,
+    Map1Normal = MAP1_NORMAL in EVAL_BIT,
+    Map1TextureCoord1 = MAP1_TEXTURE_COORD_1 in EVAL_BIT,
+    Map1TextureCoord2 = MAP1_TEXTURE_COORD_2 in EVAL_BIT,
+    Map1TextureCoord3 = MAP1_TEXTURE_COORD_3 in EVAL_BIT,
+    Map1TextureCoord4 = MAP1_TEXTURE_COORD_4 in EVAL_BIT,
+    Map1Vertex3 = MAP1_VERTEX_3 in EVAL_BIT,
+    Map1Vertex4 = MAP1_VERTEX_4 in EVAL_BIT,
+    Map2Color4 = MAP2_COLOR_4 in EVAL_BIT,
+    Map2Index = MAP2_INDEX in EVAL_BIT,
+    Map2Normal = MAP2_NORMAL in EVAL_BIT,
+    Map2TextureCoord1 = MAP2_TEXTURE_COORD_1 in EVAL_BIT,
+    Map2TextureCoord2 = MAP2_TEXTURE_COORD_2 in EVAL_BIT,
+    Map2TextureCoord3 = MAP2_TEXTURE_COORD_3 in EVAL_BIT,
+    Map2TextureCoord4 = MAP2_TEXTURE_COORD_4 in EVAL_BIT,
+    Map2Vertex3 = MAP2_VERTEX_3 in EVAL_BIT,
+    Map2Vertex4 = MAP2_VERTEX_4 in EVAL_BIT,
+    Normalize = NORMALIZE in TRANSFORM_BIT,
+    PointSmooth = POINT_SMOOTH in POINT_BIT,
+    PolygonOffsetFill = POLYGON_OFFSET_FILL in POLYGON_BIT,
+    PolygonOffsetLine = POLYGON_OFFSET_LINE in POLYGON_BIT,
+    PolygonOffsetPoint = POLYGON_OFFSET_POINT in POLYGON_BIT,
+    PolygonSmooth = POLYGON_SMOOTH in POLYGON_BIT,
+    PolygonStipple = POLYGON_STIPPLE in POLYGON_BIT,
+    ScissorTest = SCISSOR_TEST in SCISSOR_BIT,
+    StencilTest = STENCIL_TEST in STENCIL_BUFFER_BIT,
+    Texture1D = TEXTURE_1D in TEXTURE_BIT,
+    Texture2D = TEXTURE_2D in TEXTURE_BIT,
+    TextureGenQ = TEXTURE_GEN_Q in TEXTURE_BIT,
+    TextureGenR = TEXTURE_GEN_R in TEXTURE_BIT,
+    TextureGenS = TEXTURE_GEN_S in TEXTURE_BIT,
+    TextureGenT = TEXTURE_GEN_T in TEXTURE_BIT,
+}
+
+impl Capability {
+    pub const LIGHTS: [Self; light::MAX_LIGHTS] = [
+        Capability::Light0,
+        Capability::Light1,
+        Capability::Light2,
+        Capability::Light3,
+        Capability::Light4,
+        Capability::Light5,
+        Capability::Light6,
+        Capability::Light7,
+    ];
+
+    pub const CLIP_PLANES: [Self; MAX_CLIP_PLANES] = [
+        Capability::ClipPlane0,
+        Capability::ClipPlane1,
+        Capability::ClipPlane2,
+        Capability::ClipPlane3,
+        Capability::ClipPlane4,
+        Capability::ClipPlane5,
+    ];
+
+    /// Texture coordinate generation, for each of `s`, `t`, `r` and `q`.
+    pub const TEXTURE_GEN: [Self; 4] = [
+        Capability::TextureGenS,
+        Capability::TextureGenT,
+        Capability::TextureGenR,
+        Capability::TextureGenQ,
+    ];
+}
+
+/// Set of enabled capabilities.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Capabilities(u64);
+
+impl Default for Capabilities {
+    fn default() -> Self {
+        // NOTE(eddyb) dithering is the only capability enabled by default.
+        let mut caps = Capabilities(0);
+        caps.set(Capability::Dither, true);
+        caps
+    }
+}
+
+impl Capabilities {
+    fn bit(cap: Capability) -> u64 {
+        1 << cap as u32
+    }
+
+    pub fn contains(&self, cap: Capability) -> bool {
+        self.0 & Self::bit(cap) != 0
+    }
+
+    pub fn set(&mut self, cap: Capability, enabled: bool) {
+        if enabled {
+            self.0 |= Self::bit(cap);
+        } else {
+            self.0 &= !Self::bit(cap);
+        }
+    }
+}
+
+/// Current values for per-vertex attributes.
+#[derive(Clone, Debug)]
+pub struct Current {
+    pub color: Vec4,
+    pub index: f32,
+    pub normal: Vec3,
+    pub texcoord: Vec4,
+    pub edge_flag: bool,
+
+    pub raster: RasterPos,
+}
+
+impl Default for Current {
+    fn default() -> Self {
+        Current {
+            color: Vec4::one(),
+            index: 1.0,
+            normal: Vec3::unit_z(),
+            texcoord: Vec4::unit_w(),
+            edge_flag: true,
+
+            raster: RasterPos::default(),
+        }
+    }
 }
 
+/// The primitive mode passed to `glBegin`, and the vertices specified since,
+/// which are assembled into primitives by `glEnd` (see `raster::assemble`).
 #[derive(Debug)]
-enum MatrixMode {
+pub struct VertexBatch {
+    pub mode: Enum,
+    pub vertices: Vec<raster::Vertex>,
+}
+
+/// The current raster position (see `glRasterPos`), along with the values of
+/// the per-vertex attributes, as they were processed for it.
+#[derive(Clone, Debug)]
+pub struct RasterPos {
+    /// Window coordinates, with the clip `w` coordinate as the 4th component.
+    pub position: Vec4,
+
+    /// Distance from the eye to the raster position, in eye coordinates.
+    pub distance: f32,
+
+    /// Whether the raster position was inside the view volume, when set.
+    pub valid: bool,
+
+    pub color: Vec4,
+    pub index: f32,
+    pub texcoord: Vec4,
+}
+
+impl Default for RasterPos {
+    fn default() -> Self {
+        RasterPos {
+            position: Vec4::unit_w(),
+            distance: 0.0,
+            valid: true,
+            color: Vec4::one(),
+            index: 1.0,
+            texcoord: Vec4::unit_w(),
+        }
+    }
+}
+
+/// Maximum number of user-defined clip planes (see `glClipPlane`).
+pub const MAX_CLIP_PLANES: usize = 6;
+
+#[derive(Clone, Debug, Default)]
+pub struct Transform {
+    pub matrix_mode: MatrixMode,
+
+    /// Clip planes, in eye coordinates (i.e. transformed by the inverse of
+    /// the modelview matrix in effect when they were specified).
+    pub clip_planes: [DVec4; MAX_CLIP_PLANES],
+}
+
+/// Maximum viewport width and height (larger values are silently clamped).
+pub const MAX_VIEWPORT_DIMS: u32 = 4096;
+
+#[derive(Clone, Debug)]
+pub struct Viewport {
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+
+    pub depth_range: (f64, f64),
+}
+
+impl Default for Viewport {
+    fn default() -> Self {
+        // NOTE(eddyb) the size is only known once the context is made current
+        // (see `Context::set_framebuffer`).
+        Viewport {
+            x: 0,
+            y: 0,
+            width: 0,
+            height: 0,
+
+            depth_range: (0.0, 1.0),
+        }
+    }
+}
+
+impl Viewport {
+    /// Transform `ndc` (normalized device coordinates) to window coordinates.
+    pub fn to_window(&self, ndc: Vec3) -> Vec3 {
+        let (x, y) = (self.x as f32, self.y as f32);
+        let (width, height) = (self.width as f32, self.height as f32);
+        let (near, far) = self.depth_range;
+        Vec3::new(
+            x + (ndc.x + 1.0) * width / 2.0,
+            y + (ndc.y + 1.0) * height / 2.0,
+            (near + (far - near) * (ndc.z as f64 + 1.0) / 2.0) as f32,
+        )
+    }
+}
+
+/// The scissor box (set by `glScissor`), outside of which no fragments are
+/// produced (and no pixels are cleared), while `GL_SCISSOR_TEST` is enabled.
+#[derive(Clone, Debug, Default)]
+pub struct Scissor {
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Scissor {
+    pub fn contains(&self, x: i32, y: i32) -> bool {
+        let (x, y) = (x as i64 - self.x as i64, y as i64 - self.y as i64);
+        (0..self.width as i64).contains(&x) && (0..self.height as i64).contains(&y)
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Point {
+    pub size: f32,
+}
+
+impl Default for Point {
+    fn default() -> Self {
+        Point { size: 1.0 }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Line {
+    pub width: f32,
+
+    /// The bits of the stipple pattern each determine whether a fragment is
+    /// drawn, with each bit used for `stipple_repeat` consecutive fragments
+    /// (see `glLineStipple`), while `GL_LINE_STIPPLE` is enabled.
+    pub stipple_pattern: u16,
+    pub stipple_repeat: u32,
+}
+
+impl Default for Line {
+    fn default() -> Self {
+        Line {
+            width: 1.0,
+            stipple_pattern: !0,
+            stipple_repeat: 1,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum PolygonMode {
+    Point,
+    Line,
+    Fill,
+}
+
+impl PolygonMode {
+    fn from_enum(mode: Enum) -> Result<Self, Error> {
+        match mode {
+            Enum::POINT => Ok(PolygonMode::Point),
+            Enum::LINE => Ok(PolygonMode::Line),
+            Enum::FILL => Ok(PolygonMode::Fill),
+            _ => Err(Error::InvalidEnum),
+        }
+    }
+
+    pub fn to_enum(self) -> Enum {
+        match self {
+            PolygonMode::Point => Enum::POINT,
+            PolygonMode::Line => Enum::LINE,
+            PolygonMode::Fill => Enum::FILL,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum FrontFace {
+    Cw,
+    Ccw,
+}
+
+impl FrontFace {
+    fn from_enum(mode: Enum) -> Result<Self, Error> {
+        match mode {
+            Enum::CW => Ok(FrontFace::Cw),
+            Enum::CCW => Ok(FrontFace::Ccw),
+            _ => Err(Error::InvalidEnum),
+        }
+    }
+
+    pub fn to_enum(self) -> Enum {
+        match self {
+            FrontFace::Cw => Enum::CW,
+            FrontFace::Ccw => Enum::CCW,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Polygon {
+    /// The faces of polygons discarded while `GL_CULL_FACE` is enabled.
+    pub cull_face_mode: Face,
+
+    /// The orientation (in window coordinates) of front-facing polygons.
+    pub front_face: FrontFace,
+
+    /// How front-facing and back-facing polygons are rasterized, respectively.
+    pub mode: [PolygonMode; 2],
+
+    /// The scale factor (for the depth slope) and constant offset added to
+    /// the depth of polygon fragments (see `glPolygonOffset`).
+    pub offset_factor: f32,
+    pub offset_units: f32,
+}
+
+impl Default for Polygon {
+    fn default() -> Self {
+        Polygon {
+            cull_face_mode: Face::Back,
+            front_face: FrontFace::Ccw,
+            mode: [PolygonMode::Fill; 2],
+            offset_factor: 0.0,
+            offset_units: 0.0,
+        }
+    }
+}
+
+/// The 32x32 polygon stipple pattern (see `glPolygonStipple`), used while
+/// `GL_POLYGON_STIPPLE` is enabled, with one row (bottom-to-top) per `u32`,
+/// and the bit `1 << x` of each row corresponding to the window `x % 32`.
+#[derive(Clone, Debug)]
+pub struct PolygonStipple {
+    pub pattern: [u32; 32],
+}
+
+impl Default for PolygonStipple {
+    fn default() -> Self {
+        PolygonStipple { pattern: [!0; 32] }
+    }
+}
+
+pub fn fog_params_len(pname: Enum) -> Result<usize, Error> {
+    use Enum::*;
+    match pname {
+        FOG_COLOR => Ok(4),
+
+        FOG_MODE | FOG_DENSITY | FOG_START | FOG_END | FOG_INDEX => Ok(1),
+
+        _ => Err(Error::InvalidEnum),
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum FogMode {
+    Linear,
+    Exp,
+    Exp2,
+}
+
+impl FogMode {
+    fn from_enum(mode: Enum) -> Result<Self, Error> {
+        match mode {
+            Enum::LINEAR => Ok(FogMode::Linear),
+            Enum::EXP => Ok(FogMode::Exp),
+            Enum::EXP2 => Ok(FogMode::Exp2),
+            _ => Err(Error::InvalidEnum),
+        }
+    }
+
+    pub fn to_enum(self) -> Enum {
+        match self {
+            FogMode::Linear => Enum::LINEAR,
+            FogMode::Exp => Enum::EXP,
+            FogMode::Exp2 => Enum::EXP2,
+        }
+    }
+}
+
+/// Fog parameters (see `glFog`), used while `GL_FOG` is enabled.
+#[derive(Clone, Debug)]
+pub struct Fog {
+    pub mode: FogMode,
+    pub density: f32,
+    pub start: f32,
+    pub end: f32,
+    pub index: f32,
+    pub color: Vec4,
+}
+
+impl Default for Fog {
+    fn default() -> Self {
+        Fog {
+            mode: FogMode::Exp,
+            density: 1.0,
+            start: 0.0,
+            end: 1.0,
+            index: 0.0,
+            color: Vec4::zero(),
+        }
+    }
+}
+
+impl Fog {
+    /// Blend the RGB components of a fragment's `color` with the fog color,
+    /// given the fragment's `distance` from the eye (in eye coordinates).
+    pub fn apply(&self, color: Vec4, distance: f32) -> Vec4 {
+        let f = match self.mode {
+            FogMode::Linear => (self.end - distance) / (self.end - self.start),
+            FogMode::Exp => (-self.density * distance).exp(),
+            FogMode::Exp2 => (-(self.density * distance).powi(2)).exp(),
+        };
+        // NOTE(eddyb) `start == end` can result in `NaN` (i.e. `0.0 / 0.0`).
+        let f = if f.is_nan() { 0.0 } else { f.clamp(0.0, 1.0) };
+        let rgb = self.color.truncate().lerp(color.truncate(), f);
+        rgb.extend(color.w)
+    }
+
+    fn set(&mut self, pname: Enum, params: &[f32]) -> Result<(), Error> {
+        if params.len() != fog_params_len(pname)? {
+            return Err(Error::InvalidEnum);
+        }
+
+        use Enum::*;
+        match pname {
+            FOG_MODE => {
+                // NOTE(eddyb) enum values are small enough to always be exact as `f32`.
+                let mode = Enum::try_from(params[0] as u32).map_err(|_| Error::InvalidEnum)?;
+                self.mode = FogMode::from_enum(mode)?;
+            }
+            FOG_DENSITY => {
+                if params[0] < 0.0 {
+                    return Err(Error::InvalidValue);
+                }
+                self.density = params[0];
+            }
+            FOG_START => self.start = params[0],
+            FOG_END => self.end = params[0],
+            FOG_INDEX => self.index = params[0],
+            FOG_COLOR => {
+                let color = Vec4::new(params[0], params[1], params[2], params[3]);
+                self.color = color.max(Vec4::zero()).min(Vec4::one());
+            }
+            _ => unreachable!(),
+        }
+        Ok(())
+    }
+}
+
+/// Validate a color buffer selection (see `glDrawBuffer`/`glReadBuffer`),
+/// given that only the front and back left buffers exist (i.e. there are
+/// neither stereo, nor auxiliary buffers).
+fn check_color_buffer(buffer: Enum) -> Result<(), Error> {
+    use Enum::*;
+    match buffer {
+        FRONT | BACK | LEFT | FRONT_LEFT | BACK_LEFT | FRONT_AND_BACK => Ok(()),
+        RIGHT | FRONT_RIGHT | BACK_RIGHT | AUX0 | AUX1 | AUX2 | AUX3 => {
+            Err(Error::InvalidOperation)
+        }
+        _ => Err(Error::InvalidEnum),
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct ColorBuffer {
+    /// The buffer(s) drawn into (see `glDrawBuffer`).
+    pub draw_buffer: Enum,
+
+    pub clear_value: Vec4,
+    pub clear_index: f32,
+
+    /// Alpha test comparison, and the reference value fragments' alpha is
+    /// compared against (see `glAlphaFunc`).
+    pub alpha_func: (CompareFunc, f32),
+
+    /// Source and destination blend factors (see `glBlendFunc`).
+    pub blend_func: (BlendFactor, BlendFactor),
+
+    pub logic_op: LogicOp,
+
+    /// Whether each of the red, green, blue and alpha components are written.
+    pub color_writemask: [bool; 4],
+
+    // NOTE(eddyb) this has no effect, as only RGBA mode is supported.
+    pub index_writemask: u32,
+}
+
+impl Default for ColorBuffer {
+    fn default() -> Self {
+        // NOTE(eddyb) all visuals are double-buffered (see `GL_DOUBLEBUFFER`).
+        ColorBuffer {
+            draw_buffer: Enum::BACK,
+            clear_value: Vec4::zero(),
+            clear_index: 0.0,
+            alpha_func: (CompareFunc::Always, 0.0),
+            blend_func: (BlendFactor::One, BlendFactor::Zero),
+            logic_op: LogicOp::Copy,
+            color_writemask: [true; 4],
+            index_writemask: !0,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct DepthBuffer {
+    pub func: CompareFunc,
+    pub writemask: bool,
+    pub clear_value: f64,
+}
+
+impl Default for DepthBuffer {
+    fn default() -> Self {
+        DepthBuffer {
+            func: CompareFunc::Less,
+            writemask: true,
+            clear_value: 1.0,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Stencil {
+    /// Stencil test comparison, the reference value it compares against the
+    /// stencil buffer, and the mask applied to both (see `glStencilFunc`).
+    pub func: CompareFunc,
+    pub reference: i32,
+    pub value_mask: u32,
+
+    /// The actions taken when the stencil test fails, when it passes but the
+    /// depth test fails, and when both pass, respectively (see `glStencilOp`).
+    pub fail: StencilOp,
+    pub depth_fail: StencilOp,
+    pub depth_pass: StencilOp,
+
+    pub writemask: u32,
+    pub clear_value: i32,
+}
+
+impl Default for Stencil {
+    fn default() -> Self {
+        Stencil {
+            func: CompareFunc::Always,
+            reference: 0,
+            value_mask: !0,
+            fail: StencilOp::Keep,
+            depth_fail: StencilOp::Keep,
+            depth_pass: StencilOp::Keep,
+            writemask: !0,
+            clear_value: 0,
+        }
+    }
+}
+
+impl Stencil {
+    /// The largest value representable in the stencil buffer.
+    const MAX: u8 = ((1u32 << raster::STENCIL_BITS) - 1) as u8;
+
+    fn reference(&self) -> u8 {
+        self.reference.clamp(0, Self::MAX as i32) as u8
+    }
+
+    /// Compare the reference value against `value` (from the stencil buffer).
+    pub fn test(&self, value: u8) -> bool {
+        let mask = self.value_mask as u8;
+        self.func.compare(self.reference() & mask, value & mask)
+    }
+
+    /// Replace `value` (in the stencil buffer) according to `op`, but only
+    /// change the bits enabled by `glStencilMask`.
+    pub fn update(&self, value: &mut u8, op: StencilOp) {
+        let new = op.apply(*value, self.reference(), Self::MAX);
+        let writemask = self.writemask as u8;
+        *value = (*value & !writemask) | (new & writemask);
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Accum {
+    pub clear_value: Vec4,
+}
+
+/// The contents of every pixel map (e.g. `GL_PIXEL_MAP_I_TO_R`), which is
+/// always the default, as `glPixelMap` isn't supported.
+const DEFAULT_PIXEL_MAP: [f32; 1] = [0.0];
+
+/// The entry of a pixel map with `len` (a power of two) entries that an
+/// index (after its shift and offset) looks up, i.e. its integer part, masked.
+fn lookup_index(index: f64, len: usize) -> usize {
+    // NOTE(eddyb) `as` saturates, which is the best we can do here.
+    (index.floor() as i64 & (len as i64 - 1)) as usize
+}
+
+/// Pixel transfer modes (see `glPixelTransfer`), the zoom factors used by
+/// `glDrawPixels`/`glCopyPixels`, and the buffer read from by `glReadPixels`.
+#[derive(Clone, Debug)]
+pub struct PixelMode {
+    pub map_color: bool,
+    pub map_stencil: bool,
+    pub index_shift: i32,
+    pub index_offset: i32,
+
+    /// Factors (and then offsets) applied to the RGBA (or depth) components
+    /// of pixels transferred to and from GL (see `PixelMode::transfer_color`).
+    pub scale: Vec4,
+    pub bias: Vec4,
+    pub depth_scale: f32,
+    pub depth_bias: f32,
+
+    pub zoom: (f32, f32),
+
+    pub read_buffer: Enum,
+}
+
+impl Default for PixelMode {
+    fn default() -> Self {
+        PixelMode {
+            map_color: false,
+            map_stencil: false,
+            index_shift: 0,
+            index_offset: 0,
+            scale: Vec4::one(),
+            bias: Vec4::zero(),
+            depth_scale: 1.0,
+            depth_bias: 0.0,
+            zoom: (1.0, 1.0),
+            read_buffer: Enum::BACK,
+        }
+    }
+}
+
+impl PixelMode {
+    fn set_transfer(&mut self, pname: Enum, param: f32) -> Result<(), Error> {
+        use Enum::*;
+        match pname {
+            MAP_COLOR => self.map_color = param != 0.0,
+            MAP_STENCIL => self.map_stencil = param != 0.0,
+            // NOTE(eddyb) `as` saturates, which is the best we can do here.
+            INDEX_SHIFT => self.index_shift = param.round() as i32,
+            INDEX_OFFSET => self.index_offset = param.round() as i32,
+            RED_SCALE => self.scale.as_mut()[0] = param,
+            GREEN_SCALE => self.scale.as_mut()[1] = param,
+            BLUE_SCALE => self.scale.as_mut()[2] = param,
+            ALPHA_SCALE => self.scale.as_mut()[3] = param,
+            RED_BIAS => self.bias.as_mut()[0] = param,
+            GREEN_BIAS => self.bias.as_mut()[1] = param,
+            BLUE_BIAS => self.bias.as_mut()[2] = param,
+            ALPHA_BIAS => self.bias.as_mut()[3] = param,
+            DEPTH_SCALE => self.depth_scale = param,
+            DEPTH_BIAS => self.depth_bias = param,
+            _ => return Err(Error::InvalidEnum),
+        }
+        Ok(())
+    }
+
+    /// Apply the pixel transfer operations to a (RGBA) color, including the
+    /// `GL_PIXEL_MAP_*_TO_*` color maps, when `GL_MAP_COLOR` is enabled.
+    pub fn transfer_color(&self, color: Vec4) -> Vec4 {
+        let color = (color * self.scale + self.bias)
+            .max(Vec4::zero())
+            .min(Vec4::one());
+        if !self.map_color {
+            return color;
+        }
+        // NOTE(eddyb) all of `GL_PIXEL_MAP_{R,G,B,A}_TO_*` are the default map.
+        let last = (DEFAULT_PIXEL_MAP.len() - 1) as f32;
+        let map = |c: f32| DEFAULT_PIXEL_MAP[(c * last).round() as usize];
+        Vec4::new(map(color.x), map(color.y), map(color.z), map(color.w))
+    }
+
+    /// Apply the pixel transfer operations to a color index, including its
+    /// conversion to RGBA (through the `GL_PIXEL_MAP_I_TO_*` maps).
+    pub fn transfer_color_index(&self, index: f32) -> Vec4 {
+        let index = self.shift_and_offset_index(index as f64);
+        // NOTE(eddyb) all of `GL_PIXEL_MAP_I_TO_{R,G,B,A}` are the default map.
+        Vec4::splat(DEFAULT_PIXEL_MAP[lookup_index(index, DEFAULT_PIXEL_MAP.len())])
+    }
+
+    /// Apply the pixel transfer operations to a depth value.
+    pub fn transfer_depth(&self, depth: f32) -> f32 {
+        (depth * self.depth_scale + self.depth_bias).clamp(0.0, 1.0)
+    }
+
+    /// Apply the pixel transfer operations to a stencil index.
+    pub fn transfer_stencil(&self, index: u32) -> f64 {
+        let index = self.shift_and_offset_index(index as f64);
+        if !self.map_stencil {
+            return index;
+        }
+        DEFAULT_PIXEL_MAP[lookup_index(index, DEFAULT_PIXEL_MAP.len())] as f64
+    }
+
+    /// Shift an index by `GL_INDEX_SHIFT` bits (to the right, if negative),
+    /// then add `GL_INDEX_OFFSET` to it.
+    fn shift_and_offset_index(&self, index: f64) -> f64 {
+        index * 2f64.powi(self.index_shift) + self.index_offset as f64
+    }
+}
+
+/// The grids evaluated by `glEvalMesh`/`glEvalPoint` (see `glMapGrid`), each
+/// with the domain (start and end) and number of segments for each of the
+/// `u` (and `v`, for 2D maps) coordinates.
+#[derive(Clone, Debug)]
+pub struct Eval {
+    pub map1_grid_domain: [f32; 2],
+    pub map1_grid_segments: i32,
+    pub map2_grid_domain: [f32; 4],
+    pub map2_grid_segments: [i32; 2],
+}
+
+impl Default for Eval {
+    fn default() -> Self {
+        Eval {
+            map1_grid_domain: [0.0, 1.0],
+            map1_grid_segments: 1,
+            map2_grid_domain: [0.0, 1.0, 0.0, 1.0],
+            map2_grid_segments: [1, 1],
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct List {
+    /// Offset added to list names by `glCallLists`.
+    pub base: u32,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Texturing {
+    /// Names of the textures bound to each target (see `State::texture`).
+    pub bindings: texture::PerTarget<u32>,
+
+    pub env: texture::Env,
+    pub tex_gen: tex_gen::TexGen,
+}
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum HintMode {
+    #[default]
+    DontCare,
+    Fastest,
+    Nicest,
+}
+
+impl HintMode {
+    fn from_enum(mode: Enum) -> Result<Self, Error> {
+        match mode {
+            Enum::DONT_CARE => Ok(HintMode::DontCare),
+            Enum::FASTEST => Ok(HintMode::Fastest),
+            Enum::NICEST => Ok(HintMode::Nicest),
+            _ => Err(Error::InvalidEnum),
+        }
+    }
+
+    pub fn to_enum(self) -> Enum {
+        match self {
+            HintMode::DontCare => Enum::DONT_CARE,
+            HintMode::Fastest => Enum::FASTEST,
+            HintMode::Nicest => Enum::NICEST,
+        }
+    }
+}
+
+/// Implementation-specific preferences (set by `glHint`), of which only
+/// `perspective_correction` currently has any effect (see `raster`).
+#[derive(Clone, Debug, Default)]
+pub struct Hints {
+    pub perspective_correction: HintMode,
+    pub point_smooth: HintMode,
+    pub line_smooth: HintMode,
+    pub polygon_smooth: HintMode,
+    pub fog: HintMode,
+}
+
+impl Hints {
+    fn set(&mut self, target: Enum, mode: Enum) -> Result<(), Error> {
+        use Enum::*;
+        let hint = match target {
+            PERSPECTIVE_CORRECTION_HINT => &mut self.perspective_correction,
+            POINT_SMOOTH_HINT => &mut self.point_smooth,
+            LINE_SMOOTH_HINT => &mut self.line_smooth,
+            POLYGON_SMOOTH_HINT => &mut self.polygon_smooth,
+            FOG_HINT => &mut self.fog,
+            _ => return Err(Error::InvalidEnum),
+        };
+        *hint = HintMode::from_enum(mode)?;
+        Ok(())
+    }
+}
+
+#[derive(Copy, Clone, Debug)]
+pub enum CompareFunc {
+    Never,
+    Less,
+    Equal,
+    LEqual,
+    Greater,
+    NotEqual,
+    GEqual,
+    Always,
+}
+
+impl CompareFunc {
+    fn from_enum(func: Enum) -> Result<Self, Error> {
+        use Enum::*;
+        Ok(match func {
+            NEVER => CompareFunc::Never,
+            LESS => CompareFunc::Less,
+            EQUAL => CompareFunc::Equal,
+            LEQUAL => CompareFunc::LEqual,
+            GREATER => CompareFunc::Greater,
+            NOTEQUAL => CompareFunc::NotEqual,
+            GEQUAL => CompareFunc::GEqual,
+            ALWAYS => CompareFunc::Always,
+            _ => return Err(Error::InvalidEnum),
+        })
+    }
+
+    pub fn to_enum(self) -> Enum {
+        match self {
+            CompareFunc::Never => Enum::NEVER,
+            CompareFunc::Less => Enum::LESS,
+            CompareFunc::Equal => Enum::EQUAL,
+            CompareFunc::LEqual => Enum::LEQUAL,
+            CompareFunc::Greater => Enum::GREATER,
+            CompareFunc::NotEqual => Enum::NOTEQUAL,
+            CompareFunc::GEqual => Enum::GEQUAL,
+            CompareFunc::Always => Enum::ALWAYS,
+        }
+    }
+
+    /// Compare an incoming value `a` against a reference value `b`.
+    pub fn compare<T: PartialOrd>(self, a: T, b: T) -> bool {
+        match self {
+            CompareFunc::Never => false,
+            CompareFunc::Less => a < b,
+            CompareFunc::Equal => a == b,
+            CompareFunc::LEqual => a <= b,
+            CompareFunc::Greater => a > b,
+            CompareFunc::NotEqual => a != b,
+            CompareFunc::GEqual => a >= b,
+            CompareFunc::Always => true,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, Default)]
+pub enum MatrixMode {
+    #[default]
     ModelView,
     Projection,
+    Texture,
 }
 
-impl Default for MatrixMode {
-    fn default() -> Self {
-        MatrixMode::ModelView
+impl MatrixMode {
+    pub fn to_enum(self) -> Enum {
+        match self {
+            MatrixMode::ModelView => Enum::MODELVIEW,
+            MatrixMode::Projection => Enum::PROJECTION,
+            MatrixMode::Texture => Enum::TEXTURE,
+        }
+    }
+
+    /// Maximum depth of the matrix stack for this mode.
+    pub fn max_stack_depth(self) -> usize {
+        match self {
+            MatrixMode::ModelView => MAX_MODELVIEW_STACK_DEPTH,
+            MatrixMode::Projection => MAX_PROJECTION_STACK_DEPTH,
+            MatrixMode::Texture => MAX_TEXTURE_STACK_DEPTH,
+        }
     }
 }
 
+// NOTE(eddyb) the spec minimums are 32, 2 and 2, respectively.
+pub const MAX_MODELVIEW_STACK_DEPTH: usize = 32;
+pub const MAX_PROJECTION_STACK_DEPTH: usize = 32;
+pub const MAX_TEXTURE_STACK_DEPTH: usize = 10;
+
 #[derive(Debug, Default)]
 pub struct MatrixStack {
     pub mat: Mat4,
@@ -29,20 +1104,269 @@ pub struct MatrixStack {
 }
 
 impl MatrixStack {
-    fn push(&mut self) {
+    /// Number of matrices on the stack, including the current one.
+    pub fn depth(&self) -> usize {
+        self.stack.len() + 1
+    }
+
+    fn push(&mut self, max_depth: usize) -> Result<(), Error> {
+        if self.depth() >= max_depth {
+            return Err(Error::StackOverflow);
+        }
         self.stack.push(self.mat);
+        Ok(())
     }
-    fn pop(&mut self) {
-        // FIXME(eddyb) report error
-        self.mat = self.stack.pop().unwrap();
+    fn pop(&mut self) -> Result<(), Error> {
+        self.mat = self.stack.pop().ok_or(Error::StackUnderflow)?;
+        Ok(())
     }
 }
 
+/// The perspective projection matrix multiplied in by `glFrustum`.
+pub fn frustum(l: f64, r: f64, b: f64, t: f64, n: f64, f: f64) -> Result<DMat4, Error> {
+    if n <= 0.0 || f <= 0.0 || l == r || b == t || n == f {
+        return Err(Error::InvalidValue);
+    }
+    Ok(DMat4::from_cols(
+        DVec4::new(2.0 * n / (r - l), 0.0, 0.0, 0.0),
+        DVec4::new(0.0, 2.0 * n / (t - b), 0.0, 0.0),
+        DVec4::new(
+            (r + l) / (r - l),
+            (t + b) / (t - b),
+            -(f + n) / (f - n),
+            -1.0,
+        ),
+        DVec4::new(0.0, 0.0, -(2.0 * f * n) / (f - n), 0.0),
+    ))
+}
+
+/// The parallel projection matrix multiplied in by `glOrtho`.
+pub fn ortho(l: f64, r: f64, b: f64, t: f64, n: f64, f: f64) -> Result<DMat4, Error> {
+    if l == r || b == t || n == f {
+        return Err(Error::InvalidValue);
+    }
+    Ok(DMat4::from_cols(
+        DVec4::new(2.0 / (r - l), 0.0, 0.0, 0.0),
+        DVec4::new(0.0, 2.0 / (t - b), 0.0, 0.0),
+        DVec4::new(0.0, 0.0, -2.0 / (f - n), 0.0),
+        DVec4::new(
+            -(r + l) / (r - l),
+            -(t + b) / (t - b),
+            -(f + n) / (f - n),
+            1.0,
+        ),
+    ))
+}
+
 impl State {
+    /// Update the material parameter selected by `glColorMaterial` to the
+    /// current color, if `GL_COLOR_MATERIAL` is enabled (to be called whenever
+    /// either the current color, or any of the `GL_COLOR_MATERIAL` state, changes).
+    fn track_color_material(&mut self) {
+        if self.caps.contains(Capability::ColorMaterial) {
+            self.lighting.track_color_material(self.current.color);
+        }
+    }
+
+    /// Transform `position` (in object coordinates) to clip coordinates, and
+    /// compute its colors (lit, if `GL_LIGHTING` is enabled) and texcoords,
+    /// from the current values of the per-vertex attributes.
+    pub fn vertex(&self, position: Vec4) -> (Vec4, raster::Vertex) {
+        let eye = self.modelview.mat.mul_vec4(position);
+        let normal = || {
+            // FIXME(eddyb) cache the inverse-transpose of the
+            // modelview matrix, instead of recomputing it.
+            let normal = self
+                .modelview
+                .mat
+                .inverse()
+                .transpose()
+                .transform_vector3(self.current.normal);
+            if self.caps.contains(Capability::Normalize) {
+                normal.normalize()
+            } else {
+                normal
+            }
+        };
+        let colors = if self.caps.contains(Capability::Lighting) {
+            self.lighting.shade(&self.caps, eye, normal())
+        } else {
+            [self.current.color; 2]
+        };
+        let texcoord = self.texturing.tex_gen.generate(
+            &self.caps,
+            self.current.texcoord,
+            position,
+            eye,
+            normal,
+        );
+        let vertex = raster::Vertex {
+            clip: self.projection.mat.mul_vec4(eye),
+            colors,
+            eye,
+            texcoord: self.texture.mat.mul_vec4(texcoord),
+            edge_flag: self.current.edge_flag,
+        };
+        (eye, vertex)
+    }
+
+    /// Set the current raster position, processing `position` (in object
+    /// coordinates) as if it were a vertex (see `State::vertex`).
+    fn set_raster_pos(&mut self, position: Vec4) {
+        let (eye, v) = self.vertex(position);
+        let inside = v.is_inside_clip_volume(&self.enabled_clip_planes());
+        let raster = &mut self.current.raster;
+        raster.valid = inside && v.clip.w != 0.0;
+        if !raster.valid {
+            return;
+        }
+        let window = self.viewport.to_window(v.clip.truncate() / v.clip.w);
+        raster.position = window.extend(v.clip.w);
+        raster.distance = eye.truncate().length();
+        raster.color = v.colors[0];
+        raster.index = self.current.index;
+        raster.texcoord = v.texcoord;
+    }
+
+    /// The user clip planes (see `glClipPlane`) enabled by `GL_CLIP_PLANEi`.
+    pub fn enabled_clip_planes(&self) -> SmallVec<[Vec4; MAX_CLIP_PLANES]> {
+        Capability::CLIP_PLANES
+            .iter()
+            .zip(&self.transform.clip_planes)
+            .filter(|(&cap, _)| self.caps.contains(cap))
+            .map(|(_, plane)| plane.as_f32())
+            .collect()
+    }
+
+    /// The texture bound to `target` (which may be a default texture).
+    pub fn texture<'a>(&'a self, shared: &'a ShareGroup, target: Target) -> &'a Texture {
+        // FIXME(eddyb) textures deleted by another context should remain
+        // usable through this one, until they're unbound.
+        match *self.texturing.bindings.get(target) {
+            0 => self.default_textures.get(target),
+            name => shared
+                .textures
+                .get(&name)
+                .unwrap_or_else(|| self.default_textures.get(target)),
+        }
+    }
+
+    /// The texture applied to fragments, i.e. the one bound to the enabled
+    /// target (`GL_TEXTURE_2D` taking precedence over `GL_TEXTURE_1D`),
+    /// unless it's not complete (see `Texture::is_complete`), in which case
+    /// it's as if texturing was disabled.
+    pub fn enabled_texture<'a>(&'a self, shared: &'a ShareGroup) -> Option<(Target, &'a Texture)> {
+        let target = if self.caps.contains(Capability::Texture2D) {
+            Target::Texture2D
+        } else if self.caps.contains(Capability::Texture1D) {
+            Target::Texture1D
+        } else {
+            return None;
+        };
+        let texture = self.texture(shared, target);
+        if texture.is_complete(target) {
+            Some((target, texture))
+        } else {
+            None
+        }
+    }
+
+    pub fn texture_mut<'a>(
+        &'a mut self,
+        shared: &'a mut ShareGroup,
+        target: Target,
+    ) -> &'a mut Texture {
+        match *self.texturing.bindings.get(target) {
+            0 => self.default_textures.get_mut(target),
+            name => match shared.textures.get_mut(&name) {
+                Some(texture) => texture,
+                None => self.default_textures.get_mut(target),
+            },
+        }
+    }
+
+    /// Replace mipmap `level` of the texture bound to `target` (which has to
+    /// be `expected`, or its proxy target) with `image`, and its `pixels` (of
+    /// `format`, see `State::transfer_pixels`).
+    fn tex_image(
+        &mut self,
+        shared: &mut ShareGroup,
+        (target, expected): (Enum, Target),
+        level: i32,
+        mut image: Image,
+        (format, pixels): (Enum, Option<Vec<Vec4>>),
+    ) -> Result<(), Error> {
+        let (target, proxy) = Target::from_enum_or_proxy(target)?;
+        if target != expected {
+            return Err(Error::InvalidEnum);
+        }
+        if !(0..texture::MAX_LEVELS as i32).contains(&level) {
+            return Err(Error::InvalidValue);
+        }
+
+        // NOTE(eddyb) proxy textures only record whether the image would be
+        // supported (with a zero-sized image standing in for "unsupported"),
+        // and never have any texels.
+        // FIXME(eddyb) proxy targets should always be executed immediately,
+        // even while compiling a display list.
+        if proxy {
+            if image.is_too_large() {
+                image = Image::default();
+            }
+            self.proxy_textures
+                .get_mut(target)
+                .set_image(level as usize, image);
+            return Ok(());
+        }
+        if image.is_too_large() {
+            return Err(Error::InvalidValue);
+        }
+        let pixels = pixels.map(|pixels| self.transfer_pixels(format, pixels));
+        image.fill(pixels.as_deref());
+        self.texture_mut(shared, target)
+            .set_image(level as usize, image);
+        Ok(())
+    }
+
+    /// Replace a region of mipmap `level` of the texture bound to `target`
+    /// (which has to be `expected`), with `pixels` (of `format`, see
+    /// `Texture::set_sub_image`).
+    fn tex_sub_image(
+        &mut self,
+        shared: &mut ShareGroup,
+        (target, expected): (Enum, Target),
+        level: i32,
+        offset: (i32, i32),
+        size: (u32, u32),
+        (format, pixels): (Enum, Option<Vec<Vec4>>),
+    ) -> Result<(), Error> {
+        let target = Target::from_enum(target)?;
+        if target != expected {
+            return Err(Error::InvalidEnum);
+        }
+        if !(0..texture::MAX_LEVELS as i32).contains(&level) {
+            return Err(Error::InvalidValue);
+        }
+        // NOTE(eddyb) null pointers are only meaningful for `glTexImage*`.
+        let pixels = self.transfer_pixels(format, pixels.unwrap_or_default());
+        self.texture_mut(shared, target).set_sub_image(
+            target,
+            level as usize,
+            offset,
+            size,
+            &pixels,
+        )
+    }
+
+    pub fn attrib_stack_depth(&self) -> usize {
+        self.attrib_stack.len()
+    }
+
     fn matrix_stack(&mut self) -> &mut MatrixStack {
-        match self.matrix_mode {
+        match self.transform.matrix_mode {
             MatrixMode::ModelView => &mut self.modelview,
             MatrixMode::Projection => &mut self.projection,
+            MatrixMode::Texture => &mut self.texture,
         }
     }
 
@@ -60,45 +1384,828 @@ impl State {
         *mat = mat.as_f64().mul_mat4(&other).as_f32();
     }
 
-    pub fn apply(&mut self, cmd: Command, unhandled: &mut impl FnMut(&mut Self, Command)) {
+    /// Apply the pixel transfer operations to `pixels` (of `format`), as
+    /// they're used (i.e. not when they're unpacked, which may be during
+    /// display list compilation, see `pixel::unpack`).
+    fn transfer_pixels(&self, format: Enum, mut pixels: Vec<Vec4>) -> Vec<Vec4> {
+        for pixel in &mut pixels {
+            *pixel = if format == Enum::COLOR_INDEX {
+                self.pixel_mode.transfer_color_index(pixel.x)
+            } else {
+                self.pixel_mode.transfer_color(*pixel)
+            };
+        }
+        pixels
+    }
+
+    fn map_grid1(&mut self, segments: i32, domain: [f32; 2]) -> Result<(), Error> {
+        if segments <= 0 {
+            return Err(Error::InvalidValue);
+        }
+        self.eval.map1_grid_segments = segments;
+        self.eval.map1_grid_domain = domain;
+        Ok(())
+    }
+
+    fn map_grid2(&mut self, segments: [i32; 2], domain: [f32; 4]) -> Result<(), Error> {
+        if segments.iter().any(|&n| n <= 0) {
+            return Err(Error::InvalidValue);
+        }
+        self.eval.map2_grid_segments = segments;
+        self.eval.map2_grid_domain = domain;
+        Ok(())
+    }
+
+    /// Set the current per-vertex attributes specified in `attribs`.
+    fn set_attribs(&mut self, attribs: &list::Attribs) {
+        if let Some(color) = attribs.color {
+            self.current.color = color;
+            self.track_color_material();
+        }
+        if let Some(index) = attribs.index {
+            self.current.index = index;
+        }
+        if let Some(normal) = attribs.normal {
+            self.current.normal = normal;
+        }
+        if let Some(texcoord) = attribs.texcoord {
+            self.current.texcoord = texcoord;
+        }
+        if let Some(edge_flag) = attribs.edge_flag {
+            self.current.edge_flag = edge_flag;
+        }
+    }
+
+    /// Execute `op`, equivalently to the commands it was compiled from.
+    fn apply_op(
+        &mut self,
+        op: &list::Op,
+        shared: &mut ShareGroup,
+        unhandled: &mut impl FnMut(&mut Self, &mut ShareGroup, Command) -> Result<(), Error>,
+    ) -> Result<(), Error> {
+        match op {
+            list::Op::Command(cmd) => self.apply(cmd.clone(), shared, unhandled),
+            &list::Op::Matrix { load, mat } => {
+                if self.begin.is_some() {
+                    return Err(Error::InvalidOperation);
+                }
+                if load {
+                    *self.matrix() = mat.as_f32();
+                } else {
+                    self.matrix_mul_double(mat);
+                }
+                Ok(())
+            }
+            list::Op::Primitive(primitive) => {
+                // NOTE(eddyb) like `glBegin`, this is an error if already
+                // between `glBegin` and `glEnd`, but the vertices are still
+                // added to the current primitive, which `glEnd` then ends.
+                let result = if self.begin.is_some() {
+                    Err(Error::InvalidOperation)
+                } else {
+                    self.begin = Some(VertexBatch {
+                        mode: primitive.mode,
+                        vertices: Vec::with_capacity(primitive.vertices.len()),
+                    });
+                    Ok(())
+                };
+                let mut batch = self.begin.take().unwrap();
+                for (attribs, position) in &primitive.vertices {
+                    self.set_attribs(attribs);
+                    batch.vertices.push(self.vertex(*position).1);
+                }
+                self.begin = Some(batch);
+                self.set_attribs(&primitive.end);
+                result.and(unhandled(self, shared, Command::glEnd))
+            }
+        }
+    }
+
+    /// Execute all the commands in `list` (see `State::apply`).
+    fn call_list(
+        &mut self,
+        list: &DisplayList,
+        shared: &mut ShareGroup,
+        unhandled: &mut impl FnMut(&mut Self, &mut ShareGroup, Command) -> Result<(), Error>,
+    ) -> Result<(), Error> {
+        if self.list_nesting == MAX_LIST_NESTING {
+            return Ok(());
+        }
+        self.list_nesting += 1;
+
+        // NOTE(eddyb) errors don't stop the execution of the list, and
+        // only the first one needs to be kept (see `Context::error`).
+        // NOTE(eddyb) the lists flattened into this one would've been called
+        // one level deeper, which can't exceed `MAX_LIST_NESTING` either.
+        let ops = match &list.flattened {
+            Some(flattened)
+                if self.list_nesting < MAX_LIST_NESTING && flattened.is_valid(&shared.lists) =>
+            {
+                &flattened.ops
+            }
+            _ => &list.ops,
+        };
+        let mut result = Ok(());
+        for op in ops {
+            result = result.and(self.apply_op(op, shared, unhandled));
+        }
+
+        self.list_nesting -= 1;
+        result
+    }
+
+    pub fn apply(
+        &mut self,
+        cmd: Command,
+        shared: &mut ShareGroup,
+        unhandled: &mut impl FnMut(&mut Self, &mut ShareGroup, Command) -> Result<(), Error>,
+    ) -> Result<(), Error> {
         use {Command::*, Enum::*};
+        if self.begin.is_some() && !cmd.is_allowed_between_begin_end() {
+            return Err(Error::InvalidOperation);
+        }
         match cmd {
-            glCallList(list) => {
-                for cmd in list.cmds.iter().cloned() {
-                    self.apply(cmd, unhandled);
+            // NOTE(eddyb) lists are looked up by name only when called, so that
+            // lists calling other lists always see their latest contents, and
+            // undefined lists are silently ignored.
+            glCallList(name) => {
+                if let Some(list) = shared.lists.get(&name).cloned() {
+                    return self.call_list(&list, shared, unhandled);
+                }
+            }
+            glCallLists(_, _, names) => {
+                // NOTE(eddyb) the list base is read before every call, as it
+                // may be changed by any of the lists being called.
+                let mut result = Ok(());
+                for name in names {
+                    let name = self.list.base.wrapping_add(name);
+                    if let Some(list) = shared.lists.get(&name).cloned() {
+                        result = result.and(self.call_list(&list, shared, unhandled));
+                    }
+                }
+                return result;
+            }
+            glListBase(base) => self.list.base = base,
+            glEnable(cap) | glDisable(cap) => {
+                let cap = Capability::from_enum(cap)?;
+                self.caps.set(cap, matches!(cmd, glEnable(_)));
+                if cap == Capability::ColorMaterial {
+                    self.track_color_material();
+                }
+            }
+            glBegin(mode) => {
+                if !raster::is_primitive_mode(mode) {
+                    return Err(Error::InvalidEnum);
+                }
+                self.begin = Some(VertexBatch {
+                    mode,
+                    vertices: vec![],
+                });
+            }
+            glVertex4f(x, y, z, w) => {
+                // NOTE(eddyb) vertices outside `glBegin`/`glEnd` are ignored.
+                if let Some(mut batch) = self.begin.take() {
+                    batch.vertices.push(self.vertex(Vec4::new(x, y, z, w)).1);
+                    self.begin = Some(batch);
+                }
+            }
+            glEdgeFlag(flag) => self.current.edge_flag = flag,
+            glColor4f(r, g, b, a) => {
+                self.current.color = Vec4::new(r, g, b, a);
+                self.track_color_material();
+            }
+            glIndexf(c) => self.current.index = c,
+            glNormal3f(x, y, z) => self.current.normal = Vec3::new(x, y, z),
+            glTexCoord4f(s, t, r, q) => self.current.texcoord = Vec4::new(s, t, r, q),
+            glRasterPos4f(x, y, z, w) => self.set_raster_pos(Vec4::new(x, y, z, w)),
+            // NOTE(eddyb) evaluating only has an effect through the maps
+            // defined by `glMap1`/`glMap2`, which aren't supported, so
+            // there's nothing to evaluate.
+            // FIXME(eddyb) implement evaluators (and `glMap1`/`glMap2`).
+            glEvalCoord1f(_) | glEvalCoord1d(_) | glEvalCoord2f(..) | glEvalCoord2d(..)
+            | glEvalPoint1(_) | glEvalPoint2(..) => {}
+            glEvalMesh1(mode, ..) => {
+                if !matches!(mode, POINT | LINE) {
+                    return Err(Error::InvalidEnum);
+                }
+            }
+            glEvalMesh2(mode, ..) => {
+                if !matches!(mode, POINT | LINE | FILL) {
+                    return Err(Error::InvalidEnum);
+                }
+            }
+            glShadeModel(mode) => {
+                self.lighting.shade_model = match mode {
+                    FLAT => ShadeModel::Flat,
+                    SMOOTH => ShadeModel::Smooth,
+                    _ => return Err(Error::InvalidEnum),
+                };
+            }
+            glLightf(light, pname, param) => {
+                self.lighting
+                    .set_light(light, pname, &[param], &self.modelview.mat)?
+            }
+            glLighti(light, pname, param) => {
+                self.lighting
+                    .set_light(light, pname, &[param as f32], &self.modelview.mat)?
+            }
+            glLightfv(light, pname, params) => {
+                self.lighting
+                    .set_light(light, pname, &params, &self.modelview.mat)?
+            }
+            glLightiv(light, pname, params) => self.lighting.set_light(
+                light,
+                pname,
+                &light::int_params_to_float(pname, &params),
+                &self.modelview.mat,
+            )?,
+            glMaterialf(face, pname, param) => self.lighting.set_material(face, pname, &[param])?,
+            glMateriali(face, pname, param) => {
+                self.lighting.set_material(face, pname, &[param as f32])?
+            }
+            glMaterialfv(face, pname, params) => {
+                self.lighting.set_material(face, pname, &params)?
+            }
+            glMaterialiv(face, pname, params) => self.lighting.set_material(
+                face,
+                pname,
+                &light::int_params_to_float(pname, &params),
+            )?,
+            glLightModelf(pname, param) => self.lighting.set_light_model(pname, &[param])?,
+            glLightModeli(pname, param) => self.lighting.set_light_model(pname, &[param as f32])?,
+            glLightModelfv(pname, params) => self.lighting.set_light_model(pname, &params)?,
+            glLightModeliv(pname, params) => self
+                .lighting
+                .set_light_model(pname, &light::int_params_to_float(pname, &params))?,
+            glColorMaterial(face, mode) => {
+                self.lighting.set_color_material(face, mode)?;
+                self.track_color_material();
+            }
+            glViewport(x, y, w, h) => {
+                self.viewport.x = x;
+                self.viewport.y = y;
+                self.viewport.width = w.min(self::MAX_VIEWPORT_DIMS);
+                self.viewport.height = h.min(self::MAX_VIEWPORT_DIMS);
+            }
+            glDepthRange(near, far) => self.viewport.depth_range = (near, far),
+            glScissor(x, y, w, h) => {
+                self.scissor = Scissor {
+                    x,
+                    y,
+                    width: w,
+                    height: h,
+                };
+            }
+            glPointSize(size) => {
+                if size <= 0.0 {
+                    return Err(Error::InvalidValue);
+                }
+                self.point.size = size;
+            }
+            glLineWidth(width) => {
+                if width <= 0.0 {
+                    return Err(Error::InvalidValue);
                 }
+                self.line.width = width;
             }
+            glLineStipple(factor, pattern) => {
+                self.line.stipple_pattern = pattern;
+                self.line.stipple_repeat = factor.clamp(1, 256) as u32;
+            }
+            glCullFace(mode) => self.polygon.cull_face_mode = Face::from_enum(mode)?,
+            glFrontFace(mode) => self.polygon.front_face = FrontFace::from_enum(mode)?,
+            glPolygonMode(face, mode) => {
+                let (face, mode) = (Face::from_enum(face)?, PolygonMode::from_enum(mode)?);
+                for &i in face.indices() {
+                    self.polygon.mode[i] = mode;
+                }
+            }
+            glPolygonOffset(factor, units) => {
+                self.polygon.offset_factor = factor;
+                self.polygon.offset_units = units;
+            }
+            glPolygonStipple(pattern) => self.polygon_stipple.pattern = pattern,
+            glFogf(pname, param) => self.fog.set(pname, &[param])?,
+            glFogi(pname, param) => self.fog.set(pname, &[param as f32])?,
+            glFogfv(pname, params) => self.fog.set(pname, &params)?,
+            glFogiv(pname, params) => self
+                .fog
+                .set(pname, &light::int_params_to_float(pname, &params))?,
+            glDrawBuffer(buffer) => {
+                if buffer != Enum::NONE {
+                    check_color_buffer(buffer)?;
+                }
+                self.color_buffer.draw_buffer = buffer;
+            }
+            glReadBuffer(buffer) => {
+                if buffer == FRONT_AND_BACK {
+                    return Err(Error::InvalidEnum);
+                }
+                check_color_buffer(buffer)?;
+                self.pixel_mode.read_buffer = buffer;
+            }
+            glClearColor(r, g, b, a) => self.color_buffer.clear_value = Vec4::new(r, g, b, a),
+            glClearIndex(c) => self.color_buffer.clear_index = c,
+            glAlphaFunc(func, reference) => {
+                self.color_buffer.alpha_func = (CompareFunc::from_enum(func)?, reference);
+            }
+            glBlendFunc(src, dst) => {
+                self.color_buffer.blend_func = BlendFactor::from_enums(src, dst)?;
+            }
+            glLogicOp(op) => self.color_buffer.logic_op = LogicOp::from_enum(op)?,
+            glColorMask(r, g, b, a) => self.color_buffer.color_writemask = [r, g, b, a],
+            glIndexMask(mask) => self.color_buffer.index_writemask = mask,
+            glStencilFunc(func, reference, mask) => {
+                self.stencil.func = CompareFunc::from_enum(func)?;
+                self.stencil.reference = reference;
+                self.stencil.value_mask = mask;
+            }
+            glStencilOp(fail, depth_fail, depth_pass) => {
+                let (fail, depth_fail, depth_pass) = (
+                    StencilOp::from_enum(fail)?,
+                    StencilOp::from_enum(depth_fail)?,
+                    StencilOp::from_enum(depth_pass)?,
+                );
+                self.stencil.fail = fail;
+                self.stencil.depth_fail = depth_fail;
+                self.stencil.depth_pass = depth_pass;
+            }
+            glStencilMask(mask) => self.stencil.writemask = mask,
+            glClearStencil(s) => self.stencil.clear_value = s,
+            glClearAccum(r, g, b, a) => {
+                let color = Vec4::new(r, g, b, a);
+                self.accum.clear_value = color.max(-Vec4::one()).min(Vec4::one());
+            }
+            glAccum(op, _) => {
+                if !matches!(op, ACCUM | LOAD | RETURN | MULT | ADD) {
+                    return Err(Error::InvalidEnum);
+                }
+                // NOTE(eddyb) there is no accumulation buffer (see `GL_ACCUM_RED_BITS`).
+                return Err(Error::InvalidOperation);
+            }
+            glPixelTransferf(pname, param) => self.pixel_mode.set_transfer(pname, param)?,
+            glPixelTransferi(pname, param) => self.pixel_mode.set_transfer(pname, param as f32)?,
+            glPixelZoom(x, y) => self.pixel_mode.zoom = (x, y),
+            glMapGrid1f(un, u1, u2) => self.map_grid1(un, [u1, u2])?,
+            glMapGrid1d(un, u1, u2) => self.map_grid1(un, [u1 as f32, u2 as f32])?,
+            glMapGrid2f(un, u1, u2, vn, v1, v2) => self.map_grid2([un, vn], [u1, u2, v1, v2])?,
+            glMapGrid2d(un, u1, u2, vn, v1, v2) => {
+                self.map_grid2([un, vn], [u1 as f32, u2 as f32, v1 as f32, v2 as f32])?
+            }
+            glClearDepth(depth) => self.depth.clear_value = depth,
+            glDepthFunc(func) => self.depth.func = CompareFunc::from_enum(func)?,
+            glDepthMask(flag) => self.depth.writemask = flag,
+            glHint(target, mode) => self.hints.set(target, mode)?,
+            glPushAttrib(mask) => self.push_attrib(mask, shared)?,
+            glPopAttrib => self.pop_attrib(shared)?,
             glMatrixMode(mode) => {
-                self.matrix_mode = match mode {
+                self.transform.matrix_mode = match mode {
                     MODELVIEW => MatrixMode::ModelView,
                     PROJECTION => MatrixMode::Projection,
-                    // FIXME(eddyb) report error
-                    _ => unimplemented!("glMatrixMode({:?})", mode),
+                    TEXTURE => MatrixMode::Texture,
+                    _ => return Err(Error::InvalidEnum),
                 };
             }
-            glPushMatrix => self.matrix_stack().push(),
-            glPopMatrix => self.matrix_stack().pop(),
+            glClipPlane(plane, equation) => {
+                let i = (plane as u32).wrapping_sub(CLIP_PLANE0 as u32) as usize;
+                if i >= self::MAX_CLIP_PLANES {
+                    return Err(Error::InvalidEnum);
+                }
+                // NOTE(eddyb) plane equations transform by the inverse transpose.
+                let [a, b, c, d] = equation;
+                let inverse = self.modelview.mat.as_f64().inverse().transpose();
+                self.transform.clip_planes[i] = inverse.mul_vec4(DVec4::new(a, b, c, d));
+            }
+            glPushMatrix => {
+                let max_depth = self.transform.matrix_mode.max_stack_depth();
+                self.matrix_stack().push(max_depth)?
+            }
+            glPopMatrix => self.matrix_stack().pop()?,
             glLoadIdentity => *self.matrix() = Mat4::identity(),
-            glRotatef(angle, x, y, z) => self.matrix_mul(Mat4::from_axis_angle(
-                Vec3::new(x, y, z),
-                angle / 180.0 * PI,
-            )),
+            glLoadMatrixf(m) => *self.matrix() = Mat4::from_cols_array(&m),
+            glLoadMatrixd(m) => *self.matrix() = DMat4::from_cols_array(&m).as_f32(),
+            glMultMatrixf(m) => self.matrix_mul(Mat4::from_cols_array(&m)),
+            glMultMatrixd(m) => self.matrix_mul_double(DMat4::from_cols_array(&m)),
+            glRotatef(angle, x, y, z) => {
+                // NOTE(eddyb) the axis is normalized, but rotating around a
+                // zero-length axis is ill-defined, so it's treated as a no-op.
+                let axis = Vec3::new(x, y, z);
+                if axis.length_squared() > 0.0 {
+                    self.matrix_mul(Mat4::from_axis_angle(axis.normalize(), angle / 180.0 * PI));
+                }
+            }
+            glRotated(angle, x, y, z) => {
+                let axis = DVec3::new(x, y, z);
+                if axis.length_squared() > 0.0 {
+                    self.matrix_mul_double(DMat4::from_axis_angle(
+                        axis.normalize(),
+                        angle.to_radians(),
+                    ));
+                }
+            }
+            glScalef(x, y, z) => self.matrix_mul(Mat4::from_scale(Vec3::new(x, y, z))),
+            glScaled(x, y, z) => self.matrix_mul_double(DMat4::from_scale(DVec3::new(x, y, z))),
             glTranslatef(x, y, z) => self.matrix_mul(Mat4::from_translation(Vec3::new(x, y, z))),
             glTranslated(x, y, z) => {
                 self.matrix_mul_double(DMat4::from_translation(DVec3::new(x, y, z)))
             }
-            glFrustum(l, r, b, t, n, f) => self.matrix_mul_double(DMat4::from_cols(
-                DVec4::new(2.0 * n / (r - l), 0.0, 0.0, 0.0),
-                DVec4::new(0.0, 2.0 * n / (t - b), 0.0, 0.0),
-                DVec4::new(
-                    (r + l) / (r - l),
-                    (t + b) / (t - b),
-                    -(f + n) / (f - n),
-                    -1.0,
-                ),
-                DVec4::new(0.0, 0.0, -(2.0 * f * n) / (f - n), 0.0),
-            )),
-            _ => unhandled(self, cmd),
+            glFrustum(l, r, b, t, n, f) => self.matrix_mul_double(frustum(l, r, b, t, n, f)?),
+            glOrtho(l, r, b, t, n, f) => self.matrix_mul_double(ortho(l, r, b, t, n, f)?),
+            glBindTexture(target, name) => {
+                let target = Target::from_enum(target)?;
+                // NOTE(eddyb) binding a name for the first time creates the
+                // texture, which then can't be bound to any other target.
+                if name != 0 {
+                    let texture = shared.textures.entry(name).or_default();
+                    match texture.target {
+                        Some(t) if t != target => return Err(Error::InvalidOperation),
+                        _ => texture.target = Some(target),
+                    }
+                }
+                *self.texturing.bindings.get_mut(target) = name;
+            }
+            glTexParameterf(target, pname, param) => {
+                let target = Target::from_enum(target)?;
+                self.texture_mut(shared, target)
+                    .params
+                    .set(pname, &[param])?
+            }
+            glTexParameteri(target, pname, param) => {
+                let target = Target::from_enum(target)?;
+                self.texture_mut(shared, target)
+                    .params
+                    .set(pname, &[param as f32])?
+            }
+            glTexParameterfv(target, pname, params) => {
+                let target = Target::from_enum(target)?;
+                self.texture_mut(shared, target)
+                    .params
+                    .set(pname, &params)?
+            }
+            glTexParameteriv(target, pname, params) => {
+                let target = Target::from_enum(target)?;
+                self.texture_mut(shared, target)
+                    .params
+                    .set(pname, &light::int_params_to_float(pname, &params))?
+            }
+            glTexImage1D(target, level, internal_format, width, border, format, _, pixels) => {
+                texture::check_format(format)?;
+                let image = Image::new(internal_format, width, None, border)?;
+                let target = (target, Target::Texture1D);
+                self.tex_image(shared, target, level, image, (format, pixels))?
+            }
+            glTexImage2D(
+                target,
+                level,
+                internal_format,
+                width,
+                height,
+                border,
+                format,
+                _,
+                pixels,
+            ) => {
+                texture::check_format(format)?;
+                let image = Image::new(internal_format, width, Some(height), border)?;
+                let target = (target, Target::Texture2D);
+                self.tex_image(shared, target, level, image, (format, pixels))?
+            }
+            glTexSubImage1D(target, level, x, width, format, _, pixels) => {
+                texture::check_format(format)?;
+                let target = (target, Target::Texture1D);
+                let pixels = (format, pixels);
+                self.tex_sub_image(shared, target, level, (x, 0), (width, 1), pixels)?
+            }
+            glTexSubImage2D(target, level, x, y, width, height, format, _, pixels) => {
+                texture::check_format(format)?;
+                let target = (target, Target::Texture2D);
+                let pixels = (format, pixels);
+                self.tex_sub_image(shared, target, level, (x, y), (width, height), pixels)?
+            }
+            glTexEnvf(target, pname, param) => self.texturing.env.set(target, pname, &[param])?,
+            glTexEnvi(target, pname, param) => {
+                self.texturing.env.set(target, pname, &[param as f32])?
+            }
+            glTexEnvfv(target, pname, params) => self.texturing.env.set(target, pname, &params)?,
+            glTexEnviv(target, pname, params) => self.texturing.env.set(
+                target,
+                pname,
+                &light::int_params_to_float(pname, &params),
+            )?,
+            glTexGend(coord, pname, param) => {
+                self.texturing
+                    .tex_gen
+                    .set(coord, pname, &[param as f32], &self.modelview.mat)?
+            }
+            glTexGenf(coord, pname, param) => {
+                self.texturing
+                    .tex_gen
+                    .set(coord, pname, &[param], &self.modelview.mat)?
+            }
+            glTexGeni(coord, pname, param) => {
+                self.texturing
+                    .tex_gen
+                    .set(coord, pname, &[param as f32], &self.modelview.mat)?
+            }
+            glTexGendv(coord, pname, params) => {
+                let params: SmallVec<[f32; 4]> = params.iter().map(|&x| x as f32).collect();
+                self.texturing
+                    .tex_gen
+                    .set(coord, pname, &params, &self.modelview.mat)?
+            }
+            glTexGenfv(coord, pname, params) => {
+                self.texturing
+                    .tex_gen
+                    .set(coord, pname, &params, &self.modelview.mat)?
+            }
+            glTexGeniv(coord, pname, params) => self.texturing.tex_gen.set(
+                coord,
+                pname,
+                &light::int_params_to_float(pname, &params),
+                &self.modelview.mat,
+            )?,
+            glPrioritizeTextures(_, names, priorities) => {
+                // NOTE(eddyb) unused names (and `0`) are silently ignored.
+                for (name, priority) in names.into_iter().zip(priorities) {
+                    if let Some(texture) = shared.textures.get_mut(&name) {
+                        texture.params.priority = priority;
+                    }
+                }
+            }
+            _ => return unhandled(self, shared, cmd),
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gl::api_1_0::{Command::*, Enum::*};
+    use std::sync::Arc;
+
+    fn apply(state: &mut State, cmd: Command) -> Result<(), Error> {
+        state.apply(cmd, &mut ShareGroup::default(), &mut |_, _, cmd| {
+            panic!("unhandled {:?}", cmd)
+        })
+    }
+
+    #[test]
+    fn pop_attrib_only_restores_pushed_groups() {
+        let mut state = State::default();
+        apply(
+            &mut state,
+            glPushAttrib(api_1_0::POINT_BIT | api_1_0::FOG_BIT),
+        )
+        .unwrap();
+        apply(&mut state, glPointSize(4.0)).unwrap();
+        apply(&mut state, glLineWidth(3.0)).unwrap();
+        apply(&mut state, glFogf(FOG_DENSITY, 0.5)).unwrap();
+        apply(&mut state, glEnable(CULL_FACE)).unwrap();
+        apply(&mut state, glPopAttrib).unwrap();
+
+        assert_eq!(state.point.size, 1.0);
+        assert_eq!(state.fog.density, 1.0);
+        assert_eq!(state.line.width, 3.0);
+        assert!(state.caps.contains(Capability::CullFace));
+
+        // Enables are restored by `GL_ENABLE_BIT`, and by their own group.
+        for &mask in &[api_1_0::ENABLE_BIT, api_1_0::POLYGON_BIT] {
+            apply(&mut state, glPushAttrib(mask)).unwrap();
+            apply(&mut state, glDisable(CULL_FACE)).unwrap();
+            apply(&mut state, glEnable(DEPTH_TEST)).unwrap();
+            apply(&mut state, glPopAttrib).unwrap();
+            assert!(state.caps.contains(Capability::CullFace));
+            assert_eq!(
+                state.caps.contains(Capability::DepthTest),
+                mask == api_1_0::POLYGON_BIT
+            );
+            apply(&mut state, glDisable(DEPTH_TEST)).unwrap();
+        }
+    }
+
+    #[test]
+    fn attrib_stack_limits() {
+        let mut state = State::default();
+        assert!(matches!(
+            apply(&mut state, glPopAttrib),
+            Err(Error::StackUnderflow)
+        ));
+        for _ in 0..super::MAX_ATTRIB_STACK_DEPTH {
+            apply(&mut state, glPushAttrib(!0)).unwrap();
+        }
+        assert!(matches!(
+            apply(&mut state, glPushAttrib(!0)),
+            Err(Error::StackOverflow)
+        ));
+        assert_eq!(state.attrib_stack_depth(), super::MAX_ATTRIB_STACK_DEPTH);
+        for _ in 0..super::MAX_ATTRIB_STACK_DEPTH {
+            apply(&mut state, glPopAttrib).unwrap();
+        }
+        assert!(matches!(
+            apply(&mut state, glPopAttrib),
+            Err(Error::StackUnderflow)
+        ));
+    }
+
+    #[test]
+    fn matrix_stack_limits() {
+        let mut state = State::default();
+        for &(mode, max_depth) in &[
+            (MODELVIEW, super::MAX_MODELVIEW_STACK_DEPTH),
+            (PROJECTION, super::MAX_PROJECTION_STACK_DEPTH),
+            (TEXTURE, super::MAX_TEXTURE_STACK_DEPTH),
+        ] {
+            apply(&mut state, glMatrixMode(mode)).unwrap();
+            assert!(matches!(
+                apply(&mut state, glPopMatrix),
+                Err(Error::StackUnderflow)
+            ));
+            for _ in 1..max_depth {
+                apply(&mut state, glPushMatrix).unwrap();
+            }
+            assert!(matches!(
+                apply(&mut state, glPushMatrix),
+                Err(Error::StackOverflow)
+            ));
+            assert_eq!(state.matrix_stack().depth(), max_depth);
+
+            // Popping restores the matrix from before the matching push.
+            apply(&mut state, glTranslatef(1.0, 2.0, 3.0)).unwrap();
+            apply(&mut state, glPopMatrix).unwrap();
+            assert_eq!(*state.matrix(), Mat4::identity());
+        }
+
+        // Each mode has its own stack.
+        assert_eq!(
+            state.modelview.depth(),
+            super::MAX_MODELVIEW_STACK_DEPTH - 1
+        );
+        assert_eq!(
+            state.projection.depth(),
+            super::MAX_PROJECTION_STACK_DEPTH - 1
+        );
+        assert_eq!(state.texture.depth(), super::MAX_TEXTURE_STACK_DEPTH - 1);
+    }
+
+    #[test]
+    fn fog_and_clip_planes() {
+        let mut state = State::default();
+        apply(&mut state, glFogi(FOG_MODE, LINEAR as i32)).unwrap();
+        assert_eq!(state.fog.mode, FogMode::Linear);
+        assert!(matches!(
+            apply(&mut state, glFogi(FOG_MODE, FOG_END as i32)),
+            Err(Error::InvalidEnum)
+        ));
+        assert!(matches!(
+            apply(&mut state, glFogf(FOG_DENSITY, -1.0)),
+            Err(Error::InvalidValue)
+        ));
+        assert!(matches!(
+            apply(&mut state, glFogf(FOG_COLOR, 0.0)),
+            Err(Error::InvalidEnum)
+        ));
+        // Integer `0` doesn't map to exactly `0.0` (see `light::int_to_color_component`).
+        apply(
+            &mut state,
+            glFogiv(FOG_COLOR, [i32::MAX, i32::MIN, i32::MIN, i32::MAX].into()),
+        )
+        .unwrap();
+        assert_eq!(state.fog.color, Vec4::unit_x() + Vec4::unit_w());
+
+        // Clip planes are transformed into eye coordinates when specified.
+        apply(&mut state, glTranslatef(0.0, 0.0, -2.0)).unwrap();
+        apply(&mut state, glClipPlane(CLIP_PLANE1, [0.0, 0.0, 1.0, 0.0])).unwrap();
+        assert_eq!(
+            state.transform.clip_planes[1],
+            DVec4::new(0.0, 0.0, 1.0, 2.0)
+        );
+        assert!(matches!(
+            apply(&mut state, glClipPlane(FOG, [0.0; 4])),
+            Err(Error::InvalidEnum)
+        ));
+    }
+
+    #[test]
+    fn pixel_transfer() {
+        let mut state = State::default();
+        apply(&mut state, glPixelTransferi(INDEX_SHIFT, -1)).unwrap();
+        apply(&mut state, glPixelTransferf(INDEX_OFFSET, 3.0)).unwrap();
+        apply(&mut state, glPixelTransferf(RED_BIAS, 0.5)).unwrap();
+        let pixel_mode = &state.pixel_mode;
+        assert_eq!(pixel_mode.transfer_stencil(5), 5.5);
+        assert_eq!(
+            pixel_mode.transfer_color(Vec4::splat(0.75)),
+            Vec4::new(1.0, 0.75, 0.75, 0.75)
+        );
+
+        // Color indices are converted to RGBA through the (default) pixel
+        // maps, instead of being scaled and biased like RGBA components.
+        assert_eq!(pixel_mode.transfer_color_index(5.0), Vec4::zero());
+
+        // The default pixel maps map everything to `0`.
+        apply(&mut state, glPixelTransferi(MAP_STENCIL, 1)).unwrap();
+        apply(&mut state, glPixelTransferi(MAP_COLOR, 1)).unwrap();
+        let pixel_mode = &state.pixel_mode;
+        assert_eq!(pixel_mode.transfer_stencil(5), 0.0);
+        assert_eq!(pixel_mode.transfer_color(Vec4::one()), Vec4::zero());
+    }
+
+    fn project(m: DMat4, v: DVec3) -> DVec3 {
+        let v = m.mul_vec4(v.extend(1.0));
+        v.truncate() / v.w
+    }
+
+    #[test]
+    fn frustum_validation() {
+        let invalid = Err(Error::InvalidValue);
+        assert_eq!(frustum(-1.0, 1.0, -1.0, 1.0, 0.0, 1.0), invalid);
+        assert_eq!(frustum(-1.0, 1.0, -1.0, 1.0, 1.0, -1.0), invalid);
+        assert_eq!(frustum(-1.0, 1.0, -1.0, 1.0, 1.0, 1.0), invalid);
+        assert_eq!(frustum(1.0, 1.0, -1.0, 1.0, 1.0, 2.0), invalid);
+        assert_eq!(frustum(-1.0, 1.0, 1.0, 1.0, 1.0, 2.0), invalid);
+    }
+
+    #[test]
+    fn frustum_maps_corners() {
+        let m = frustum(-1.0, 1.0, -2.0, 2.0, 1.0, 3.0).unwrap();
+        assert_eq!(project(m, DVec3::new(-1.0, -2.0, -1.0)), DVec3::splat(-1.0));
+        assert_eq!(project(m, DVec3::new(3.0, 6.0, -3.0)), DVec3::splat(1.0));
+    }
+
+    #[test]
+    fn ortho_validation() {
+        let invalid = Err(Error::InvalidValue);
+        assert_eq!(ortho(1.0, 1.0, -1.0, 1.0, -1.0, 1.0), invalid);
+        assert_eq!(ortho(-1.0, 1.0, 1.0, 1.0, -1.0, 1.0), invalid);
+        assert_eq!(ortho(-1.0, 1.0, -1.0, 1.0, 1.0, 1.0), invalid);
+        // Unlike `glFrustum`, negative (and zero) near/far planes are allowed.
+        assert!(ortho(-1.0, 1.0, -1.0, 1.0, -1.0, 0.0).is_ok());
+    }
+
+    #[test]
+    fn ortho_maps_corners() {
+        let m = ortho(0.0, 4.0, -2.0, 2.0, -1.0, 3.0).unwrap();
+        assert_eq!(project(m, DVec3::new(0.0, -2.0, 1.0)), DVec3::splat(-1.0));
+        assert_eq!(project(m, DVec3::new(4.0, 2.0, -3.0)), DVec3::splat(1.0));
+    }
+
+    /// Compile `cmds` into a list, flattening calls to lists in `shared`.
+    fn list(shared: &ShareGroup, cmds: &[Command]) -> Arc<DisplayList> {
+        Arc::new(DisplayList::compile(cmds, &shared.lists))
+    }
+
+    fn call(state: &mut State, name: u32, shared: &mut ShareGroup) {
+        state
+            .apply(glCallList(name), shared, &mut |_, _, cmd| {
+                panic!("unhandled {:?}", cmd)
+            })
+            .unwrap();
+    }
+
+    #[test]
+    fn called_lists_are_looked_up_when_called() {
+        let mut state = State::default();
+        let mut shared = ShareGroup::default();
+        let caller = list(&shared, &[glCallList(2)]);
+        shared.lists.insert(1, caller);
+
+        // Calling an undefined list has no effect.
+        call(&mut state, 1, &mut shared);
+        assert_eq!(state.point.size, 1.0);
+
+        let callee = list(&shared, &[glPointSize(3.0)]);
+        shared.lists.insert(2, callee);
+        call(&mut state, 1, &mut shared);
+        assert_eq!(state.point.size, 3.0);
+
+        // Lists calling a redefined list always see its latest contents, even
+        // if the old contents were flattened into them.
+        let caller = list(&shared, &[glCallList(2)]);
+        assert!(caller.flattened.is_some());
+        shared.lists.insert(1, caller);
+        let callee = list(&shared, &[glPointSize(5.0)]);
+        shared.lists.insert(2, callee);
+        call(&mut state, 1, &mut shared);
+        assert_eq!(state.point.size, 5.0);
+    }
+
+    #[test]
+    fn list_nesting_is_limited() {
+        let mut state = State::default();
+        let mut shared = ShareGroup::default();
+        let list = list(&shared, &[glTranslatef(1.0, 0.0, 0.0), glCallList(1)]);
+        shared.lists.insert(1, list);
+
+        // A list calling itself stops at the nesting limit, instead of
+        // recursing forever, and later calls aren't affected by it.
+        for i in 1..=2 {
+            call(&mut state, 1, &mut shared);
+            assert_eq!(
+                state.modelview.mat.w_axis.x,
+                (i * super::MAX_LIST_NESTING) as f32
+            );
+            assert_eq!(state.list_nesting, 0);
         }
     }
 }
@@ -0,0 +1,229 @@
+//! Texture coordinate generation (`glTexGen`).
+
+use crate::gl::api_1_0::Enum;
+use crate::gl::state::{Capabilities, Capability};
+use crate::gl::Error;
+use glam::{Mat4, Vec3, Vec4};
+use std::convert::TryFrom;
+
+pub fn params_len(pname: Enum) -> Result<usize, Error> {
+    use Enum::*;
+    match pname {
+        OBJECT_PLANE | EYE_PLANE => Ok(4),
+
+        TEXTURE_GEN_MODE => Ok(1),
+
+        _ => Err(Error::InvalidEnum),
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Mode {
+    ObjectLinear,
+    EyeLinear,
+    SphereMap,
+}
+
+impl Mode {
+    fn from_enum(mode: Enum) -> Result<Self, Error> {
+        match mode {
+            Enum::OBJECT_LINEAR => Ok(Mode::ObjectLinear),
+            Enum::EYE_LINEAR => Ok(Mode::EyeLinear),
+            Enum::SPHERE_MAP => Ok(Mode::SphereMap),
+            _ => Err(Error::InvalidEnum),
+        }
+    }
+
+    pub fn to_enum(self) -> Enum {
+        match self {
+            Mode::ObjectLinear => Enum::OBJECT_LINEAR,
+            Mode::EyeLinear => Enum::EYE_LINEAR,
+            Mode::SphereMap => Enum::SPHERE_MAP,
+        }
+    }
+}
+
+/// How one texture coordinate is generated, when enabled.
+#[derive(Copy, Clone, Debug)]
+pub struct CoordGen {
+    pub mode: Mode,
+
+    /// Plane whose dot product with the vertex position (in object
+    /// coordinates) is the generated coordinate (for `GL_OBJECT_LINEAR`).
+    pub object_plane: Vec4,
+
+    /// Plane in eye coordinates (i.e. transformed by the inverse of the
+    /// modelview matrix in effect when it was specified), used like the
+    /// object plane, but with eye coordinates (for `GL_EYE_LINEAR`).
+    pub eye_plane: Vec4,
+}
+
+/// Texture coordinate generation, for each of the `s`, `t`, `r` and `q`
+/// coordinates (only used when enabled, see `Capability::TEXTURE_GEN`).
+#[derive(Clone, Debug)]
+pub struct TexGen {
+    pub coords: [CoordGen; 4],
+}
+
+impl Default for TexGen {
+    fn default() -> Self {
+        // NOTE(eddyb) by default, `s` and `t` are the `x` and `y` coordinates.
+        let coord = |plane| CoordGen {
+            mode: Mode::EyeLinear,
+            object_plane: plane,
+            eye_plane: plane,
+        };
+        TexGen {
+            coords: [
+                coord(Vec4::unit_x()),
+                coord(Vec4::unit_y()),
+                coord(Vec4::zero()),
+                coord(Vec4::zero()),
+            ],
+        }
+    }
+}
+
+impl TexGen {
+    fn coord_index(coord: Enum) -> Result<usize, Error> {
+        use Enum::*;
+        match coord {
+            S => Ok(0),
+            T => Ok(1),
+            R => Ok(2),
+            Q => Ok(3),
+            _ => Err(Error::InvalidEnum),
+        }
+    }
+
+    pub fn coord(&self, coord: Enum) -> Result<&CoordGen, Error> {
+        Ok(&self.coords[Self::coord_index(coord)?])
+    }
+
+    pub fn set(
+        &mut self,
+        coord: Enum,
+        pname: Enum,
+        params: &[f32],
+        modelview: &Mat4,
+    ) -> Result<(), Error> {
+        let i = Self::coord_index(coord)?;
+        let gen = &mut self.coords[i];
+        if params.len() != params_len(pname)? {
+            return Err(Error::InvalidEnum);
+        }
+        let vec4 = || Vec4::new(params[0], params[1], params[2], params[3]);
+
+        use Enum::*;
+        match pname {
+            TEXTURE_GEN_MODE => {
+                // NOTE(eddyb) enum values are small enough to always be exact as `f32`.
+                let mode = Enum::try_from(params[0] as u32).map_err(|_| Error::InvalidEnum)?;
+                let mode = Mode::from_enum(mode)?;
+                // NOTE(eddyb) sphere mapping only generates `s` and `t`.
+                if mode == Mode::SphereMap && i >= 2 {
+                    return Err(Error::InvalidEnum);
+                }
+                gen.mode = mode;
+            }
+            OBJECT_PLANE => gen.object_plane = vec4(),
+            // NOTE(eddyb) transforming the plane by the inverse modelview matrix
+            // (as a row vector, hence the transpose) keeps its dot product with
+            // eye coordinates equal to that with the original coordinates.
+            EYE_PLANE => gen.eye_plane = modelview.inverse().transpose().mul_vec4(vec4()),
+            _ => unreachable!(),
+        }
+        Ok(())
+    }
+
+    /// Replace the coordinates of `texcoord` that have generation enabled
+    /// (according to `caps`), for a vertex at `object` (in object coordinates)
+    /// and `eye` (in eye coordinates), with its `normal` (in eye coordinates),
+    /// which is only computed if needed (i.e. for `GL_SPHERE_MAP`).
+    pub fn generate(
+        &self,
+        caps: &Capabilities,
+        mut texcoord: Vec4,
+        object: Vec4,
+        eye: Vec4,
+        normal: impl FnOnce() -> Vec3,
+    ) -> Vec4 {
+        let enabled = |i: usize| caps.contains(Capability::TEXTURE_GEN[i]);
+        let sphere_map = if (0..2).any(|i| enabled(i) && self.coords[i].mode == Mode::SphereMap) {
+            Some(sphere_map_coords(eye, normal()))
+        } else {
+            None
+        };
+        for (i, gen) in self.coords.iter().enumerate() {
+            if !enabled(i) {
+                continue;
+            }
+            texcoord.as_mut()[i] = match gen.mode {
+                Mode::ObjectLinear => gen.object_plane.dot(object),
+                Mode::EyeLinear => gen.eye_plane.dot(eye),
+                Mode::SphereMap => {
+                    let (s, t) = sphere_map.unwrap();
+                    [s, t][i]
+                }
+            };
+        }
+        texcoord
+    }
+}
+
+/// The `(s, t)` coordinates generated by `GL_SPHERE_MAP`, which are those of
+/// the reflection of the direction towards `eye` (around `normal`), mapped
+/// onto a sphere, as seen from infinitely far away (along the `z` axis).
+fn sphere_map_coords(eye: Vec4, normal: Vec3) -> (f32, f32) {
+    let u = eye.truncate().normalize();
+    let r = u - 2.0 * normal * normal.dot(u);
+    let m = 2.0 * (r.x * r.x + r.y * r.y + (r.z + 1.0) * (r.z + 1.0)).sqrt();
+    (r.x / m + 0.5, r.y / m + 0.5)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn set_validation() {
+        let mut gen = TexGen::default();
+        let modelview = Mat4::identity();
+        let sphere_map = [Enum::SPHERE_MAP as u32 as f32];
+        let plane = [0.0; 4];
+        assert_eq!(
+            gen.set(Enum::S, Enum::EYE_PLANE, &plane[..1], &modelview),
+            Err(Error::InvalidEnum)
+        );
+        assert_eq!(
+            gen.set(Enum::RGBA, Enum::OBJECT_PLANE, &plane, &modelview),
+            Err(Error::InvalidEnum)
+        );
+        assert_eq!(
+            gen.set(Enum::R, Enum::TEXTURE_GEN_MODE, &sphere_map, &modelview),
+            Err(Error::InvalidEnum)
+        );
+        assert_eq!(
+            gen.set(Enum::T, Enum::TEXTURE_GEN_MODE, &sphere_map, &modelview),
+            Ok(())
+        );
+        assert_eq!(gen.coord(Enum::T).unwrap().mode, Mode::SphereMap);
+    }
+
+    #[test]
+    fn eye_plane_follows_the_modelview() {
+        let modelview =
+            Mat4::from_translation(Vec3::new(1.0, 2.0, 3.0)) * Mat4::from_scale(Vec3::splat(2.0));
+        let plane = Vec4::new(1.0, -1.0, 0.5, 2.0);
+        let mut gen = TexGen::default();
+        gen.set(Enum::Q, Enum::EYE_PLANE, plane.as_ref(), &modelview)
+            .unwrap();
+
+        // The eye plane, applied to eye coordinates, gives the same result as
+        // the original plane applied to object coordinates.
+        let object = Vec4::new(3.0, -1.0, 2.0, 1.0);
+        let eye = modelview.mul_vec4(object);
+        let eye_plane = gen.coord(Enum::Q).unwrap().eye_plane;
+        assert!((eye_plane.dot(eye) - plane.dot(object)).abs() < 1e-5);
+    }
+}
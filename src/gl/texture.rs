@@ -0,0 +1,899 @@
+//! Texture objects (`glGenTextures`, `glBindTexture`), their parameters
+//! (`glTexParameter`) and images, and the texture environment (`glTexEnv`).
+
+use crate::gl::api_1_0::Enum;
+use crate::gl::{raster, Error};
+use glam::{Vec2, Vec3, Vec4};
+use indexmap::IndexMap;
+use std::convert::TryFrom;
+use std::iter;
+
+/// Maximum width and height of texture images (not counting the border).
+pub const MAX_TEXTURE_SIZE: u32 = 2048;
+
+/// Number of mipmap levels, for a texture of the maximum size.
+pub const MAX_LEVELS: usize = MAX_TEXTURE_SIZE.trailing_zeros() as usize + 1;
+
+/// Find `count` names not in `textures`, starting with the lowest available.
+pub fn find_unused_names(textures: &IndexMap<u32, Texture>, count: u32) -> Vec<u32> {
+    (1..=u32::MAX)
+        .filter(|n| !textures.contains_key(n))
+        .take(count as usize)
+        .collect()
+}
+
+pub fn params_len(pname: Enum) -> Result<usize, Error> {
+    use Enum::*;
+    match pname {
+        TEXTURE_BORDER_COLOR => Ok(4),
+
+        TEXTURE_MIN_FILTER | TEXTURE_MAG_FILTER | TEXTURE_WRAP_S | TEXTURE_WRAP_T
+        | TEXTURE_PRIORITY => Ok(1),
+
+        _ => Err(Error::InvalidEnum),
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Target {
+    Texture1D,
+    Texture2D,
+}
+
+impl Target {
+    pub fn from_enum(target: Enum) -> Result<Self, Error> {
+        match target {
+            Enum::TEXTURE_1D => Ok(Target::Texture1D),
+            Enum::TEXTURE_2D => Ok(Target::Texture2D),
+            _ => Err(Error::InvalidEnum),
+        }
+    }
+
+    /// Like `from_enum`, but also accepting proxy targets (returning `true`
+    /// alongside the target they're a proxy for).
+    pub fn from_enum_or_proxy(target: Enum) -> Result<(Self, bool), Error> {
+        match target {
+            Enum::PROXY_TEXTURE_1D => Ok((Target::Texture1D, true)),
+            Enum::PROXY_TEXTURE_2D => Ok((Target::Texture2D, true)),
+            _ => Ok((Self::from_enum(target)?, false)),
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Filter {
+    Nearest,
+    Linear,
+    NearestMipmapNearest,
+    LinearMipmapNearest,
+    NearestMipmapLinear,
+    LinearMipmapLinear,
+}
+
+impl Filter {
+    fn from_enum(filter: Enum) -> Result<Self, Error> {
+        use Enum::*;
+        Ok(match filter {
+            NEAREST => Filter::Nearest,
+            LINEAR => Filter::Linear,
+            NEAREST_MIPMAP_NEAREST => Filter::NearestMipmapNearest,
+            LINEAR_MIPMAP_NEAREST => Filter::LinearMipmapNearest,
+            NEAREST_MIPMAP_LINEAR => Filter::NearestMipmapLinear,
+            LINEAR_MIPMAP_LINEAR => Filter::LinearMipmapLinear,
+            _ => return Err(Error::InvalidEnum),
+        })
+    }
+
+    pub fn to_enum(self) -> Enum {
+        match self {
+            Filter::Nearest => Enum::NEAREST,
+            Filter::Linear => Enum::LINEAR,
+            Filter::NearestMipmapNearest => Enum::NEAREST_MIPMAP_NEAREST,
+            Filter::LinearMipmapNearest => Enum::LINEAR_MIPMAP_NEAREST,
+            Filter::NearestMipmapLinear => Enum::NEAREST_MIPMAP_LINEAR,
+            Filter::LinearMipmapLinear => Enum::LINEAR_MIPMAP_LINEAR,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Wrap {
+    Clamp,
+    Repeat,
+}
+
+impl Wrap {
+    fn from_enum(wrap: Enum) -> Result<Self, Error> {
+        match wrap {
+            Enum::CLAMP => Ok(Wrap::Clamp),
+            Enum::REPEAT => Ok(Wrap::Repeat),
+            _ => Err(Error::InvalidEnum),
+        }
+    }
+
+    pub fn to_enum(self) -> Enum {
+        match self {
+            Wrap::Clamp => Enum::CLAMP,
+            Wrap::Repeat => Enum::REPEAT,
+        }
+    }
+}
+
+/// Parameters set by `glTexParameter`.
+#[derive(Copy, Clone, Debug)]
+pub struct Params {
+    pub min_filter: Filter,
+
+    /// Always either `Filter::Nearest` or `Filter::Linear`.
+    pub mag_filter: Filter,
+
+    pub wrap_s: Wrap,
+    pub wrap_t: Wrap,
+    pub border_color: Vec4,
+    pub priority: f32,
+}
+
+impl Default for Params {
+    fn default() -> Self {
+        Params {
+            min_filter: Filter::NearestMipmapLinear,
+            mag_filter: Filter::Linear,
+            wrap_s: Wrap::Repeat,
+            wrap_t: Wrap::Repeat,
+            border_color: Vec4::zero(),
+            priority: 1.0,
+        }
+    }
+}
+
+impl Params {
+    pub fn set(&mut self, pname: Enum, params: &[f32]) -> Result<(), Error> {
+        if params.len() != params_len(pname)? {
+            return Err(Error::InvalidEnum);
+        }
+        // NOTE(eddyb) enum values are small enough to always be exact as `f32`.
+        let enum_param = || Enum::try_from(params[0] as u32).map_err(|_| Error::InvalidEnum);
+
+        use Enum::*;
+        match pname {
+            TEXTURE_MIN_FILTER => self.min_filter = Filter::from_enum(enum_param()?)?,
+            TEXTURE_MAG_FILTER => {
+                self.mag_filter = match Filter::from_enum(enum_param()?)? {
+                    filter @ Filter::Nearest | filter @ Filter::Linear => filter,
+                    _ => return Err(Error::InvalidEnum),
+                };
+            }
+            TEXTURE_WRAP_S => self.wrap_s = Wrap::from_enum(enum_param()?)?,
+            TEXTURE_WRAP_T => self.wrap_t = Wrap::from_enum(enum_param()?)?,
+            TEXTURE_BORDER_COLOR => {
+                let color = Vec4::new(params[0], params[1], params[2], params[3]);
+                self.border_color = color.max(Vec4::zero()).min(Vec4::one());
+            }
+            TEXTURE_PRIORITY => self.priority = params[0].clamp(0.0, 1.0),
+            _ => unreachable!(),
+        }
+        Ok(())
+    }
+}
+
+pub fn env_params_len(pname: Enum) -> Result<usize, Error> {
+    match pname {
+        Enum::TEXTURE_ENV_MODE => Ok(1),
+        Enum::TEXTURE_ENV_COLOR => Ok(4),
+        _ => Err(Error::InvalidEnum),
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum EnvMode {
+    Modulate,
+    Decal,
+    Blend,
+    Replace,
+}
+
+impl EnvMode {
+    fn from_enum(mode: Enum) -> Result<Self, Error> {
+        use Enum::*;
+        Ok(match mode {
+            MODULATE => EnvMode::Modulate,
+            DECAL => EnvMode::Decal,
+            BLEND => EnvMode::Blend,
+            REPLACE => EnvMode::Replace,
+            _ => return Err(Error::InvalidEnum),
+        })
+    }
+
+    pub fn to_enum(self) -> Enum {
+        match self {
+            EnvMode::Modulate => Enum::MODULATE,
+            EnvMode::Decal => Enum::DECAL,
+            EnvMode::Blend => Enum::BLEND,
+            EnvMode::Replace => Enum::REPLACE,
+        }
+    }
+}
+
+/// The texture environment (set by `glTexEnv`), which determines how texels
+/// are combined with the colors of the fragments they're applied to.
+#[derive(Copy, Clone, Debug)]
+pub struct Env {
+    pub mode: EnvMode,
+    pub color: Vec4,
+}
+
+impl Default for Env {
+    fn default() -> Self {
+        Env {
+            mode: EnvMode::Modulate,
+            color: Vec4::zero(),
+        }
+    }
+}
+
+impl Env {
+    pub fn set(&mut self, target: Enum, pname: Enum, params: &[f32]) -> Result<(), Error> {
+        if target != Enum::TEXTURE_ENV || params.len() != env_params_len(pname)? {
+            return Err(Error::InvalidEnum);
+        }
+        match pname {
+            Enum::TEXTURE_ENV_MODE => {
+                // NOTE(eddyb) enum values are small enough to always be exact as `f32`.
+                let mode = Enum::try_from(params[0] as u32).map_err(|_| Error::InvalidEnum)?;
+                self.mode = EnvMode::from_enum(mode)?;
+            }
+            Enum::TEXTURE_ENV_COLOR => {
+                let color = Vec4::new(params[0], params[1], params[2], params[3]);
+                self.color = color.max(Vec4::zero()).min(Vec4::one());
+            }
+            _ => unreachable!(),
+        }
+        Ok(())
+    }
+
+    /// Combine the `color` of a fragment with a `texel` (see `Texture::sample`)
+    /// from a texture with the base internal format `format`.
+    pub fn apply(&self, format: Enum, color: Vec4, texel: Vec4) -> Vec4 {
+        use Enum::*;
+
+        // NOTE(eddyb) texels have all their components filled in (see
+        // `Image::texels`), so e.g. luminance and intensity texels can be
+        // treated as RGB(A) ones, and only missing components need care.
+        let has_color = format != ALPHA;
+        let has_alpha = matches!(format, ALPHA | LUMINANCE_ALPHA | INTENSITY | RGBA);
+        let rgb = |v: Vec4| v.truncate();
+        let with_alpha = |c: Vec3, a: f32| c.extend(a);
+
+        let (c, a) = match self.mode {
+            EnvMode::Replace => (texel, texel),
+            EnvMode::Modulate => (color * texel, color * texel),
+            EnvMode::Decal => match format {
+                RGB | RGBA => {
+                    let c = rgb(color).lerp(rgb(texel), texel.w);
+                    return with_alpha(c, color.w);
+                }
+                // NOTE(eddyb) the result is undefined for other formats.
+                _ => return color,
+            },
+            EnvMode::Blend => {
+                let c = color * (Vec4::one() - texel) + self.color * texel;
+                (
+                    c,
+                    if format == INTENSITY {
+                        c
+                    } else {
+                        color * texel
+                    },
+                )
+            }
+        };
+        with_alpha(
+            if has_color { rgb(c) } else { rgb(color) },
+            if has_alpha { a.w } else { color.w },
+        )
+    }
+}
+
+/// The base internal format (e.g. `GL_RGB`) for an internal format accepted
+/// by `glTexImage*` (which can also be e.g. `GL_RGB8`, or `3`).
+pub fn base_internal_format(internal_format: i32) -> Result<Enum, Error> {
+    use Enum::*;
+    let internal_format = match internal_format {
+        1 => return Ok(LUMINANCE),
+        2 => return Ok(LUMINANCE_ALPHA),
+        3 => return Ok(RGB),
+        4 => return Ok(RGBA),
+        _ => Enum::try_from(internal_format as u32).map_err(|_| Error::InvalidValue)?,
+    };
+    Ok(match internal_format {
+        ALPHA | ALPHA4 | ALPHA8 | ALPHA12 | ALPHA16 => ALPHA,
+        LUMINANCE | LUMINANCE4 | LUMINANCE8 | LUMINANCE12 | LUMINANCE16 => LUMINANCE,
+        LUMINANCE_ALPHA | LUMINANCE4_ALPHA4 | LUMINANCE6_ALPHA2 | LUMINANCE8_ALPHA8
+        | LUMINANCE12_ALPHA4 | LUMINANCE12_ALPHA12 | LUMINANCE16_ALPHA16 => LUMINANCE_ALPHA,
+        INTENSITY | INTENSITY4 | INTENSITY8 | INTENSITY12 | INTENSITY16 => INTENSITY,
+        RGB | R3_G3_B2 | RGB4 | RGB5 | RGB8 | RGB10 | RGB12 | RGB16 => RGB,
+        RGBA | RGBA2 | RGBA4 | RGB5_A1 | RGBA8 | RGB10_A2 | RGBA12 | RGBA16 => RGBA,
+        _ => return Err(Error::InvalidValue),
+    })
+}
+
+/// Check that `format` is valid for texture images (i.e. a color format).
+pub fn check_format(format: Enum) -> Result<(), Error> {
+    use Enum::*;
+    match format {
+        COLOR_INDEX | RED | GREEN | BLUE | ALPHA | RGB | RGBA | LUMINANCE | LUMINANCE_ALPHA => {
+            Ok(())
+        }
+        _ => Err(Error::InvalidEnum),
+    }
+}
+
+/// Check that `size` (a width or height, including the border) is of the
+/// form `2^n + 2 * border`.
+fn is_valid_size(size: u32, border: u32) -> bool {
+    size.checked_sub(2 * border)
+        .is_some_and(u32::is_power_of_two)
+}
+
+/// One mipmap level of a texture.
+#[derive(Clone, Debug)]
+pub struct Image {
+    /// Width and height, including the border (i.e. `2^n + 2 * border`).
+    pub width: u32,
+    pub height: u32,
+    pub border: u32,
+
+    /// The internal format, exactly as it was specified.
+    pub internal_format: i32,
+
+    /// RGBA texels (with rows ordered bottom-to-top), with any components
+    /// missing from the internal format filled in as they would be when the
+    /// texture is applied (e.g. `(L, L, L, 1)` for `GL_LUMINANCE`).
+    pub texels: Vec<[u8; 4]>,
+}
+
+static EMPTY_IMAGE: Image = Image {
+    width: 0,
+    height: 0,
+    border: 0,
+    internal_format: 1,
+    texels: Vec::new(),
+};
+
+impl Default for Image {
+    fn default() -> Self {
+        EMPTY_IMAGE.clone()
+    }
+}
+
+impl Image {
+    /// Create an image (without any texels, see `Image::fill`), with `height`
+    /// being `None` for 1D images (which have no border above or below).
+    pub fn new(
+        internal_format: i32,
+        width: u32,
+        height: Option<u32>,
+        border: i32,
+    ) -> Result<Self, Error> {
+        base_internal_format(internal_format)?;
+        let border = match border {
+            0 | 1 => border as u32,
+            _ => return Err(Error::InvalidValue),
+        };
+        if !is_valid_size(width, border) || !height.is_none_or(|h| is_valid_size(h, border)) {
+            return Err(Error::InvalidValue);
+        }
+        Ok(Image {
+            width,
+            height: height.unwrap_or(1),
+            border,
+            internal_format,
+            texels: vec![],
+        })
+    }
+
+    pub fn base_internal_format(&self) -> Enum {
+        base_internal_format(self.internal_format).unwrap()
+    }
+
+    /// Width and height, without the border (with the height always being `1`
+    /// for 1D images, which have no border above or below).
+    fn inner_size(&self, target: Target) -> (u32, u32) {
+        let width = self.width - 2 * self.border;
+        match target {
+            Target::Texture1D => (width, 1),
+            Target::Texture2D => (width, self.height - 2 * self.border),
+        }
+    }
+
+    /// Whether the image is larger than `MAX_TEXTURE_SIZE` (without the border).
+    pub fn is_too_large(&self) -> bool {
+        self.width - 2 * self.border > MAX_TEXTURE_SIZE
+            || self.height.saturating_sub(2 * self.border) > MAX_TEXTURE_SIZE
+    }
+
+    /// Convert an unpacked RGBA pixel (see `pixel::unpack`) to a texel.
+    fn texel(&self, pixel: Vec4) -> [u8; 4] {
+        use Enum::*;
+        let p = pixel;
+        raster::color_to_rgba8(match self.base_internal_format() {
+            ALPHA => Vec4::new(0.0, 0.0, 0.0, p.w),
+            LUMINANCE => Vec4::new(p.x, p.x, p.x, 1.0),
+            LUMINANCE_ALPHA => Vec4::new(p.x, p.x, p.x, p.w),
+            INTENSITY => Vec4::splat(p.x),
+            RGB => Vec4::new(p.x, p.y, p.z, 1.0),
+            RGBA => p,
+            _ => unreachable!(),
+        })
+    }
+
+    /// Set all the texels from unpacked RGBA pixels (see `pixel::unpack`),
+    /// or to zero, if `pixels` is `None` (as their contents are undefined).
+    pub fn fill(&mut self, pixels: Option<&[Vec4]>) {
+        self.texels = match pixels {
+            Some(pixels) => pixels.iter().map(|&p| self.texel(p)).collect(),
+            None => vec![[0; 4]; self.width as usize * self.height as usize],
+        };
+    }
+
+    /// The RGBA color of the texel at `(x, y)` (counting the border as part of
+    /// the image), in the form returned by `glGetTexImage` (e.g. `(L, 0, 0, 1)`
+    /// for `GL_LUMINANCE`).
+    pub fn read_back(&self, x: u32, y: u32) -> Vec4 {
+        let [r, g, b, a] = self.texels[(y * self.width + x) as usize];
+        let t = Vec4::new(r as f32, g as f32, b as f32, a as f32) / 255.0;
+        use Enum::*;
+        match self.base_internal_format() {
+            LUMINANCE | INTENSITY => Vec4::new(t.x, 0.0, 0.0, 1.0),
+            LUMINANCE_ALPHA => Vec4::new(t.x, 0.0, 0.0, t.w),
+            _ => t,
+        }
+    }
+
+    /// Number of bits used to store the component given by `pname`
+    /// (e.g. `GL_TEXTURE_RED_SIZE`), which is `0` for missing components.
+    pub fn component_size(&self, pname: Enum) -> u32 {
+        if self.width == 0 {
+            return 0;
+        }
+        use Enum::*;
+        let base = self.base_internal_format();
+        let present = match pname {
+            TEXTURE_RED_SIZE | TEXTURE_GREEN_SIZE | TEXTURE_BLUE_SIZE => {
+                matches!(base, RGB | RGBA)
+            }
+            TEXTURE_ALPHA_SIZE => matches!(base, ALPHA | LUMINANCE_ALPHA | RGBA),
+            TEXTURE_LUMINANCE_SIZE => matches!(base, LUMINANCE | LUMINANCE_ALPHA),
+            TEXTURE_INTENSITY_SIZE => base == INTENSITY,
+            _ => false,
+        };
+        // NOTE(eddyb) all components are stored with 8 bits, regardless of
+        // any sizes requested by the internal format.
+        if present {
+            8
+        } else {
+            0
+        }
+    }
+}
+
+/// A texture object, i.e. parameters and mipmap levels.
+#[derive(Clone, Debug, Default)]
+pub struct Texture {
+    /// The target this texture was first bound to, which it can't be used
+    /// with any other target afterwards (or `None`, if it was never bound,
+    /// and its name was only reserved by `glGenTextures`).
+    pub target: Option<Target>,
+
+    pub params: Params,
+
+    /// Mipmap levels, starting with the base level (`0`), with any
+    /// levels past the end treated as empty (see `Texture::level`).
+    pub levels: Vec<Image>,
+}
+
+impl Texture {
+    pub fn level(&self, level: usize) -> &Image {
+        self.levels.get(level).unwrap_or(&EMPTY_IMAGE)
+    }
+
+    /// Replace mipmap `level` with `image` (see `glTexImage1D`/`glTexImage2D`).
+    pub fn set_image(&mut self, level: usize, image: Image) {
+        if self.levels.len() <= level {
+            self.levels.resize_with(level + 1, Image::default);
+        }
+        self.levels[level] = image;
+    }
+
+    /// Replace the `width`x`height` region at `(x, y)` (relative to the image,
+    /// not its border) of mipmap `level`, with unpacked RGBA `pixels` (see
+    /// `glTexSubImage1D`/`glTexSubImage2D`, and `pixel::unpack`).
+    pub fn set_sub_image(
+        &mut self,
+        target: Target,
+        level: usize,
+        (x, y): (i32, i32),
+        (width, height): (u32, u32),
+        pixels: &[Vec4],
+    ) -> Result<(), Error> {
+        let image = self
+            .levels
+            .get_mut(level)
+            .filter(|image| image.width != 0)
+            .ok_or(Error::InvalidOperation)?;
+        let border = image.border as i64;
+        let border_y = match target {
+            Target::Texture1D => 0,
+            Target::Texture2D => border,
+        };
+        let (x, y) = (x as i64 + border, y as i64 + border_y);
+        if x < 0
+            || y < 0
+            || x + width as i64 > image.width as i64
+            || y + height as i64 > image.height as i64
+        {
+            return Err(Error::InvalidValue);
+        }
+
+        if width == 0 {
+            return Ok(());
+        }
+        let (x, y) = (x as usize, y as usize);
+        for (j, row) in pixels.chunks_exact(width as usize).enumerate() {
+            for (i, &pixel) in row.iter().enumerate() {
+                let texel = image.texel(pixel);
+                image.texels[(y + j) * image.width as usize + x + i] = texel;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Texel indices (relative to the image, not its border) along one axis, of
+/// `size` texels, for the texture coordinate `s`, as a pair of texels and the
+/// weight of the second one (with `linear`), or just the nearest texel.
+fn texel_indices(s: f32, size: u32, wrap: Wrap, linear: bool) -> (i64, i64, f32) {
+    let size = size as i64;
+    let s = match wrap {
+        Wrap::Clamp => s.clamp(0.0, 1.0),
+        Wrap::Repeat => s - s.floor(),
+    };
+    let wrap = |i: i64| match wrap {
+        // NOTE(eddyb) out of range indices refer to the border (if any).
+        Wrap::Clamp => i,
+        Wrap::Repeat => i.rem_euclid(size),
+    };
+    let u = s * size as f32;
+    if linear {
+        let u = u - 0.5;
+        let i = u.floor();
+        (wrap(i as i64), wrap(i as i64 + 1), u - i)
+    } else {
+        // NOTE(eddyb) `s = 1` would otherwise be just past the last texel.
+        let i = wrap((u.floor() as i64).min(size - 1));
+        (i, i, 0.0)
+    }
+}
+
+impl Texture {
+    /// Number of mipmap levels needed for the texture to be complete (i.e.
+    /// until the `1x1` level), based on the size of its base level.
+    fn mipmap_levels(&self, target: Target) -> usize {
+        let (width, height) = self.level(0).inner_size(target);
+        width.max(height).trailing_zeros() as usize + 1
+    }
+
+    /// Whether the texture can be applied (with its current minification
+    /// filter), which requires its base level to be defined and, if the
+    /// filter uses mipmaps, all the levels down to `1x1` to be defined, each
+    /// half the size of the previous one, and with the same border and
+    /// internal format as the base level.
+    pub fn is_complete(&self, target: Target) -> bool {
+        let base = self.level(0);
+        if base.width == 0 {
+            return false;
+        }
+        if let Filter::Nearest | Filter::Linear = self.params.min_filter {
+            return true;
+        }
+        let (width, height) = base.inner_size(target);
+        (1..self.mipmap_levels(target)).all(|level| {
+            let image = self.level(level);
+            image.width != 0
+                && image.border == base.border
+                && image.internal_format == base.internal_format
+                && image.inner_size(target) == ((width >> level).max(1), (height >> level).max(1))
+        })
+    }
+
+    /// The level of detail ("lambda"), i.e. the base 2 logarithm of the scale
+    /// factor between texels (of the base level) and pixels, given the rates
+    /// of change of `(s, t)`, along the window `x` and `y` axes.
+    pub fn lod(&self, target: Target, d_dx: Vec2, d_dy: Vec2) -> f32 {
+        let (width, height) = self.level(0).inner_size(target);
+        let scale = match target {
+            Target::Texture1D => Vec2::new(width as f32, 0.0),
+            Target::Texture2D => Vec2::new(width as f32, height as f32),
+        };
+        let rho = (d_dx * scale).length().max((d_dy * scale).length());
+        rho.log2()
+    }
+
+    /// Sample the (complete, see `Texture::is_complete`) texture at `(s, t)`,
+    /// with `lambda` being the level of detail (see `Texture::lod`), which
+    /// determines whether the texture is magnified or minified, and which
+    /// mipmap levels are used (for the minification filters using them).
+    pub fn sample(&self, target: Target, st: Vec2, lambda: f32) -> Vec4 {
+        use Filter::*;
+        let Params {
+            min_filter,
+            mag_filter,
+            ..
+        } = self.params;
+
+        // NOTE(eddyb) with `GL_LINEAR` magnification, and minification using
+        // the nearest texel (of one or two mipmaps), switching over at `0.5`
+        // (instead of `0`) ensures minification can't ever be sharper than
+        // magnification.
+        let c = match (mag_filter, min_filter) {
+            (Linear, NearestMipmapNearest) | (Linear, NearestMipmapLinear) => 0.5,
+            _ => 0.0,
+        };
+        if lambda <= c {
+            return self.sample_level(target, 0, st, mag_filter == Linear);
+        }
+
+        let linear = matches!(
+            min_filter,
+            Linear | LinearMipmapNearest | LinearMipmapLinear
+        );
+        let last = self.mipmap_levels(target) - 1;
+        match min_filter {
+            Nearest | Linear => self.sample_level(target, 0, st, linear),
+            NearestMipmapNearest | LinearMipmapNearest => {
+                let level = if lambda <= 0.5 {
+                    0
+                } else {
+                    ((lambda + 0.5).ceil() - 1.0) as usize
+                };
+                self.sample_level(target, level.min(last), st, linear)
+            }
+            NearestMipmapLinear | LinearMipmapLinear => {
+                let level = (lambda.floor() as usize).min(last);
+                let a = self.sample_level(target, level, st, linear);
+                if level == last {
+                    return a;
+                }
+                let b = self.sample_level(target, level + 1, st, linear);
+                a.lerp(b, lambda.fract())
+            }
+        }
+    }
+
+    /// Sample mipmap `level` at `(s, t)`, using either the nearest texel, or
+    /// (with `linear`) a weighted average of the nearest 2x2 texels (or 2
+    /// texels, for 1D textures), with texels outside the image (and its border)
+    /// having the border color (`GL_TEXTURE_BORDER_COLOR`).
+    fn sample_level(&self, target: Target, level: usize, st: Vec2, linear: bool) -> Vec4 {
+        let image = self.level(level);
+        let (width, height) = image.inner_size(target);
+        let border_y = match target {
+            Target::Texture1D => 0,
+            Target::Texture2D => image.border as i64,
+        };
+        let texel = |i: i64, j: i64| {
+            let (x, y) = (i + image.border as i64, j + border_y);
+            if x < 0 || y < 0 || x >= image.width as i64 || y >= image.height as i64 {
+                return self.params.border_color;
+            }
+            let [r, g, b, a] = image.texels[(y * image.width as i64 + x) as usize];
+            Vec4::new(r as f32, g as f32, b as f32, a as f32) / 255.0
+        };
+
+        let (i0, i1, a) = texel_indices(st.x, width, self.params.wrap_s, linear);
+        let (j0, j1, b) = match target {
+            Target::Texture1D => (0, 0, 0.0),
+            Target::Texture2D => texel_indices(st.y, height, self.params.wrap_t, linear),
+        };
+        if !linear {
+            return texel(i0, j0);
+        }
+        let lo = texel(i0, j0).lerp(texel(i1, j0), a);
+        let hi = texel(i0, j1).lerp(texel(i1, j1), a);
+        lo.lerp(hi, b)
+    }
+}
+
+/// Per-target state (e.g. which textures are bound to each target).
+#[derive(Clone, Debug, Default)]
+pub struct PerTarget<T> {
+    pub texture_1d: T,
+    pub texture_2d: T,
+}
+
+impl<T> PerTarget<T> {
+    pub fn get(&self, target: Target) -> &T {
+        match target {
+            Target::Texture1D => &self.texture_1d,
+            Target::Texture2D => &self.texture_2d,
+        }
+    }
+
+    pub fn get_mut(&mut self, target: Target) -> &mut T {
+        match target {
+            Target::Texture1D => &mut self.texture_1d,
+            Target::Texture2D => &mut self.texture_2d,
+        }
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = &mut T> {
+        iter::once(&mut self.texture_1d).chain(iter::once(&mut self.texture_2d))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn nearest_texel_indices() {
+        assert_eq!(texel_indices(0.3, 4, Wrap::Clamp, false), (1, 1, 0.0));
+        assert_eq!(texel_indices(1.0, 4, Wrap::Clamp, false), (3, 3, 0.0));
+        assert_eq!(texel_indices(-0.5, 4, Wrap::Clamp, false), (0, 0, 0.0));
+        assert_eq!(texel_indices(1.25, 4, Wrap::Repeat, false), (1, 1, 0.0));
+        assert_eq!(texel_indices(-0.25, 4, Wrap::Repeat, false), (3, 3, 0.0));
+    }
+
+    #[test]
+    fn linear_texel_indices() {
+        assert_eq!(texel_indices(0.5, 4, Wrap::Clamp, true), (1, 2, 0.5));
+        assert_eq!(texel_indices(0.375, 4, Wrap::Repeat, true), (1, 2, 0.0));
+        // Clamping reaches into the border, while repeating wraps around.
+        assert_eq!(texel_indices(0.0, 4, Wrap::Clamp, true), (-1, 0, 0.5));
+        assert_eq!(texel_indices(1.0, 4, Wrap::Clamp, true), (3, 4, 0.5));
+        assert_eq!(texel_indices(0.0, 4, Wrap::Repeat, true), (3, 0, 0.5));
+    }
+
+    fn texture(sizes: &[(u32, u32)]) -> Texture {
+        let mut texture = Texture::default();
+        for (level, &(width, height)) in sizes.iter().enumerate() {
+            let image = Image::new(Enum::RGBA as i32, width, Some(height), 0).unwrap();
+            texture.set_image(level, image);
+        }
+        texture
+    }
+
+    #[test]
+    fn mipmap_completeness() {
+        let target = Target::Texture2D;
+        assert!(!texture(&[]).is_complete(target));
+        assert!(texture(&[(4, 2), (2, 1), (1, 1)]).is_complete(target));
+        assert!(!texture(&[(4, 2), (2, 1)]).is_complete(target));
+        assert!(!texture(&[(4, 2), (2, 2), (1, 1)]).is_complete(target));
+
+        let mut mismatched = texture(&[(4, 2), (2, 1), (1, 1)]);
+        mismatched.set_image(1, Image::new(Enum::RGB as i32, 2, Some(1), 0).unwrap());
+        assert!(!mismatched.is_complete(target));
+
+        // Only the base level is needed without mipmapping.
+        let mut base_only = texture(&[(4, 2)]);
+        base_only.params.min_filter = Filter::Linear;
+        assert!(base_only.is_complete(target));
+
+        // 1D textures ignore the height (always `1`) of their levels.
+        assert!(texture(&[(4, 1), (2, 1), (1, 1)]).is_complete(Target::Texture1D));
+    }
+
+    #[test]
+    fn magnification_switchover() {
+        // A 2x2 base level with a single white texel, and a grey 1x1 mipmap.
+        let mut texture = texture(&[(2, 2), (1, 1)]);
+        let mut base = texture.level(0).clone();
+        base.texels = vec![[255; 4], [0; 4], [0; 4], [0; 4]];
+        texture.set_image(0, base);
+        let mut mipmap = texture.level(1).clone();
+        mipmap.texels = vec![[128; 4]];
+        texture.set_image(1, mipmap);
+
+        // Linear magnification averages all 4 base texels, while the nearest
+        // base texel (to the center) is black.
+        let sample = |texture: &Texture, lambda| {
+            texture
+                .sample(Target::Texture2D, Vec2::new(0.5, 0.5), lambda)
+                .x
+        };
+        let magnified = 0.25;
+        for &min_filter in &[Filter::NearestMipmapNearest, Filter::NearestMipmapLinear] {
+            texture.params.min_filter = min_filter;
+            for &lambda in &[0.0, 0.25, 0.5] {
+                assert_eq!(sample(&texture, lambda), magnified, "{:?}", min_filter);
+            }
+            assert_ne!(sample(&texture, 0.75), magnified, "{:?}", min_filter);
+        }
+
+        // With linear filtering within mipmaps, minification starts at `0`.
+        texture.params.min_filter = Filter::LinearMipmapNearest;
+        assert_eq!(sample(&texture, 0.0), magnified);
+        assert_eq!(sample(&texture, 0.25), magnified);
+        texture.params.min_filter = Filter::LinearMipmapLinear;
+        assert_ne!(sample(&texture, 0.25), magnified);
+
+        // Nearest magnification always switches over at `0`.
+        texture.params.mag_filter = Filter::Nearest;
+        texture.params.min_filter = Filter::NearestMipmapNearest;
+        assert_eq!(sample(&texture, 0.0), 0.0);
+    }
+
+    #[test]
+    fn env_set_validation() {
+        let mut env = Env::default();
+        let modulate = Enum::MODULATE as u32 as f32;
+        assert_eq!(
+            env.set(Enum::TEXTURE_2D, Enum::TEXTURE_ENV_MODE, &[modulate]),
+            Err(Error::InvalidEnum)
+        );
+        assert_eq!(
+            env.set(Enum::TEXTURE_ENV, Enum::TEXTURE_ENV_MODE, &[0.0]),
+            Err(Error::InvalidEnum)
+        );
+        let color = [2.0, -1.0, 0.5, 1.0];
+        assert_eq!(
+            env.set(Enum::TEXTURE_ENV, Enum::TEXTURE_ENV_COLOR, &color),
+            Ok(())
+        );
+        assert_eq!(env.color, Vec4::new(1.0, 0.0, 0.5, 1.0));
+    }
+
+    #[test]
+    fn env_apply() {
+        let env = |mode| Env {
+            mode,
+            color: Vec4::new(0.0, 1.0, 0.0, 1.0),
+        };
+        let color = Vec4::splat(0.5);
+        let texel = Vec4::new(1.0, 0.0, 0.5, 0.25);
+
+        let modulate = env(EnvMode::Modulate);
+        assert_eq!(
+            modulate.apply(Enum::RGB, color, texel),
+            Vec4::new(0.5, 0.0, 0.25, 0.5)
+        );
+        assert_eq!(
+            modulate.apply(Enum::RGBA, color, texel),
+            Vec4::new(0.5, 0.0, 0.25, 0.125)
+        );
+
+        let replace = env(EnvMode::Replace);
+        assert_eq!(
+            replace.apply(Enum::ALPHA, color, texel),
+            Vec4::new(0.5, 0.5, 0.5, 0.25)
+        );
+        assert_eq!(
+            replace.apply(Enum::RGB, color, texel),
+            Vec4::new(1.0, 0.0, 0.5, 0.5)
+        );
+
+        let decal = env(EnvMode::Decal);
+        assert_eq!(
+            decal.apply(Enum::RGBA, color, texel),
+            Vec4::new(0.625, 0.375, 0.5, 0.5)
+        );
+        assert_eq!(
+            decal.apply(Enum::RGB, color, texel.truncate().extend(1.0)),
+            texel.truncate().extend(0.5)
+        );
+
+        // Only intensity textures blend the alpha with the environment color.
+        let blend = env(EnvMode::Blend);
+        let intensity = Vec4::splat(0.25);
+        assert_eq!(
+            blend.apply(Enum::INTENSITY, color, intensity),
+            Vec4::new(0.375, 0.625, 0.375, 0.625)
+        );
+        let luminance = Vec4::new(0.25, 0.25, 0.25, 1.0);
+        assert_eq!(
+            blend.apply(Enum::LUMINANCE, color, luminance),
+            Vec4::new(0.375, 0.625, 0.375, 0.5)
+        );
+    }
+}
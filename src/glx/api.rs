@@ -1,128 +1,16 @@
-use lazy_static::lazy_static;
 use libc::{c_char, c_int, c_uchar, c_ulong, free, malloc};
 use std::ffi::{CStr, CString};
 use std::sync::Arc;
 use std::{iter, mem, ptr};
 use x11_dl::glx::{
-    GLXContext, GLXDrawable, GLXFBConfig, GLX_DONT_CARE, GLX_EXTENSIONS, GLX_NONE, GLX_RGBA_BIT,
-    GLX_VENDOR, GLX_VERSION, GLX_WINDOW_BIT,
+    GLXContext, GLXDrawable, GLXFBConfig, GLX_BAD_ATTRIBUTE, GLX_BAD_VISUAL, GLX_DONT_CARE,
+    GLX_EXTENSIONS, GLX_NONE, GLX_RGBA_BIT, GLX_RGBA_TYPE, GLX_VENDOR, GLX_VERSION, GLX_WINDOW_BIT,
+};
+use x11_dl::xlib::{
+    Bool, DirectColor, Display, False, Success, True, TrueColor, VisualIDMask, XVisualInfo,
 };
-use x11_dl::xlib::{Bool, Display, False, InputOutput, Success, True, XVisualInfo, Xlib};
-
-lazy_static! {
-    static ref XLIB: Xlib = Xlib::open().unwrap();
-}
-
-#[no_mangle]
-pub unsafe extern "C" fn glXGetProcAddressARB(
-    proc_name: *const c_uchar,
-) -> Option<unsafe extern "C" fn()> {
-    let proc_name = CStr::from_ptr(proc_name as *const c_char).to_str().unwrap();
-
-    eprintln!("glXGetProcAddressARB({:?})", proc_name);
-
-    macro_rules! export {
-        ($($name:ident)*) => {
-            match proc_name {
-                $(stringify!($name) => Some(mem::transmute($name as usize)),)*
-                _ => None
-            }
-        };
-    }
-    // FIXME(eddyb) DRY with the actual entry-point definitions
-    export! {
-        glXQueryVersion
-        glXGetClientString
-        glXQueryServerString
-        glXQueryExtensionsString
-        glXChooseVisual
-        glXGetConfig
-        glXGetFBConfigs
-        glXChooseFBConfig
-        glXGetFBConfigAttrib
-        glXGetVisualFromFBConfig
-        glXCreateWindow
-        glXDestroyWindow
-        glXCreateContext
-        glXIsDirect
-        glXMakeCurrent
-        glXDestroyContext
-        glXSwapBuffers
-
-        // glxgears
-        glXQueryDrawable
-
-        // wine
-        glXCopyContext
-        glXCreateNewContext
-        glXCreatePbuffer
-        glXCreatePixmap
-        glXDestroyPbuffer
-        glXDestroyPixmap
-        glXGetCurrentContext
-        glXGetCurrentDrawable
-        glXMakeContextCurrent
-    }
-}
-
-#[no_mangle]
-pub unsafe extern "C" fn glXQueryVersion(
-    _dpy: *mut Display,
-    major: *mut c_int,
-    minor: *mut c_int,
-) -> Bool {
-    eprintln!("glXQueryVersion()");
-
-    // wine needs either GLX 1.3 or some vendor extensions.
-    *major = 1;
-    *minor = 3;
-
-    True
-}
-
-#[no_mangle]
-pub unsafe extern "C" fn glXGetClientString(_dpy: *mut Display, name: c_int) -> *const c_char {
-    eprintln!("glXGetClientString(name={})", name);
-
-    match name {
-        GLX_VENDOR => "glOOF\0",
-        GLX_VERSION => concat!(version_str!(major.minor), "\0"),
-        GLX_EXTENSIONS => "\0",
-        _ => return ptr::null(),
-    }
-    .as_ptr() as *const c_char
-}
-
-#[no_mangle]
-pub unsafe extern "C" fn glXQueryServerString(
-    _dpy: *mut Display,
-    screen: c_int,
-    name: c_int,
-) -> *const c_char {
-    assert_eq!(screen, 0);
-
-    eprintln!("glXQueryServerString(name={})", name);
-
-    match name {
-        GLX_VENDOR => "glOOF\0",
-        GLX_VERSION => concat!(version_str!(major.minor), "\0"),
-        GLX_EXTENSIONS => "\0",
-        _ => return ptr::null(),
-    }
-    .as_ptr() as *const c_char
-}
-
-#[no_mangle]
-pub unsafe extern "C" fn glXQueryExtensionsString(
-    _dpy: *mut Display,
-    screen: c_int,
-) -> *const c_char {
-    assert_eq!(screen, 0);
-
-    eprintln!("glXQueryExtensionsString()");
 
-    "\0".as_ptr() as *const c_char
-}
+use super::XLIB;
 
 macro_rules! attribs {
     (@type(Visual) bool) => {bool};
@@ -160,16 +48,21 @@ macro_rules! attribs {
                 }
             }
 
-            fn parse(attrib: c_int, next: impl FnOnce() -> c_int) -> Self {
+            /// Parse `attrib` (reading its value with `next`, if it has one),
+            /// returning `None` if it's not a known attribute.
+            fn parse(attrib: c_int, next: impl FnOnce() -> c_int) -> Option<Self> {
                 match attrib {
-                    $(x11_dl::glx::$name => Self::$name(attribs!(@parse($mode, next) $ty)),)*
-                    attrib => panic!(concat!(stringify!($Attrib), "::parse: invalid attribute {}"), attrib),
+                    $(x11_dl::glx::$name => Some(Self::$name(attribs!(@parse($mode, next) $ty))),)*
+                    _ => None,
                 }
             }
 
-            unsafe fn parse_list(mut list: *const c_int) -> impl Iterator<Item = Self> {
+            /// Parse a `0`-terminated attribute list, stopping after the first
+            /// unknown attribute (as its value, if any, can't be skipped).
+            unsafe fn parse_list(mut list: *const c_int) -> impl Iterator<Item = Option<Self>> {
+                let mut unknown = false;
                 iter::from_fn(move || {
-                    if *list == 0 {
+                    if *list == 0 || unknown {
                         return None;
                     }
                     let mut next = || {
@@ -177,7 +70,9 @@ macro_rules! attribs {
                         list = list.add(1);
                         attrib
                     };
-                    Some(Self::parse(next(), next))
+                    let attrib = Self::parse(next(), next);
+                    unknown = attrib.is_none();
+                    Some(attrib)
                 })
             }
         }
@@ -193,7 +88,7 @@ macro_rules! attribs {
                     $($name: Default::default()),*
                 };
                 default.extend([
-                    $($($Attrib::parse(x11_dl::glx::$name, || $default),)?)*
+                    $($($Attrib::parse(x11_dl::glx::$name, || $default).unwrap(),)?)*
                 ].iter().copied());
                 default
             }
@@ -290,342 +185,470 @@ attribs! {
 const COLOR_DEPTH: c_int = 24;
 const COLOR_CHANNEL_DEPTH: c_int = 8;
 
-unsafe fn default_visual_info(dpy: *mut Display) -> *mut XVisualInfo {
+/// Whether GL can render to drawables using `visual_info`, i.e. whether its
+/// pixel values are made of separate RGB channels (see `present::Presenter`).
+pub(super) fn is_supported_visual(visual_info: &XVisualInfo) -> bool {
+    [TrueColor, DirectColor].contains(&visual_info.class)
+}
+
+/// The sole visual (and `GLXFBConfig`) GL can render with, or null if the
+/// display doesn't have any supported visual (see `is_supported_visual`).
+pub(super) unsafe fn default_visual_info(dpy: *mut Display) -> *mut XVisualInfo {
     let visual_info = malloc(mem::size_of::<XVisualInfo>()) as *mut XVisualInfo;
 
-    // HACK(eddyb) `XMatchVisualInfo` returns 0 (failure) for some reason.
-    if false {
-        if (XLIB.XMatchVisualInfo)(dpy, 0, COLOR_DEPTH, InputOutput, visual_info) == 0 {
-            free(visual_info as *mut _);
-            return ptr::null_mut();
-        }
-        eprintln!("visual_info = {:#?}", *visual_info);
-    } else {
-        visual_info.write(XVisualInfo {
-            visual: (XLIB.XDefaultVisual)(dpy, 0),
-            depth: COLOR_DEPTH,
-            ..mem::zeroed()
-        });
+    if (XLIB.XMatchVisualInfo)(dpy, 0, COLOR_DEPTH, TrueColor, visual_info) != 0 {
+        return visual_info;
+    }
+    free(visual_info as *mut _);
+
+    // NOTE(eddyb) presenting can convert to any depth (e.g. 16-bit displays),
+    // so the default visual can also be used, as long as it's supported.
+    let mut template: XVisualInfo = mem::zeroed();
+    template.visualid = (XLIB.XVisualIDFromVisual)((XLIB.XDefaultVisual)(dpy, 0));
+    let mut count = 0;
+    let visual_info = (XLIB.XGetVisualInfo)(dpy, VisualIDMask, &mut template, &mut count);
+    if !visual_info.is_null() && (count == 0 || !is_supported_visual(&*visual_info)) {
+        (XLIB.XFree)(visual_info as *mut _);
+        return ptr::null_mut();
     }
     visual_info
 }
 
-#[no_mangle]
-pub unsafe extern "C" fn glXChooseVisual(
-    dpy: *mut Display,
-    screen: c_int,
-    attrib_list: *mut c_int,
-) -> *mut XVisualInfo {
-    assert_eq!(screen, 0);
-
-    eprintln!("glXChooseVisual(attribList=[");
-    for attrib in VisualAttrib::parse_list(attrib_list) {
-        eprintln!("    {:?},", attrib);
+/// The value of `attrib` for `visual_info`, and our sole `GLXFBConfig` (see
+/// `glXGetConfig`/`glXGetFBConfigAttrib`), or `None` if it's not known.
+fn config_attrib(visual_info: &XVisualInfo, attrib: c_int) -> Option<c_int> {
+    use x11_dl::glx::*;
+    Some(match attrib {
+        GLX_USE_GL | GLX_RGBA | GLX_DOUBLEBUFFER | GLX_X_RENDERABLE => True,
+        GLX_STEREO => False,
+        GLX_FBCONFIG_ID | GLX_LEVEL | GLX_SCREEN => 0,
+        GLX_VISUAL_ID => visual_info.visualid as c_int,
+        GLX_X_VISUAL_TYPE if visual_info.class == DirectColor => GLX_DIRECT_COLOR,
+        GLX_X_VISUAL_TYPE => GLX_TRUE_COLOR,
+        GLX_BUFFER_SIZE => COLOR_DEPTH,
+        GLX_AUX_BUFFERS => 0,
+        GLX_RED_SIZE | GLX_GREEN_SIZE | GLX_BLUE_SIZE | GLX_ALPHA_SIZE => COLOR_CHANNEL_DEPTH,
+        GLX_DEPTH_SIZE => crate::gl::raster::DEPTH_BITS as c_int,
+        GLX_STENCIL_SIZE => crate::gl::raster::STENCIL_BITS as c_int,
+        GLX_ACCUM_RED_SIZE | GLX_ACCUM_GREEN_SIZE | GLX_ACCUM_BLUE_SIZE | GLX_ACCUM_ALPHA_SIZE => 0,
+        GLX_RENDER_TYPE => GLX_RGBA_BIT,
+        GLX_DRAWABLE_TYPE => GLX_WINDOW_BIT,
+        GLX_CONFIG_CAVEAT | GLX_TRANSPARENT_TYPE => GLX_NONE,
+        GLX_TRANSPARENT_INDEX_VALUE
+        | GLX_TRANSPARENT_RED_VALUE
+        | GLX_TRANSPARENT_GREEN_VALUE
+        | GLX_TRANSPARENT_BLUE_VALUE
+        | GLX_TRANSPARENT_ALPHA_VALUE => 0,
+        GLX_MAX_PBUFFER_WIDTH | GLX_MAX_PBUFFER_HEIGHT | GLX_MAX_PBUFFER_PIXELS => 0,
+        GLX_SAMPLE_BUFFERS | GLX_SAMPLES => 0,
+        _ => return None,
+    })
+}
+
+impl VisualAttribs {
+    /// Whether our sole visual (see `default_visual_info`) has at least the
+    /// capabilities requested (from `glXChooseVisual`) by these attributes.
+    fn is_satisfied(&self) -> bool {
+        let color = [
+            self.GLX_RED_SIZE,
+            self.GLX_GREEN_SIZE,
+            self.GLX_BLUE_SIZE,
+            self.GLX_ALPHA_SIZE,
+        ];
+        let accum = [
+            self.GLX_ACCUM_RED_SIZE,
+            self.GLX_ACCUM_GREEN_SIZE,
+            self.GLX_ACCUM_BLUE_SIZE,
+            self.GLX_ACCUM_ALPHA_SIZE,
+        ];
+        // FIXME(eddyb) `GLX_DOUBLEBUFFER` is ignored, so even visuals for
+        // single-buffered rendering (which isn't supported) are satisfied.
+        self.GLX_USE_GL
+            && self.GLX_RGBA
+            && !self.GLX_STEREO
+            && self.GLX_LEVEL == 0
+            && self.GLX_AUX_BUFFERS <= 0
+            && color.iter().all(|&size| size <= COLOR_CHANNEL_DEPTH)
+            && self.GLX_DEPTH_SIZE <= crate::gl::raster::DEPTH_BITS as c_int
+            && self.GLX_STENCIL_SIZE <= crate::gl::raster::STENCIL_BITS as c_int
+            && accum.iter().all(|&size| size <= 0)
     }
-    eprintln!("])");
+}
 
-    eprintln!(
-        "attribs = {:#?}",
-        VisualAttrib::parse_list(attrib_list).collect::<VisualAttribs>()
-    );
+include!(concat!(env!("OUT_DIR"), "/glx_api.rs"));
 
-    default_visual_info(dpy)
-}
+/// Implementations for the GLX entry points (see also `build.rs`).
+pub struct Api;
 
-#[no_mangle]
-pub unsafe extern "C" fn glXGetConfig(
-    _dpy: *mut Display,
-    visual: *mut XVisualInfo,
-    attrib: c_int,
-    _value: *mut c_int,
-) -> c_int {
-    // FIXME(eddyb) make a separate `enum` for an attrib w/o values.
-    let attrib = VisualAttrib::parse(attrib, || 0);
-
-    unimplemented!(
-        "glXGetConfig(visual={:#?}, attrib={})",
-        *visual,
-        attrib.name()
-    );
-}
+#[allow(non_snake_case)]
+impl EntryPoints for Api {
+    unsafe fn glXGetProcAddressARB(proc_name: *const c_uchar) -> Option<unsafe extern "C" fn()> {
+        let proc_name = CStr::from_ptr(proc_name as *const c_char).to_str().unwrap();
 
-#[no_mangle]
-pub unsafe extern "C" fn glXGetFBConfigs(
-    _dpy: *mut Display,
-    screen: c_int,
-    nelements: *mut c_int,
-) -> *mut GLXFBConfig {
-    assert_eq!(screen, 0);
+        eprintln!("glXGetProcAddressARB({:?})", proc_name);
 
-    eprintln!("glXGetFBConfigs()");
+        proc_address(proc_name).or_else(|| crate::gl::api_1_0::proc_address(proc_name))
+    }
 
-    let fb_config_array = [ptr::null_mut()];
+    unsafe fn glXQueryVersion(_dpy: *mut Display, major: *mut c_int, minor: *mut c_int) -> Bool {
+        eprintln!("glXQueryVersion()");
 
-    let fb_configs = malloc(mem::size_of_val(&fb_config_array)) as *mut [GLXFBConfig; 1];
-    fb_configs.write(fb_config_array);
-    *nelements = fb_config_array.len() as c_int;
-    fb_configs as *mut GLXFBConfig
-}
+        // wine needs either GLX 1.3 or some vendor extensions.
+        *major = 1;
+        *minor = 3;
 
-#[no_mangle]
-pub unsafe extern "C" fn glXChooseFBConfig(
-    _dpy: *mut Display,
-    screen: c_int,
-    attrib_list: *const c_int,
-    nelements: *mut c_int,
-) -> *mut GLXFBConfig {
-    assert_eq!(screen, 0);
-
-    eprintln!("glXChooseFBConfig(attrib_list=[");
-    for attrib in FBConfigAttrib::parse_list(attrib_list) {
-        eprintln!("    {:?},", attrib);
+        True
     }
-    eprintln!("])");
 
-    eprintln!(
-        "attribs = {:#?}",
-        FBConfigAttrib::parse_list(attrib_list).collect::<FBConfigAttribs>()
-    );
+    unsafe fn glXGetClientString(_dpy: *mut Display, name: c_int) -> *const c_char {
+        eprintln!("glXGetClientString(name={})", name);
 
-    let fb_config_array = [ptr::null_mut()];
+        match name {
+            GLX_VENDOR => "glOOF\0",
+            GLX_VERSION => concat!(version_str!(major.minor), "\0"),
+            GLX_EXTENSIONS => "\0",
+            _ => return ptr::null(),
+        }
+        .as_ptr() as *const c_char
+    }
 
-    let fb_configs = malloc(mem::size_of_val(&fb_config_array)) as *mut [GLXFBConfig; 1];
-    fb_configs.write(fb_config_array);
-    *nelements = fb_config_array.len() as c_int;
-    fb_configs as *mut GLXFBConfig
-}
+    unsafe fn glXQueryServerString(
+        _dpy: *mut Display,
+        screen: c_int,
+        name: c_int,
+    ) -> *const c_char {
+        assert_eq!(screen, 0);
 
-#[no_mangle]
-pub unsafe extern "C" fn glXGetFBConfigAttrib(
-    _dpy: *mut Display,
-    config: GLXFBConfig,
-    attribute: c_int,
-    value: *mut c_int,
-) -> c_int {
-    assert_eq!(config, ptr::null_mut());
-
-    // FIXME(eddyb) make a separate `enum` for an attrib w/o values.
-    let attribute = FBConfigAttrib::parse(attribute, || 0);
-
-    eprintln!("glXGetFBConfigAttrib(attribute={})", attribute.name());
-
-    *value = match attribute {
-        // HACK(eddyb) hardcode some values for our sole `GLXFBConfig`.
-        FBConfigAttrib::GLX_FBCONFIG_ID(_) => 0,
-        FBConfigAttrib::GLX_BUFFER_SIZE(_) => COLOR_DEPTH,
-        FBConfigAttrib::GLX_DOUBLEBUFFER(_) => True,
-        FBConfigAttrib::GLX_STEREO(_) => False,
-        FBConfigAttrib::GLX_AUX_BUFFERS(_) => 0,
-        FBConfigAttrib::GLX_RED_SIZE(_)
-        | FBConfigAttrib::GLX_GREEN_SIZE(_)
-        | FBConfigAttrib::GLX_BLUE_SIZE(_)
-        | FBConfigAttrib::GLX_ALPHA_SIZE(_) => COLOR_CHANNEL_DEPTH,
-        FBConfigAttrib::GLX_DEPTH_SIZE(_) => 16,
-        FBConfigAttrib::GLX_STENCIL_SIZE(_) => 0,
-        FBConfigAttrib::GLX_ACCUM_RED_SIZE(_)
-        | FBConfigAttrib::GLX_ACCUM_GREEN_SIZE(_)
-        | FBConfigAttrib::GLX_ACCUM_BLUE_SIZE(_)
-        | FBConfigAttrib::GLX_ACCUM_ALPHA_SIZE(_) => 0,
-        FBConfigAttrib::GLX_RENDER_TYPE(_) => GLX_RGBA_BIT,
-        FBConfigAttrib::GLX_DRAWABLE_TYPE(_) => GLX_WINDOW_BIT,
-
-        _ => unimplemented!(),
-    };
+        eprintln!("glXQueryServerString(name={})", name);
 
-    Success as c_int
-}
+        match name {
+            GLX_VENDOR => "glOOF\0",
+            GLX_VERSION => concat!(version_str!(major.minor), "\0"),
+            GLX_EXTENSIONS => "\0",
+            _ => return ptr::null(),
+        }
+        .as_ptr() as *const c_char
+    }
 
-#[no_mangle]
-pub unsafe extern "C" fn glXGetVisualFromFBConfig(
-    dpy: *mut Display,
-    config: GLXFBConfig,
-) -> *mut XVisualInfo {
-    assert_eq!(config, ptr::null_mut());
+    unsafe fn glXQueryExtensionsString(_dpy: *mut Display, screen: c_int) -> *const c_char {
+        assert_eq!(screen, 0);
 
-    eprintln!("glXGetVisualFromFBConfig()");
+        eprintln!("glXQueryExtensionsString()");
 
-    default_visual_info(dpy)
-}
+        "\0".as_ptr() as *const c_char
+    }
+
+    unsafe fn glXChooseVisual(
+        dpy: *mut Display,
+        screen: c_int,
+        attrib_list: *mut c_int,
+    ) -> *mut XVisualInfo {
+        assert_eq!(screen, 0);
+
+        eprintln!("glXChooseVisual(attribList=[");
+        for attrib in VisualAttrib::parse_list(attrib_list) {
+            match attrib {
+                Some(attrib) => eprintln!("    {:?},", attrib),
+                None => eprintln!("    <unknown attribute>,"),
+            }
+        }
+        eprintln!("])");
+
+        let attribs = VisualAttrib::parse_list(attrib_list).collect::<Option<VisualAttribs>>();
+        eprintln!("attribs = {:#?}", attribs);
 
-#[no_mangle]
-pub unsafe extern "C" fn glXCreateWindow(
-    _dpy: *mut Display,
-    config: GLXFBConfig,
-    win: c_ulong,
-    attrib_list: *const c_int,
-) -> c_ulong {
-    assert_eq!(config, ptr::null_mut());
-    if !attrib_list.is_null() {
-        assert_eq!(*attrib_list, 0);
+        match attribs {
+            Some(attribs) if attribs.is_satisfied() => default_visual_info(dpy),
+            _ => ptr::null_mut(),
+        }
     }
 
-    eprintln!("glXCreateWindow(win={:#x})", win);
+    unsafe fn glXGetConfig(
+        _dpy: *mut Display,
+        visual: *mut XVisualInfo,
+        attrib: c_int,
+        value: *mut c_int,
+    ) -> c_int {
+        // FIXME(eddyb) make a separate `enum` for an attrib w/o values.
+        let name = VisualAttrib::parse(attrib, || 0).map(|attrib| attrib.name());
+
+        eprintln!("glXGetConfig(visual={:#?}, attrib={:?})", *visual, name);
+
+        if !is_supported_visual(&*visual) {
+            if attrib == x11_dl::glx::GLX_USE_GL {
+                *value = False;
+                return Success as c_int;
+            }
+            return GLX_BAD_VISUAL;
+        }
+        match config_attrib(&*visual, attrib) {
+            Some(v) if name.is_some() => {
+                *value = v;
+                Success as c_int
+            }
+            _ => GLX_BAD_ATTRIBUTE,
+        }
+    }
 
-    // HACK(eddyb) don't bother creating a child window inside `win`.
-    win
-}
+    unsafe fn glXGetFBConfigs(
+        _dpy: *mut Display,
+        screen: c_int,
+        nelements: *mut c_int,
+    ) -> *mut GLXFBConfig {
+        assert_eq!(screen, 0);
 
-#[no_mangle]
-pub unsafe extern "C" fn glXDestroyWindow(_dpy: *mut Display, win: c_ulong) {
-    eprintln!("glXDestroyWindow(win={:#x})", win);
+        eprintln!("glXGetFBConfigs()");
 
-    // NOTE(eddyb) don't have to do anything right now, see `glXCreateWindow`.
-}
+        let fb_config_array = [ptr::null_mut()];
 
-#[no_mangle]
-pub unsafe extern "C" fn glXCreateContext(
-    _dpy: *mut Display,
-    vis: *mut XVisualInfo,
-    share_list: GLXContext,
-    direct: Bool,
-) -> GLXContext {
-    assert_eq!(share_list, ptr::null_mut());
-
-    eprintln!(
-        "glXCreateContext(vis={:#?}, direct={})",
-        *vis,
-        direct != False
-    );
-
-    Arc::into_raw(super::Context::new()) as GLXContext
-}
+        let fb_configs = malloc(mem::size_of_val(&fb_config_array)) as *mut [GLXFBConfig; 1];
+        fb_configs.write(fb_config_array);
+        *nelements = fb_config_array.len() as c_int;
+        fb_configs as *mut GLXFBConfig
+    }
 
-#[no_mangle]
-pub unsafe extern "C" fn glXIsDirect(_dpy: *mut Display, ctx: GLXContext) -> Bool {
-    eprintln!("glXIsDirect(ctx={:#?})", ctx);
+    unsafe fn glXChooseFBConfig(
+        _dpy: *mut Display,
+        screen: c_int,
+        attrib_list: *const c_int,
+        nelements: *mut c_int,
+    ) -> *mut GLXFBConfig {
+        assert_eq!(screen, 0);
+
+        eprintln!("glXChooseFBConfig(attrib_list=[");
+        for attrib in FBConfigAttrib::parse_list(attrib_list) {
+            match attrib {
+                Some(attrib) => eprintln!("    {:?},", attrib),
+                None => eprintln!("    <unknown attribute>,"),
+            }
+        }
+        eprintln!("])");
 
-    assert!(!ctx.is_null());
+        let attribs = FBConfigAttrib::parse_list(attrib_list).collect::<Option<FBConfigAttribs>>();
+        eprintln!("attribs = {:#?}", attribs);
+        if attribs.is_none() {
+            *nelements = 0;
+            return ptr::null_mut();
+        }
 
-    True
-}
+        let fb_config_array = [ptr::null_mut()];
 
-#[no_mangle]
-pub unsafe extern "C" fn glXMakeCurrent(
-    dpy: *mut Display,
-    drawable: GLXDrawable,
-    ctx: GLXContext,
-) -> Bool {
-    eprintln!("glXMakeCurrent(drawable={:#x}, ctx={:#?})", drawable, ctx);
-
-    if ctx.is_null() {
-        super::Context::remove_current();
-    } else {
-        mem::ManuallyDrop::new(Arc::from_raw(ctx as *mut super::Context))
-            .make_current(super::Surface { drawable }, super::Surface { drawable });
+        let fb_configs = malloc(mem::size_of_val(&fb_config_array)) as *mut [GLXFBConfig; 1];
+        fb_configs.write(fb_config_array);
+        *nelements = fb_config_array.len() as c_int;
+        fb_configs as *mut GLXFBConfig
     }
 
-    let mut name = ptr::null_mut();
-    (XLIB.XFetchName)(dpy, drawable, &mut name);
-    if !name.is_null() {
-        let new_name = CString::new(format!(
-            "{} [glOOF]",
-            CStr::from_ptr(name).to_str().unwrap()
-        ))
-        .unwrap();
-        (XLIB.XStoreName)(dpy, drawable, new_name.as_ptr());
-        free(name as *mut _);
+    unsafe fn glXGetFBConfigAttrib(
+        dpy: *mut Display,
+        config: GLXFBConfig,
+        attribute: c_int,
+        value: *mut c_int,
+    ) -> c_int {
+        assert_eq!(config, ptr::null_mut());
+
+        // FIXME(eddyb) make a separate `enum` for an attrib w/o values.
+        let name = FBConfigAttrib::parse(attribute, || 0).map(|attribute| attribute.name());
+
+        eprintln!("glXGetFBConfigAttrib(attribute={:?})", name);
+
+        let visual_info = default_visual_info(dpy);
+        if visual_info.is_null() {
+            return GLX_BAD_ATTRIBUTE;
+        }
+        let result = config_attrib(&*visual_info, attribute);
+        free(visual_info as *mut _);
+
+        match result {
+            // NOTE(eddyb) these are only valid for visuals (see `glXGetConfig`).
+            Some(_) if matches!(attribute, x11_dl::glx::GLX_USE_GL | x11_dl::glx::GLX_RGBA) => {
+                GLX_BAD_ATTRIBUTE
+            }
+            Some(v) => {
+                *value = v;
+                Success as c_int
+            }
+            None => GLX_BAD_ATTRIBUTE,
+        }
     }
 
-    True
-}
+    unsafe fn glXGetVisualFromFBConfig(dpy: *mut Display, config: GLXFBConfig) -> *mut XVisualInfo {
+        assert_eq!(config, ptr::null_mut());
 
-#[no_mangle]
-pub unsafe extern "C" fn glXDestroyContext(_dpy: *mut Display, ctx: GLXContext) {
-    eprintln!("glXDestroyContext(ctx={:#?})", ctx);
+        eprintln!("glXGetVisualFromFBConfig()");
 
-    assert!(!ctx.is_null());
+        default_visual_info(dpy)
+    }
 
-    // NOTE(eddyb) this will only drop one reference (the one lent to the user),
-    // *not* the context itself, if it's still in use - this is according to spec:
-    // > If `ctx` is still current to any thread, `ctx` is not destroyed until it
-    // > is no longer current.
-    drop(Arc::from_raw(ctx as *mut super::Context));
-}
+    unsafe fn glXCreateWindow(
+        _dpy: *mut Display,
+        config: GLXFBConfig,
+        win: c_ulong,
+        attrib_list: *const c_int,
+    ) -> c_ulong {
+        assert_eq!(config, ptr::null_mut());
+        if !attrib_list.is_null() {
+            assert_eq!(*attrib_list, 0);
+        }
+
+        eprintln!("glXCreateWindow(win={:#x})", win);
+
+        // HACK(eddyb) don't bother creating a child window inside `win`.
+        win
+    }
 
-#[no_mangle]
-pub unsafe extern "C" fn glXSwapBuffers(dpy: *mut Display, drawable: GLXDrawable) {
-    use x11_dl::xlib::*;
+    unsafe fn glXDestroyWindow(_dpy: *mut Display, win: c_ulong) {
+        eprintln!("glXDestroyWindow(win={:#x})", win);
 
-    // eprintln!("glXSwapBuffers(drawable={:#x})", drawable);
+        // NOTE(eddyb) `win` is used directly as a drawable, see `glXCreateWindow`.
+        super::Surface::destroy(win);
+    }
 
-    match &*super::Context::get_current().unwrap().0.lock().unwrap() {
-        super::State::Inactive(_) => unreachable!("glOOF: inactive current GLX context"),
-        super::State::Current { read, draw } => {
-            assert_eq!(read.drawable, drawable);
-            assert_eq!(draw.drawable, drawable);
+    unsafe fn glXCreateContext(
+        _dpy: *mut Display,
+        vis: *mut XVisualInfo,
+        share_list: GLXContext,
+        direct: Bool,
+    ) -> GLXContext {
+        eprintln!(
+            "glXCreateContext(vis={:#?}, share_list={:#?}, direct={})",
+            vis.as_ref(),
+            share_list,
+            direct != False
+        );
+
+        if !vis.as_ref().is_some_and(is_supported_visual) {
+            return ptr::null_mut();
         }
+
+        let share = (share_list as *const super::Context).as_ref();
+        Arc::into_raw(super::Context::new(share)) as GLXContext
     }
 
-    (XLIB.XSetWindowBackground)(dpy, drawable, 0);
-    (XLIB.XClearWindow)(dpy, drawable);
-    let gc = (XLIB.XCreateGC)(
-        dpy,
-        drawable,
-        GCForeground as c_ulong,
-        &XGCValues {
-            foreground: 0xffffff,
-            ..mem::zeroed()
-        } as *const _ as *mut _,
-    );
-
-    crate::gl::Context::with("glXSwapBuffers", |gl| {
-        let mut last_point = None;
-        for cmd in gl.pending_cmds.drain(..) {
-            gl.state.apply(cmd, &mut |state, cmd| {
-                use crate::gl::api_1_0::Command::*;
-                use glam::Vec3;
-                match cmd {
-                    glVertex3f(x, y, z) => {
-                        let mut p = Vec3::new(x, y, z);
-                        p = state.modelview.mat.transform_point3(p);
-                        p = state.projection.mat.transform_point3(p);
-                        match last_point {
-                            None => last_point = Some(p),
-                            Some(last) => {
-                                (XLIB.XDrawLine)(
-                                    dpy,
-                                    drawable,
-                                    gc,
-                                    (last.x * 300.0 / 2.0 + 300.0 / 2.0) as _,
-                                    (-last.y * 300.0 / 2.0 + 300.0 / 2.0) as _,
-                                    (p.x * 300.0 / 2.0 + 300.0 / 2.0) as _,
-                                    (-p.y * 300.0 / 2.0 + 300.0 / 2.0) as _,
-                                );
-                            }
-                        }
-                    }
-                    _ => {
-                        // eprintln!("{:?}", cmd);
-                    }
-                }
-            });
+    unsafe fn glXCreateNewContext(
+        _dpy: *mut Display,
+        config: GLXFBConfig,
+        render_type: c_int,
+        share_list: GLXContext,
+        direct: Bool,
+    ) -> GLXContext {
+        eprintln!(
+            "glXCreateNewContext(config={:#?}, render_type={:#x}, share_list={:#?}, direct={})",
+            config,
+            render_type,
+            share_list,
+            direct != False
+        );
+
+        // NOTE(eddyb) there's no color index mode.
+        if render_type != GLX_RGBA_TYPE {
+            return ptr::null_mut();
         }
-    });
 
-    (XLIB.XFlush)(dpy);
-}
+        let share = (share_list as *const super::Context).as_ref();
+        Arc::into_raw(super::Context::new(share)) as GLXContext
+    }
 
-macro_rules! unimplemented_entry_points {
-    ($($name:ident)*) => {
-        $(#[no_mangle]
-        pub extern "C" fn $name() {
-            unimplemented!(stringify!($name));
-        })*
-    };
+    unsafe fn glXIsDirect(_dpy: *mut Display, ctx: GLXContext) -> Bool {
+        eprintln!("glXIsDirect(ctx={:#?})", ctx);
+
+        assert!(!ctx.is_null());
+
+        True
+    }
+
+    unsafe fn glXMakeCurrent(dpy: *mut Display, drawable: GLXDrawable, ctx: GLXContext) -> Bool {
+        eprintln!("glXMakeCurrent(drawable={:#x}, ctx={:#?})", drawable, ctx);
+
+        if ctx.is_null() {
+            super::Context::remove_current();
+        } else {
+            mem::ManuallyDrop::new(Arc::from_raw(ctx as *mut super::Context)).make_current(
+                super::Surface::get(dpy, drawable),
+                super::Surface::get(dpy, drawable),
+            );
+        }
+
+        let mut name = ptr::null_mut();
+        (XLIB.XFetchName)(dpy, drawable, &mut name);
+        if !name.is_null() {
+            let new_name = CString::new(format!(
+                "{} [glOOF]",
+                CStr::from_ptr(name).to_str().unwrap()
+            ))
+            .unwrap();
+            (XLIB.XStoreName)(dpy, drawable, new_name.as_ptr());
+            free(name as *mut _);
+        }
+
+        True
+    }
+
+    unsafe fn glXDestroyContext(_dpy: *mut Display, ctx: GLXContext) {
+        eprintln!("glXDestroyContext(ctx={:#?})", ctx);
+
+        assert!(!ctx.is_null());
+
+        // NOTE(eddyb) this will only drop one reference (the one lent to the user),
+        // *not* the context itself, if it's still in use - this is according to spec:
+        // > If `ctx` is still current to any thread, `ctx` is not destroyed until it
+        // > is no longer current.
+        drop(Arc::from_raw(ctx as *mut super::Context));
+    }
+
+    unsafe fn glXSwapBuffers(dpy: *mut Display, drawable: GLXDrawable) {
+        // eprintln!("glXSwapBuffers(drawable={:#x})", drawable);
+
+        match &*super::Context::get_current().unwrap().state.lock().unwrap() {
+            super::State::Inactive(_) => unreachable!("glOOF: inactive current GLX context"),
+            super::State::Current { read, draw } => {
+                assert_eq!(read.drawable, drawable);
+                assert_eq!(draw.drawable, drawable);
+                draw.present(dpy);
+
+                // NOTE(eddyb) this is a convenient time to check for resizes, as
+                // any rendering after a swap is for the next frame.
+                draw.update_size(dpy);
+            }
+        }
+    }
 }
 
-unimplemented_entry_points! {
-    // glxgears
-    glXQueryDrawable
-
-    // wine
-    glXCopyContext
-    glXCreateNewContext
-    glXCreatePbuffer
-    glXCreatePixmap
-    glXDestroyPbuffer
-    glXDestroyPixmap
-    glXGetCurrentContext
-    glXGetCurrentDrawable
-    glXMakeContextCurrent
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use x11_dl::glx::*;
+
+    fn choose(attrib_list: &[c_int]) -> Option<bool> {
+        let attribs = unsafe { VisualAttrib::parse_list(attrib_list.as_ptr()) }
+            .collect::<Option<VisualAttribs>>()?;
+        Some(attribs.is_satisfied())
+    }
+
+    #[test]
+    fn visual_attribs() {
+        assert_eq!(choose(&[GLX_RGBA, GLX_DOUBLEBUFFER, 0]), Some(true));
+        assert_eq!(
+            choose(&[
+                GLX_RGBA,
+                GLX_RED_SIZE,
+                8,
+                GLX_DEPTH_SIZE,
+                16,
+                GLX_STENCIL_SIZE,
+                8,
+                0
+            ]),
+            Some(true)
+        );
+
+        // Color index, or more bits than are available, can't be satisfied.
+        assert_eq!(choose(&[GLX_BUFFER_SIZE, 8, 0]), Some(false));
+        assert_eq!(choose(&[GLX_RGBA, GLX_DEPTH_SIZE, 24, 0]), Some(false));
+        assert_eq!(choose(&[GLX_RGBA, GLX_ACCUM_RED_SIZE, 1, 0]), Some(false));
+        assert_eq!(choose(&[GLX_RGBA, GLX_STEREO, 0]), Some(false));
+
+        // Unknown attributes stop parsing (as they may be followed by a value).
+        assert_eq!(choose(&[GLX_RGBA, GLX_RENDER_TYPE, GLX_RGBA_BIT, 0]), None);
+    }
 }
@@ -1,17 +1,32 @@
+use crate::gl::raster::Framebuffer;
+use lazy_static::lazy_static;
 use std::cell::Cell;
+use std::collections::HashMap;
 use std::mem;
 use std::sync::{Arc, Mutex};
+use x11_dl::xlib::{Display, Xlib};
 
 mod api;
+mod present;
 
-struct Context(Mutex<State>);
+lazy_static! {
+    static ref XLIB: Xlib = Xlib::open().unwrap();
+}
+
+struct Context {
+    state: Mutex<State>,
+
+    /// The share group of the GL context, kept here because the GL context
+    /// itself isn't accessible while it's current (possibly on another thread).
+    share_group: Arc<Mutex<crate::gl::ShareGroup>>,
+}
 
 enum State {
     Inactive(Box<crate::gl::Context>),
     Current { read: Surface, draw: Surface },
 }
 
-thread_local!(static CURRENT_CX: Cell<Option<Arc<Context>>> = Cell::new(None));
+thread_local!(static CURRENT_CX: Cell<Option<Arc<Context>>> = const { Cell::new(None) });
 
 /// Enforce that `T` is `Send`, guaranteeing it even when it may only be relied
 /// upon in `unsafe` code that wouldn't have the necessary bounds itself.
@@ -20,10 +35,15 @@ fn assert_send<T: Send>(x: T) -> T {
 }
 
 impl Context {
-    fn new() -> Arc<Self> {
-        assert_send(Arc::new(Self(Mutex::new(State::Inactive(Box::new(
-            crate::gl::Context::new(),
-        ))))))
+    /// Create a new context, sharing display lists with `share` (if present).
+    fn new(share: Option<&Context>) -> Arc<Self> {
+        let share_group = share.map_or_else(Default::default, |share| share.share_group.clone());
+        assert_send(Arc::new(Context {
+            state: Mutex::new(State::Inactive(Box::new(crate::gl::Context::new(
+                share_group.clone(),
+            )))),
+            share_group,
+        }))
     }
 
     fn get_current() -> Option<Arc<Context>> {
@@ -37,9 +57,10 @@ impl Context {
     fn remove_current() -> Option<Arc<Context>> {
         CURRENT_CX.with(
             |current| match (current.take(), crate::gl::Context::leave()) {
-                (Some(glx), Some(gl)) => {
+                (Some(glx), Some(mut gl)) => {
+                    gl.set_framebuffer(None);
                     // FIXME(eddyb) flush `gl` and/or surfaces?
-                    match mem::replace(&mut *glx.0.lock().unwrap(), State::Inactive(gl)) {
+                    match mem::replace(&mut *glx.state.lock().unwrap(), State::Inactive(gl)) {
                         State::Inactive(_) => unreachable!("glOOF: inactive current GLX context"),
                         State::Current { .. } => {}
                     }
@@ -54,8 +75,13 @@ impl Context {
     fn make_current(self: &Arc<Self>, read: Surface, draw: Surface) {
         Self::remove_current();
 
-        match mem::replace(&mut *self.0.lock().unwrap(), State::Current { read, draw }) {
-            State::Inactive(gl) => {
+        let framebuffer = draw.buffers.framebuffer.clone();
+        match mem::replace(
+            &mut *self.state.lock().unwrap(),
+            State::Current { read, draw },
+        ) {
+            State::Inactive(mut gl) => {
+                gl.set_framebuffer(Some(framebuffer));
                 gl.enter();
             }
             State::Current { .. } => {
@@ -71,7 +97,91 @@ impl Context {
     }
 }
 
+/// Buffers of a drawable, shared by all `Surface`s for that drawable.
+struct Buffers {
+    /// The back color buffer (and ancillary buffers), rendered into by GL.
+    framebuffer: Arc<Mutex<Framebuffer>>,
+
+    /// Used to present the color buffer, created on the first swap.
+    presenter: Mutex<Option<present::Presenter>>,
+}
+
+unsafe fn drawable_size(dpy: *mut Display, drawable: x11_dl::glx::GLXDrawable) -> (u32, u32) {
+    let (mut root, mut x, mut y) = (0, 0, 0);
+    let (mut width, mut height, mut border_width, mut depth) = (0, 0, 0, 0);
+    let status = (XLIB.XGetGeometry)(
+        dpy,
+        drawable,
+        &mut root,
+        &mut x,
+        &mut y,
+        &mut width,
+        &mut height,
+        &mut border_width,
+        &mut depth,
+    );
+    assert_ne!(status, 0, "glOOF: XGetGeometry({:#x}) failed", drawable);
+    (width, height)
+}
+
+lazy_static! {
+    /// Buffers of all drawables that have been made current so far,
+    /// kept around until the drawable is destroyed (see `Surface::destroy`).
+    static ref BUFFERS: Mutex<HashMap<x11_dl::glx::GLXDrawable, Arc<Buffers>>> =
+        Mutex::new(HashMap::new());
+}
+
 struct Surface {
     // FIXME(eddyb) encapsulate this better.
     drawable: x11_dl::glx::GLXDrawable,
+    buffers: Arc<Buffers>,
+}
+
+impl Surface {
+    unsafe fn get(dpy: *mut Display, drawable: x11_dl::glx::GLXDrawable) -> Self {
+        let buffers = BUFFERS
+            .lock()
+            .unwrap()
+            .entry(drawable)
+            .or_insert_with(|| {
+                let (width, height) = drawable_size(dpy, drawable);
+                Arc::new(Buffers {
+                    framebuffer: Arc::new(Mutex::new(Framebuffer::new(width, height))),
+                    presenter: Mutex::new(None),
+                })
+            })
+            .clone();
+        let surface = Surface { drawable, buffers };
+        surface.update_size(dpy);
+        surface
+    }
+
+    /// Resize the buffers to match the drawable, if it was resized.
+    unsafe fn update_size(&self, dpy: *mut Display) {
+        let (width, height) = drawable_size(dpy, self.drawable);
+        let mut framebuffer = self.buffers.framebuffer.lock().unwrap();
+        if (framebuffer.width, framebuffer.height) != (width, height) {
+            // NOTE(eddyb) the contents of resized buffers are undefined.
+            *framebuffer = Framebuffer::new(width, height);
+        }
+    }
+
+    fn destroy(drawable: x11_dl::glx::GLXDrawable) {
+        BUFFERS.lock().unwrap().remove(&drawable);
+    }
+
+    /// Copy the back color buffer to the drawable.
+    unsafe fn present(&self, dpy: *mut Display) {
+        let framebuffer = self.buffers.framebuffer.lock().unwrap();
+        let mut presenter = self.buffers.presenter.lock().unwrap();
+        if !matches!(&*presenter, Some(p) if p.is_compatible(dpy, &framebuffer)) {
+            // NOTE(eddyb) the old `Presenter` (if any) is dropped first.
+            *presenter = None;
+            *presenter = present::Presenter::new(dpy, framebuffer.width, framebuffer.height);
+        }
+        match &mut *presenter {
+            Some(presenter) => presenter.present(self.drawable, &framebuffer),
+            None => eprintln!("glOOF: no suitable visual to present with"),
+        }
+    }
 }
@@ -0,0 +1,458 @@
+//! Presenting the color buffer of a `Framebuffer` to an X11 drawable, through
+//! an `XImage`, shared with the X server via MIT-SHM, whenever possible.
+
+use super::XLIB;
+use crate::gl::raster::Framebuffer;
+use lazy_static::lazy_static;
+use libc::{c_char, c_int, c_uint, c_ulong};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::{mem, ptr};
+use x11_dl::xlib::{
+    Bool, Display, Drawable, False, LSBFirst, Visual, XErrorEvent, XImage, ZPixmap, GC,
+};
+
+#[allow(non_camel_case_types)]
+type ShmSeg = c_ulong;
+
+#[allow(non_snake_case)]
+#[repr(C)]
+struct XShmSegmentInfo {
+    shmseg: ShmSeg,
+    shmid: c_int,
+    shmaddr: *mut c_char,
+    readOnly: Bool,
+}
+
+macro_rules! dynamic_library {
+    ($lib:ident in $soname:literal {
+        $(fn $name:ident($($arg:ty),*) -> $ret:ty;)*
+    }) => {
+        #[allow(non_snake_case)]
+        struct $lib {
+            $($name: unsafe extern "C" fn($($arg),*) -> $ret,)*
+        }
+
+        impl $lib {
+            fn open() -> Option<Self> {
+                unsafe {
+                    let soname = concat!($soname, "\0").as_ptr() as *const c_char;
+                    let lib = libc::dlopen(soname, libc::RTLD_LAZY);
+                    if lib.is_null() {
+                        return None;
+                    }
+                    Some($lib {
+                        $($name: {
+                            let name = concat!(stringify!($name), "\0");
+                            let sym = libc::dlsym(lib, name.as_ptr() as *const c_char);
+                            if sym.is_null() {
+                                return None;
+                            }
+                            mem::transmute::<
+                                *mut libc::c_void,
+                                unsafe extern "C" fn($($arg),*) -> $ret,
+                            >(sym)
+                        },)*
+                    })
+                }
+            }
+        }
+    };
+}
+
+// Entry points of the MIT-SHM extension, which `x11-dl` doesn't provide.
+dynamic_library! {
+    XShm in "libXext.so.6" {
+        fn XShmQueryExtension(*mut Display) -> Bool;
+        fn XShmAttach(*mut Display, *mut XShmSegmentInfo) -> Bool;
+        fn XShmDetach(*mut Display, *mut XShmSegmentInfo) -> Bool;
+        fn XShmCreateImage(
+            *mut Display,
+            *mut Visual,
+            c_uint,
+            c_int,
+            *mut c_char,
+            *mut XShmSegmentInfo,
+            c_uint,
+            c_uint
+        ) -> *mut XImage;
+        fn XShmPutImage(
+            *mut Display,
+            Drawable,
+            GC,
+            *mut XImage,
+            c_int,
+            c_int,
+            c_int,
+            c_int,
+            c_uint,
+            c_uint,
+            Bool
+        ) -> Bool;
+    }
+}
+
+lazy_static! {
+    static ref XSHM: Option<XShm> = XShm::open();
+}
+
+/// How a single color channel is encoded in a pixel value.
+#[derive(Copy, Clone, Debug)]
+struct Channel {
+    shift: u32,
+    max: u32,
+}
+
+impl Channel {
+    fn from_mask(mask: c_ulong) -> Self {
+        let mask = mask as u32;
+        let shift = mask.trailing_zeros() % 32;
+        Channel {
+            shift,
+            max: mask >> shift,
+        }
+    }
+
+    fn encode(self, c: u8) -> u32 {
+        ((c as u32 * self.max + 127) / 255) << self.shift
+    }
+}
+
+/// Pixel layout of an `XImage`, as determined by its visual and the display.
+#[derive(Copy, Clone, Debug)]
+struct PixelFormat {
+    /// Size of each pixel in bytes, or `None` if pixels aren't whole bytes
+    /// (or are larger than 32 bits), in which case `XPutPixel` is used.
+    bytes_per_pixel: Option<usize>,
+    lsb_first: bool,
+    rgb: [Channel; 3],
+}
+
+impl PixelFormat {
+    /// The pixel value for `color`, combining its channels by the visual's masks.
+    fn pixel(&self, [r, g, b, _]: [u8; 4]) -> u32 {
+        let [cr, cg, cb] = self.rgb;
+        cr.encode(r) | cg.encode(g) | cb.encode(b)
+    }
+
+    /// Write the pixel value for `color` to `out` (which must have exactly
+    /// `bytes_per_pixel` bytes), in the image's byte order.
+    fn encode(&self, color: [u8; 4], out: &mut [u8]) {
+        let pixel = self.pixel(color);
+        if self.lsb_first {
+            out.copy_from_slice(&pixel.to_le_bytes()[..out.len()]);
+        } else {
+            out.copy_from_slice(&pixel.to_be_bytes()[4 - out.len()..]);
+        }
+    }
+}
+
+static SHM_ATTACH_FAILED: AtomicBool = AtomicBool::new(false);
+
+unsafe extern "C" fn shm_attach_error_handler(_: *mut Display, _: *mut XErrorEvent) -> c_int {
+    SHM_ATTACH_FAILED.store(true, Ordering::SeqCst);
+    0
+}
+
+/// An `XImage` matching the size of a `Framebuffer`, and the storage for its
+/// pixels, which is shared with the X server, if MIT-SHM is available.
+pub struct Presenter {
+    dpy: *mut Display,
+    width: u32,
+    height: u32,
+
+    image: *mut XImage,
+    format: PixelFormat,
+
+    /// Shared memory segment holding the pixels of `image`, if using MIT-SHM.
+    // NOTE(eddyb) boxed because `image` keeps a pointer to it.
+    shm: Option<Box<XShmSegmentInfo>>,
+
+    /// Storage for the pixels of `image`, if not using MIT-SHM.
+    data: Vec<u8>,
+}
+
+// HACK(eddyb) the raw pointers make `Presenter` `!Send` by default, but
+// they're only used while the drawable is being presented to (or destroyed),
+// which is already serialized by the `Mutex` around the `Presenter`.
+unsafe impl Send for Presenter {}
+
+impl Presenter {
+    /// Create a `Presenter` for the visual returned by `glXChooseVisual`, or
+    /// `None` if there is no such visual (see `api::default_visual_info`).
+    pub unsafe fn new(dpy: *mut Display, width: u32, height: u32) -> Option<Self> {
+        let visual_info = super::api::default_visual_info(dpy);
+        if visual_info.is_null() {
+            return None;
+        }
+        let visual_info = {
+            let ptr = visual_info;
+            let visual_info = ptr.read();
+            libc::free(ptr as *mut _);
+            visual_info
+        };
+        // NOTE(eddyb) only visuals where pixel values are made of separate
+        // RGB channels (i.e. their masks) are ever returned by `glXChooseVisual`.
+        if !super::api::is_supported_visual(&visual_info) {
+            return None;
+        }
+
+        let (image, shm) = match Self::create_shm_image(dpy, &visual_info, width, height) {
+            Some((image, shm)) => (image, Some(shm)),
+            None => {
+                let image = (XLIB.XCreateImage)(
+                    dpy,
+                    visual_info.visual,
+                    visual_info.depth as c_uint,
+                    ZPixmap,
+                    0,
+                    ptr::null_mut(),
+                    width,
+                    height,
+                    32,
+                    0,
+                );
+                if image.is_null() {
+                    return None;
+                }
+                (image, None)
+            }
+        };
+
+        let bits_per_pixel = (*image).bits_per_pixel;
+        let format = PixelFormat {
+            bytes_per_pixel: match bits_per_pixel {
+                8 | 16 | 24 | 32 => Some(bits_per_pixel as usize / 8),
+                _ => None,
+            },
+            lsb_first: (*image).byte_order == LSBFirst,
+            rgb: [
+                Channel::from_mask(visual_info.red_mask),
+                Channel::from_mask(visual_info.green_mask),
+                Channel::from_mask(visual_info.blue_mask),
+            ],
+        };
+
+        let mut data = vec![];
+        if shm.is_none() {
+            data = vec![0; (*image).bytes_per_line as usize * height as usize];
+            (*image).data = data.as_mut_ptr() as *mut c_char;
+        }
+
+        Some(Presenter {
+            dpy,
+            width,
+            height,
+            image,
+            format,
+            shm,
+            data,
+        })
+    }
+
+    unsafe fn create_shm_image(
+        dpy: *mut Display,
+        visual_info: &x11_dl::xlib::XVisualInfo,
+        width: u32,
+        height: u32,
+    ) -> Option<(*mut XImage, Box<XShmSegmentInfo>)> {
+        let xshm = XSHM.as_ref()?;
+        if (xshm.XShmQueryExtension)(dpy) == False {
+            return None;
+        }
+
+        let mut shm = Box::new(XShmSegmentInfo {
+            shmseg: 0,
+            shmid: -1,
+            shmaddr: ptr::null_mut(),
+            readOnly: False,
+        });
+        let image = (xshm.XShmCreateImage)(
+            dpy,
+            visual_info.visual,
+            visual_info.depth as c_uint,
+            ZPixmap,
+            ptr::null_mut(),
+            &mut *shm,
+            width,
+            height,
+        );
+        if image.is_null() {
+            return None;
+        }
+
+        let size = (*image).bytes_per_line as usize * height as usize;
+        shm.shmid = libc::shmget(libc::IPC_PRIVATE, size, libc::IPC_CREAT | 0o600);
+        if shm.shmid < 0 {
+            (XLIB.XDestroyImage)(image);
+            return None;
+        }
+        shm.shmaddr = libc::shmat(shm.shmid, ptr::null(), 0) as *mut c_char;
+        if shm.shmaddr as isize == -1 {
+            libc::shmctl(shm.shmid, libc::IPC_RMID, ptr::null_mut());
+            (XLIB.XDestroyImage)(image);
+            return None;
+        }
+        (*image).data = shm.shmaddr;
+
+        // NOTE(eddyb) attaching fails (asynchronously) when the X server can't
+        // access our shared memory (e.g. it's remote), and the default error
+        // handler would exit the process, so a temporary one is used instead.
+        SHM_ATTACH_FAILED.store(false, Ordering::SeqCst);
+        let old_handler = (XLIB.XSetErrorHandler)(Some(shm_attach_error_handler));
+        (xshm.XShmAttach)(dpy, &mut *shm);
+        (XLIB.XSync)(dpy, False);
+        (XLIB.XSetErrorHandler)(old_handler);
+
+        // NOTE(eddyb) the segment will be destroyed once it's fully detached.
+        libc::shmctl(shm.shmid, libc::IPC_RMID, ptr::null_mut());
+
+        if SHM_ATTACH_FAILED.load(Ordering::SeqCst) {
+            libc::shmdt(shm.shmaddr as *const _);
+            (*image).data = ptr::null_mut();
+            (XLIB.XDestroyImage)(image);
+            return None;
+        }
+
+        Some((image, shm))
+    }
+
+    /// Whether this `Presenter` can be used for `framebuffer` on `dpy`.
+    pub fn is_compatible(&self, dpy: *mut Display, framebuffer: &Framebuffer) -> bool {
+        self.dpy == dpy && (self.width, self.height) == (framebuffer.width, framebuffer.height)
+    }
+
+    pub unsafe fn present(&mut self, drawable: Drawable, framebuffer: &Framebuffer) {
+        assert!(self.is_compatible(self.dpy, framebuffer));
+
+        let bytes_per_line = (*self.image).bytes_per_line as usize;
+        let format = self.format;
+        let pixels = match &self.shm {
+            Some(shm) => std::slice::from_raw_parts_mut(
+                shm.shmaddr as *mut u8,
+                bytes_per_line * self.height as usize,
+            ),
+            None => &mut self.data[..],
+        };
+
+        // NOTE(eddyb) `framebuffer` rows are bottom-to-top, unlike `XImage`'s.
+        let rows = framebuffer.color.chunks(self.width as usize).rev();
+        match format.bytes_per_pixel {
+            Some(bytes_per_pixel) => {
+                for (src, dst) in rows.zip(pixels.chunks_mut(bytes_per_line)) {
+                    let dst = dst.chunks_exact_mut(bytes_per_pixel);
+                    for (&color, out) in src.iter().zip(dst) {
+                        format.encode(color, out);
+                    }
+                }
+            }
+            // NOTE(eddyb) `XPutPixel` is much slower, but it handles packing
+            // pixels of any size, in the image's byte and bit order.
+            None => {
+                for (y, src) in rows.enumerate() {
+                    for (x, &color) in src.iter().enumerate() {
+                        let pixel = format.pixel(color) as c_ulong;
+                        (XLIB.XPutPixel)(self.image, x as c_int, y as c_int, pixel);
+                    }
+                }
+            }
+        }
+
+        let gc = (XLIB.XCreateGC)(self.dpy, drawable, 0, ptr::null_mut());
+        match (&self.shm, XSHM.as_ref()) {
+            (Some(_), Some(xshm)) => {
+                (xshm.XShmPutImage)(
+                    self.dpy,
+                    drawable,
+                    gc,
+                    self.image,
+                    0,
+                    0,
+                    0,
+                    0,
+                    self.width,
+                    self.height,
+                    False,
+                );
+            }
+            _ => {
+                (XLIB.XPutImage)(
+                    self.dpy,
+                    drawable,
+                    gc,
+                    self.image,
+                    0,
+                    0,
+                    0,
+                    0,
+                    self.width,
+                    self.height,
+                );
+            }
+        }
+        (XLIB.XFreeGC)(self.dpy, gc);
+
+        // NOTE(eddyb) with MIT-SHM, the X server reads the pixels directly from
+        // shared memory, so it has to be done before they can be overwritten.
+        if self.shm.is_some() {
+            (XLIB.XSync)(self.dpy, False);
+        } else {
+            (XLIB.XFlush)(self.dpy);
+        }
+    }
+}
+
+impl Drop for Presenter {
+    fn drop(&mut self) {
+        unsafe {
+            if let (Some(shm), Some(xshm)) = (&mut self.shm, XSHM.as_ref()) {
+                (xshm.XShmDetach)(self.dpy, &mut **shm);
+                (XLIB.XSync)(self.dpy, False);
+                libc::shmdt(shm.shmaddr as *const _);
+            }
+
+            // NOTE(eddyb) `XDestroyImage` would otherwise try to free the pixels.
+            (*self.image).data = ptr::null_mut();
+            (XLIB.XDestroyImage)(self.image);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pixel_formats() {
+        let rgb565 = PixelFormat {
+            bytes_per_pixel: Some(2),
+            lsb_first: true,
+            rgb: [0xf800, 0x07e0, 0x001f].map(Channel::from_mask),
+        };
+        let mut out = [0; 2];
+        rgb565.encode([255, 128, 0, 0], &mut out);
+        assert_eq!(u16::from_le_bytes(out), 0xfc00);
+
+        let bgr888 = PixelFormat {
+            bytes_per_pixel: Some(3),
+            lsb_first: false,
+            rgb: [0x0000ff, 0x00ff00, 0xff0000].map(Channel::from_mask),
+        };
+        let mut out = [0; 3];
+        bgr888.encode([1, 2, 3, 4], &mut out);
+        assert_eq!(out, [3, 2, 1]);
+
+        // Channels narrower or wider than 8 bits are scaled to fit.
+        let rgb555 = PixelFormat {
+            bytes_per_pixel: None,
+            lsb_first: true,
+            rgb: [0x7c00, 0x03e0, 0x001f].map(Channel::from_mask),
+        };
+        assert_eq!(rgb555.pixel([255, 128, 0, 0]), 0x7e00);
+        let rgb101010 = PixelFormat {
+            bytes_per_pixel: Some(4),
+            lsb_first: true,
+            rgb: [0x3ff0_0000, 0x000f_fc00, 0x0000_03ff].map(Channel::from_mask),
+        };
+        assert_eq!(rgb101010.pixel([255, 0, 128, 0]), 0x3ff0_0202);
+    }
+}
@@ -36,7 +36,7 @@ fn main() {
     let exe = args.next().unwrap();
     let program = args
         .next()
-        .expect(&format!("Usage: {} <program>", exe.to_string_lossy()));
+        .unwrap_or_else(|| panic!("Usage: {} <program>", exe.to_string_lossy()));
 
     // FIXME(eddyb) try to get this working cross-platform.
     let gloof = find_gloof_dylib();